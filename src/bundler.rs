@@ -0,0 +1,798 @@
+//! Construction of the `bun build` invocation, and of the esbuild one used
+//! where Bun isn't installed.
+
+use lambda_http::tracing;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Once;
+
+/// How Bun compiles JSX.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum JsxRuntime {
+    /// `react/jsx-runtime` is imported automatically.
+    #[default]
+    Automatic,
+    /// JSX compiles to `React.createElement`, so `React` must be in scope.
+    Classic,
+}
+
+impl JsxRuntime {
+    fn as_arg(self) -> &'static str {
+        match self {
+            JsxRuntime::Automatic => "automatic",
+            JsxRuntime::Classic => "classic",
+        }
+    }
+}
+
+/// Which React build the bundle uses, chosen through `process.env.NODE_ENV`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ReactMode {
+    /// Extra warnings and checks, at the cost of size and speed.
+    Development,
+    #[default]
+    Production,
+}
+
+impl ReactMode {
+    fn node_env(self) -> &'static str {
+        match self {
+            ReactMode::Development => "\"development\"",
+            ReactMode::Production => "\"production\"",
+        }
+    }
+}
+
+const NODE_ENV_DEFINE: &str = "process.env.NODE_ENV";
+
+/// Module format of the emitted JavaScript.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum OutputFormat {
+    Esm,
+    Cjs,
+    /// A self-executing script for a plain `<script>` tag.
+    Iife,
+}
+
+impl OutputFormat {
+    pub(crate) fn as_arg(self) -> &'static str {
+        match self {
+            OutputFormat::Esm => "esm",
+            OutputFormat::Cjs => "cjs",
+            OutputFormat::Iife => "iife",
+        }
+    }
+
+    /// Name of the bundle built in this format for a library build, e.g.
+    /// `index.cjs.js`.
+    pub(crate) fn file_name(self) -> String {
+        format!("index.{}.js", self.as_arg())
+    }
+}
+
+/// Everything that varies between `bun build` runs.
+#[derive(Debug, Default)]
+pub(crate) struct BunBuildOptions<'a> {
+    /// Entry points relative to the workspace; [`DEFAULT_ENTRY_POINT`] if empty.
+    pub entry_points: &'a [String],
+    /// Raw flags appended after the modelled ones, already validated with
+    /// [`validate_extra_arg`].
+    pub extra_args: &'a [String],
+    /// Prefix Bun puts in front of asset and chunk URLs it emits.
+    pub public_path: Option<&'a str>,
+    /// File extension (with or without the dot) to Bun loader name, checked
+    /// by [`validate_loader`].
+    pub loaders: Option<&'a HashMap<String, String>>,
+    pub jsx_runtime: JsxRuntime,
+    /// Package the automatic runtime imports `jsx-runtime` from instead of
+    /// `react`, checked by [`validate_jsx_import_source`].
+    pub jsx_import_source: Option<&'a str>,
+    /// Bare specifiers left as imports for the page to resolve (e.g. through
+    /// an import map) instead of being bundled.
+    pub externals: &'a [&'a str],
+    /// Where Bun writes its build metadata (inputs, outputs and their sizes),
+    /// relative to the workspace.
+    pub metafile: Option<&'a str>,
+    /// Split code shared between entry points (and dynamic imports) into
+    /// chunks the entry modules import.
+    pub splitting: bool,
+    /// Keep function and class names through minification, so stack traces
+    /// and component names in devtools stay readable.
+    pub keep_names: bool,
+    /// Identifiers replaced at build time, checked by [`validate_define`].
+    pub defines: Option<&'a HashMap<String, String>>,
+    /// Sets `process.env.NODE_ENV` unless `defines` already does.
+    pub react_mode: ReactMode,
+    /// Module format; the bundler's own default (ESM) when unset.
+    pub format: Option<OutputFormat>,
+    /// Output directory relative to the workspace; [`DEFAULT_OUTDIR`] if unset.
+    pub outdir: Option<&'a str>,
+}
+
+/// How many `define` entries a request may carry.
+pub(crate) const MAX_DEFINES: usize = 50;
+
+const MAX_DEFINE_VALUE_LEN: usize = 1024;
+
+/// Output directory location of the metafile requested with `stats`.
+pub(crate) const STATS_FILE: &str = "stats.json";
+
+/// Every specifier a React entry point and the automatic JSX runtime import,
+/// which must all resolve to one shared copy for previews to share React.
+pub(crate) const SHARED_REACT_MODULES: &[&str] = &[
+    "react",
+    "react/jsx-runtime",
+    "react/jsx-dev-runtime",
+    "react-dom",
+    "react-dom/client",
+];
+
+/// Loader names `bun build --loader` understands.
+const LOADERS: &[&str] = &[
+    "js", "jsx", "ts", "tsx", "json", "toml", "text", "file", "napi", "wasm", "css",
+];
+
+pub(crate) const DEFAULT_ENTRY_POINT: &str = "./src/index.tsx";
+
+pub(crate) const DEFAULT_OUTDIR: &str = "./dist";
+
+impl BunBuildOptions<'_> {
+    pub(crate) fn args(&self) -> Vec<String> {
+        let mut args = vec!["build".to_string()];
+        args.extend(self.entry_point_args());
+
+        args.extend(
+            ["--outdir", self.outdir(), "--target", "browser"]
+                .into_iter()
+                .map(String::from),
+        );
+
+        args.push("--jsx-runtime".to_string());
+        args.push(self.jsx_runtime.as_arg().to_string());
+
+        if let Some(import_source) = self.jsx_import_source {
+            args.push("--jsx-import-source".to_string());
+            args.push(import_source.to_string());
+        }
+
+        if let Some(public_path) = self.public_path {
+            args.push("--public-path".to_string());
+            args.push(public_path.to_string());
+        }
+
+        // Chunks are loaded with `import`, which only ESM output supports.
+        if self.splitting {
+            args.extend(
+                ["--splitting", "--format", "esm"]
+                    .into_iter()
+                    .map(String::from),
+            );
+        } else if let Some(format) = self.format {
+            args.push("--format".to_string());
+            args.push(format.as_arg().to_string());
+        }
+
+        if self.keep_names {
+            args.push("--keep-names".to_string());
+        }
+
+        if let Some(metafile) = self.metafile {
+            args.push(format!("--metafile={}", metafile));
+        }
+
+        // Each definition is one argv element, so a value can never be read
+        // as a flag of its own.
+        for (key, value) in self.sorted_defines() {
+            args.push("--define".to_string());
+            args.push(format!("{}:{}", key, value));
+        }
+
+        for external in self.externals {
+            args.push("--external".to_string());
+            args.push(external.to_string());
+        }
+
+        for (extension, loader) in self.sorted_loaders() {
+            args.push("--loader".to_string());
+            args.push(format!(".{}:{}", extension, loader));
+        }
+
+        args.extend(self.extra_args.iter().cloned());
+        args
+    }
+
+    fn outdir(&self) -> &str {
+        self.outdir.unwrap_or(DEFAULT_OUTDIR)
+    }
+
+    fn entry_point_args(&self) -> Vec<String> {
+        if self.entry_points.is_empty() {
+            vec![DEFAULT_ENTRY_POINT.to_string()]
+        } else {
+            self.entry_points.to_vec()
+        }
+    }
+
+    /// `defines` plus `NODE_ENV` from `react_mode`, sorted by key.
+    fn sorted_defines(&self) -> Vec<(&str, &str)> {
+        let mut defines: Vec<(&str, &str)> = self
+            .defines
+            .into_iter()
+            .flatten()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect();
+        if !defines.iter().any(|(key, _)| *key == NODE_ENV_DEFINE) {
+            defines.push((NODE_ENV_DEFINE, self.react_mode.node_env()));
+        }
+        defines.sort();
+        defines
+    }
+
+    /// Loaders keyed by extension without the dot, sorted.
+    fn sorted_loaders(&self) -> Vec<(&str, &str)> {
+        let mut loaders: Vec<(&str, &str)> = self
+            .loaders
+            .into_iter()
+            .flatten()
+            .map(|(extension, loader)| (extension.trim_start_matches('.'), loader.as_str()))
+            .collect();
+        loaders.sort();
+        loaders
+    }
+}
+
+/// A tool that can turn [`BunBuildOptions`] into the bundle in `./dist`.
+pub(crate) trait Bundler {
+    /// Names the step in logs and error messages.
+    fn step_name(&self) -> &'static str;
+    /// Names the tool in build reports.
+    fn tool_name(&self) -> &'static str;
+    /// Whether this is Bun itself, which the install, prebuild and lint
+    /// steps also need.
+    fn is_bun(&self) -> bool;
+    fn program(&self) -> &Path;
+    /// Arguments to `program` that run a package's executable, as `bun x`
+    /// and `npx --yes` do; the Tailwind CLI runs this way.
+    fn exec_args(&self) -> Vec<String>;
+    fn args(&self, options: &BunBuildOptions) -> Vec<String>;
+    /// Arguments to `program` that print the tool's version.
+    fn version_args(&self) -> Vec<String>;
+}
+
+pub(crate) struct Bun {
+    pub bin: PathBuf,
+}
+
+impl Bundler for Bun {
+    fn step_name(&self) -> &'static str {
+        "Bun build"
+    }
+
+    fn tool_name(&self) -> &'static str {
+        "bun"
+    }
+
+    fn is_bun(&self) -> bool {
+        true
+    }
+
+    fn program(&self) -> &Path {
+        &self.bin
+    }
+
+    fn exec_args(&self) -> Vec<String> {
+        vec!["x".to_string()]
+    }
+
+    fn args(&self, options: &BunBuildOptions) -> Vec<String> {
+        options.args()
+    }
+
+    fn version_args(&self) -> Vec<String> {
+        vec!["--version".to_string()]
+    }
+}
+
+/// esbuild run through `npx`, for machines without Bun such as a developer's
+/// laptop. `extra_args` are Bun flags, so the handler refuses requests that
+/// set them rather than build without them.
+pub(crate) struct Esbuild {
+    pub npx: PathBuf,
+}
+
+impl Bundler for Esbuild {
+    fn step_name(&self) -> &'static str {
+        "esbuild build"
+    }
+
+    fn tool_name(&self) -> &'static str {
+        "esbuild"
+    }
+
+    fn is_bun(&self) -> bool {
+        false
+    }
+
+    fn program(&self) -> &Path {
+        &self.npx
+    }
+
+    fn exec_args(&self) -> Vec<String> {
+        vec!["--yes".to_string()]
+    }
+
+    fn version_args(&self) -> Vec<String> {
+        ["--yes", "esbuild", "--version"]
+            .into_iter()
+            .map(String::from)
+            .collect()
+    }
+
+    fn args(&self, options: &BunBuildOptions) -> Vec<String> {
+        let mut args = self.exec_args();
+        args.push("esbuild".to_string());
+        args.extend(options.entry_point_args());
+        args.extend([
+            "--bundle".to_string(),
+            format!("--outdir={}", options.outdir()),
+        ]);
+        args.push("--platform=browser".to_string());
+        args.push(format!(
+            "--format={}",
+            options.format.unwrap_or(OutputFormat::Esm).as_arg()
+        ));
+
+        args.push(match options.jsx_runtime {
+            JsxRuntime::Automatic => "--jsx=automatic".to_string(),
+            JsxRuntime::Classic => "--jsx=transform".to_string(),
+        });
+        if let Some(import_source) = options.jsx_import_source {
+            args.push(format!("--jsx-import-source={}", import_source));
+        }
+        if let Some(public_path) = options.public_path {
+            args.push(format!("--public-path={}", public_path));
+        }
+        if options.splitting {
+            args.push("--splitting".to_string());
+        }
+        if options.keep_names {
+            args.push("--keep-names".to_string());
+        }
+        if let Some(metafile) = options.metafile {
+            args.push(format!("--metafile={}", metafile));
+        }
+
+        for (key, value) in options.sorted_defines() {
+            args.push(format!("--define:{}={}", key, value));
+        }
+        for external in options.externals {
+            args.push(format!("--external:{}", external));
+        }
+        for (extension, loader) in options.sorted_loaders() {
+            args.push(format!("--loader:.{}={}", extension, loader));
+        }
+
+        args
+    }
+}
+
+/// Bun when its binary is installed at `bun_bin`, esbuild through `npx`
+/// otherwise.
+pub(crate) fn select_bundler(bun_bin: &Path, npx: &Path) -> Box<dyn Bundler + Send + Sync> {
+    if bun_bin.is_file() {
+        Box::new(Bun {
+            bin: bun_bin.to_path_buf(),
+        })
+    } else {
+        // Checked on every request, but the machine won't change under us.
+        static WARNED: Once = Once::new();
+        WARNED.call_once(
+            || tracing::warn!(bun = %bun_bin.display(), "Bun not found; bundling with esbuild"),
+        );
+        Box::new(Esbuild {
+            npx: npx.to_path_buf(),
+        })
+    }
+}
+
+pub(crate) fn validate_loader(extension: &str, loader: &str) -> Result<(), String> {
+    let bare = extension.strip_prefix('.').unwrap_or(extension);
+    let valid_extension =
+        !bare.is_empty() && bare.len() <= 16 && bare.chars().all(|c| c.is_ascii_alphanumeric());
+    if !valid_extension {
+        return Err(format!("invalid loader extension: {}", extension));
+    }
+
+    if !LOADERS.contains(&loader) {
+        return Err(format!("unknown loader for {}: {}", extension, loader));
+    }
+
+    Ok(())
+}
+
+/// Keys must be dotted identifiers such as `process.env.API_URL`; values are
+/// expressions and may contain anything except NUL and line breaks.
+pub(crate) fn validate_define(key: &str, value: &str) -> Result<(), String> {
+    let valid_key = !key.is_empty()
+        && key.len() <= 128
+        && key.split('.').all(|part| {
+            part.chars()
+                .next()
+                .is_some_and(|c| c.is_ascii_alphabetic() || matches!(c, '_' | '$'))
+                && part
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '$'))
+        });
+    if !valid_key {
+        return Err(format!("invalid define key: {}", key));
+    }
+
+    if value.len() > MAX_DEFINE_VALUE_LEN || value.contains(['\0', '\n', '\r']) {
+        return Err(format!("invalid value for define {}", key));
+    }
+
+    Ok(())
+}
+
+/// Import sources are npm package names, e.g. `@emotion/react` or `preact`.
+pub(crate) fn validate_jsx_import_source(source: &str) -> Result<(), String> {
+    let valid_part = |part: &str| {
+        !part.is_empty()
+            && !part.starts_with(['.', '_'])
+            && part.chars().all(|c| {
+                c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '-' | '.' | '_')
+            })
+    };
+
+    let valid = source.len() <= 214
+        && match source.strip_prefix('@') {
+            Some(scoped) => scoped
+                .split_once('/')
+                .is_some_and(|(scope, name)| valid_part(scope) && valid_part(name)),
+            None => valid_part(source),
+        };
+    if !valid {
+        return Err(format!("invalid jsx_import_source: {}", source));
+    }
+
+    Ok(())
+}
+
+/// Flags that would move the output away from where the upload step reads it.
+const RESERVED_FLAGS: &[&str] = &["--outdir", "--outfile"];
+
+/// Extra arguments must be `--flag` or `--flag=value`, so nothing can be
+/// smuggled in as a positional entry point or a short option.
+pub(crate) fn validate_extra_arg(arg: &str) -> Result<(), String> {
+    if !arg.starts_with("--") || arg.len() == 2 {
+        return Err(format!("bun_args entries must start with --: {}", arg));
+    }
+
+    let flag = arg.split('=').next().unwrap_or(arg);
+    if RESERVED_FLAGS.contains(&flag) {
+        return Err(format!("bun_args may not override {}", flag));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extra_args_are_appended() {
+        let extra = vec!["--splitting".to_string(), "--public-path=/cdn/".to_string()];
+        let options = BunBuildOptions {
+            extra_args: &extra,
+            ..Default::default()
+        };
+
+        let args = options.args();
+        assert_eq!(&args[..2], ["build", "./src/index.tsx"]);
+        assert_eq!(
+            &args[args.len() - 2..],
+            ["--splitting", "--public-path=/cdn/"]
+        );
+    }
+
+    #[test]
+    fn test_multiple_entry_points() {
+        let entry_points = vec!["./src/index.tsx".to_string(), "./src/Card.tsx".to_string()];
+        let options = BunBuildOptions {
+            entry_points: &entry_points,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            &options.args()[..4],
+            ["build", "./src/index.tsx", "./src/Card.tsx", "--outdir"]
+        );
+    }
+
+    #[test]
+    fn test_public_path_arg() {
+        let options = BunBuildOptions {
+            public_path: Some("/previews/abc/"),
+            ..Default::default()
+        };
+
+        let args = options.args();
+        let at = args.iter().position(|a| a == "--public-path").unwrap();
+        assert_eq!(args[at + 1], "/previews/abc/");
+    }
+
+    #[test]
+    fn test_loader_args() {
+        let loaders = HashMap::from([
+            ("svg".to_string(), "file".to_string()),
+            (".graphql".to_string(), "text".to_string()),
+        ]);
+        let options = BunBuildOptions {
+            loaders: Some(&loaders),
+            ..Default::default()
+        };
+
+        let args = options.args();
+        let at = args.iter().position(|a| a == "--loader").unwrap();
+        assert_eq!(
+            &args[at..at + 4],
+            ["--loader", ".graphql:text", "--loader", ".svg:file"]
+        );
+    }
+
+    #[test]
+    fn test_jsx_runtime_arg() {
+        let runtime_arg = |jsx_runtime| {
+            let args = BunBuildOptions {
+                jsx_runtime,
+                ..Default::default()
+            }
+            .args();
+            let at = args.iter().position(|a| a == "--jsx-runtime").unwrap();
+            args[at + 1].clone()
+        };
+
+        assert_eq!(runtime_arg(JsxRuntime::default()), "automatic");
+        assert_eq!(runtime_arg(JsxRuntime::Classic), "classic");
+    }
+
+    #[test]
+    fn test_shared_react_is_external() {
+        let args = BunBuildOptions {
+            externals: SHARED_REACT_MODULES,
+            ..Default::default()
+        }
+        .args();
+
+        let externals: Vec<_> = args
+            .windows(2)
+            .filter(|pair| pair[0] == "--external")
+            .map(|pair| pair[1].as_str())
+            .collect();
+        assert_eq!(externals, SHARED_REACT_MODULES);
+    }
+
+    #[test]
+    fn test_metafile_arg() {
+        let args = BunBuildOptions {
+            metafile: Some("./dist/stats.json"),
+            ..Default::default()
+        }
+        .args();
+
+        assert!(args.contains(&"--metafile=./dist/stats.json".to_string()));
+        assert!(!BunBuildOptions::default()
+            .args()
+            .iter()
+            .any(|arg| arg.starts_with("--metafile")));
+    }
+
+    #[test]
+    fn test_splitting_emits_esm() {
+        let args = BunBuildOptions {
+            splitting: true,
+            ..Default::default()
+        }
+        .args();
+
+        let at = args.iter().position(|a| a == "--splitting").unwrap();
+        assert_eq!(&args[at..at + 3], ["--splitting", "--format", "esm"]);
+    }
+
+    #[test]
+    fn test_format_and_outdir_args() {
+        let options = BunBuildOptions {
+            format: Some(OutputFormat::Cjs),
+            outdir: Some("./.formats/cjs"),
+            ..Default::default()
+        };
+
+        let args = options.args();
+        let at = args.iter().position(|arg| arg == "--outdir").unwrap();
+        assert_eq!(args[at + 1], "./.formats/cjs");
+        let at = args.iter().position(|arg| arg == "--format").unwrap();
+        assert_eq!(args[at + 1], "cjs");
+        let esbuild = Esbuild {
+            npx: PathBuf::from("npx"),
+        };
+        assert!(esbuild.args(&options).contains(&"--format=cjs".to_string()));
+        assert_eq!(OutputFormat::Iife.file_name(), "index.iife.js");
+    }
+
+    #[test]
+    fn test_define_value_stays_one_argument() {
+        let defines = HashMap::from([(
+            "process.env.API_URL".to_string(),
+            r#""x" --outdir=/var/task"#.to_string(),
+        )]);
+        let args = BunBuildOptions {
+            defines: Some(&defines),
+            ..Default::default()
+        }
+        .args();
+
+        let at = args.iter().position(|a| a == "--define").unwrap();
+        assert_eq!(
+            args[at + 1],
+            r#"process.env.API_URL:"x" --outdir=/var/task"#
+        );
+        assert!(!args.iter().any(|a| a == "--outdir=/var/task"));
+    }
+
+    #[test]
+    fn test_define_validation() {
+        assert!(validate_define("process.env.NODE_ENV", r#""production""#).is_ok());
+        assert!(validate_define("__DEV__", "false").is_ok());
+        assert!(validate_define("process.env.X", "1\n--minify").is_err());
+        assert!(validate_define("process.env.X", "a\0b").is_err());
+        assert!(validate_define("--outdir", "x").is_err());
+        assert!(validate_define("a..b", "x").is_err());
+    }
+
+    #[test]
+    fn test_loader_validation() {
+        assert!(validate_loader("svg", "file").is_ok());
+        assert!(validate_loader(".md", "text").is_ok());
+        assert!(validate_loader("svg", "shell").is_err());
+        assert!(validate_loader("s:vg", "file").is_err());
+        assert!(validate_loader(".", "file").is_err());
+    }
+
+    #[test]
+    fn test_extra_arg_validation() {
+        assert!(validate_extra_arg("--splitting").is_ok());
+        assert!(validate_extra_arg("--minify-syntax").is_ok());
+        assert!(validate_extra_arg("-e").is_err());
+        assert!(validate_extra_arg("./other-entry.ts").is_err());
+        assert!(validate_extra_arg("--").is_err());
+        assert!(validate_extra_arg("--outdir=/var/task").is_err());
+    }
+
+    #[test]
+    fn test_keep_names_arg() {
+        let args = BunBuildOptions {
+            keep_names: true,
+            ..Default::default()
+        }
+        .args();
+
+        assert!(args.contains(&"--keep-names".to_string()));
+        assert!(!BunBuildOptions::default()
+            .args()
+            .contains(&"--keep-names".to_string()));
+    }
+
+    #[test]
+    fn test_node_env_follows_react_mode() {
+        let node_env = |react_mode, defines| {
+            let args = BunBuildOptions {
+                react_mode,
+                defines,
+                ..Default::default()
+            }
+            .args();
+            args.windows(2)
+                .find(|pair| pair[0] == "--define" && pair[1].starts_with("process.env.NODE_ENV:"))
+                .map(|pair| pair[1].clone())
+                .unwrap()
+        };
+
+        assert_eq!(
+            node_env(ReactMode::default(), None),
+            r#"process.env.NODE_ENV:"production""#
+        );
+        assert_eq!(
+            node_env(ReactMode::Development, None),
+            r#"process.env.NODE_ENV:"development""#
+        );
+
+        let defines = HashMap::from([(NODE_ENV_DEFINE.to_string(), r#""test""#.to_string())]);
+        assert_eq!(
+            node_env(ReactMode::Development, Some(&defines)),
+            r#"process.env.NODE_ENV:"test""#
+        );
+    }
+
+    #[test]
+    fn test_jsx_import_source_arg() {
+        let args = BunBuildOptions {
+            jsx_import_source: Some("@emotion/react"),
+            ..Default::default()
+        }
+        .args();
+
+        let at = args
+            .iter()
+            .position(|a| a == "--jsx-import-source")
+            .unwrap();
+        assert_eq!(args[at + 1], "@emotion/react");
+        assert!(!BunBuildOptions::default()
+            .args()
+            .contains(&"--jsx-import-source".to_string()));
+    }
+
+    #[test]
+    fn test_jsx_import_source_validation() {
+        assert!(validate_jsx_import_source("@emotion/react").is_ok());
+        assert!(validate_jsx_import_source("preact").is_ok());
+        assert!(validate_jsx_import_source("@emotion").is_err());
+        assert!(validate_jsx_import_source("./local").is_err());
+        assert!(validate_jsx_import_source("--outdir=/tmp").is_err());
+        assert!(validate_jsx_import_source("@a/b/c").is_err());
+    }
+
+    #[test]
+    fn test_esbuild_is_selected_without_bun() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let bundler = select_bundler(&dir.path().join("bun"), Path::new("npx"));
+        assert_eq!(bundler.step_name(), "esbuild build");
+        assert_eq!(bundler.program(), Path::new("npx"));
+        assert!(!bundler.is_bun());
+        assert_eq!(bundler.version_args(), ["--yes", "esbuild", "--version"]);
+        assert_eq!(bundler.exec_args(), ["--yes"]);
+
+        let bun_bin = dir.path().join("bun");
+        std::fs::write(&bun_bin, "").unwrap();
+        let bundler = select_bundler(&bun_bin, Path::new("npx"));
+        assert_eq!(bundler.step_name(), "Bun build");
+        assert_eq!(bundler.program(), bun_bin);
+        assert!(bundler.is_bun());
+        assert_eq!(bundler.exec_args(), ["x"]);
+    }
+
+    #[test]
+    fn test_esbuild_args() {
+        let externals: &[&str] = &["react"];
+        let options = BunBuildOptions {
+            externals,
+            splitting: true,
+            ..Default::default()
+        };
+
+        let esbuild = Esbuild {
+            npx: PathBuf::from("npx"),
+        };
+        assert_eq!(
+            esbuild.args(&options),
+            [
+                "--yes",
+                "esbuild",
+                "./src/index.tsx",
+                "--bundle",
+                "--outdir=./dist",
+                "--platform=browser",
+                "--format=esm",
+                "--jsx=automatic",
+                "--splitting",
+                r#"--define:process.env.NODE_ENV="production""#,
+                "--external:react",
+            ]
+        );
+    }
+}
@@ -0,0 +1,198 @@
+//! CloudFront cache invalidation for republished components.
+
+use aws_sdk_cloudfront::{
+    error::ProvideErrorMetadata,
+    types::{InvalidationBatch, Paths},
+    Client,
+};
+use lambda_http::{tracing, Error};
+use std::{fmt, time::Duration};
+
+/// Error codes CloudFront answers `CreateInvalidation` with when it is
+/// rate-limiting the account: too many calls, or too many invalidations
+/// still in progress on the distribution.
+const THROTTLING_CODES: &[&str] = &["Throttling", "TooManyInvalidationsInProgress"];
+
+/// CloudFront refused an invalidation because of its rate limits; trying
+/// again later may succeed.
+#[derive(Debug)]
+pub(crate) struct Throttled(pub String);
+
+impl fmt::Display for Throttled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "CloudFront throttled the invalidation: {}", self.0)
+    }
+}
+
+impl std::error::Error for Throttled {}
+
+/// The CloudFront operation the handler relies on. Implemented for the real
+/// `aws_sdk_cloudfront::Client` and for a scripted client in tests.
+pub(crate) trait Invalidator {
+    /// Asks CloudFront to drop `paths` from the distribution's edge caches.
+    /// `caller_reference` must be unique to this request.
+    async fn create_invalidation(
+        &self,
+        distribution_id: &str,
+        paths: &[String],
+        caller_reference: &str,
+    ) -> Result<(), Error>;
+}
+
+impl Invalidator for Client {
+    async fn create_invalidation(
+        &self,
+        distribution_id: &str,
+        paths: &[String],
+        caller_reference: &str,
+    ) -> Result<(), Error> {
+        let batch = InvalidationBatch::builder()
+            .paths(
+                Paths::builder()
+                    .quantity(paths.len() as i32)
+                    .set_items(Some(paths.to_vec()))
+                    .build()?,
+            )
+            .caller_reference(caller_reference)
+            .build()?;
+
+        let result = self
+            .create_invalidation()
+            .distribution_id(distribution_id)
+            .invalidation_batch(batch)
+            .send()
+            .await;
+
+        match result {
+            Ok(_) => Ok(()),
+            Err(err)
+                if err
+                    .code()
+                    .is_some_and(|code| THROTTLING_CODES.contains(&code)) =>
+            {
+                Err(Throttled(err.message().unwrap_or_default().to_string()).into())
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+/// How often a throttled invalidation is retried: up to `attempts` calls in
+/// all, waiting `backoff` after the first and twice as long after each one
+/// since.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct RetryPolicy {
+    pub attempts: u32,
+    pub backoff: Duration,
+}
+
+/// Creates the invalidation, retrying with exponential backoff while
+/// CloudFront throttles it. Any other error, or throttling that outlasts
+/// `policy`, is returned.
+pub(crate) async fn invalidate_with_retries<I: Invalidator>(
+    cdn: &I,
+    distribution_id: &str,
+    paths: &[String],
+    caller_reference: &str,
+    policy: RetryPolicy,
+) -> Result<(), Error> {
+    let mut delay = policy.backoff;
+    let mut attempt = 1;
+    loop {
+        match cdn
+            .create_invalidation(distribution_id, paths, caller_reference)
+            .await
+        {
+            Ok(()) => return Ok(()),
+            Err(e) if attempt < policy.attempts && e.is::<Throttled>() => {
+                tracing::warn!(
+                    attempt = attempt,
+                    delay_ms = delay.as_millis() as u64,
+                    "CloudFront throttled invalidation; retrying"
+                );
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod mock {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// `Invalidator` that throttles its first `throttles` calls and records
+    /// the paths of every call.
+    #[derive(Default)]
+    pub(crate) struct ThrottlingCdn {
+        pub throttles: Mutex<u32>,
+        pub calls: Mutex<Vec<Vec<String>>>,
+    }
+
+    impl ThrottlingCdn {
+        pub(crate) fn throttling(times: u32) -> Self {
+            Self {
+                throttles: Mutex::new(times),
+                ..Default::default()
+            }
+        }
+    }
+
+    impl Invalidator for ThrottlingCdn {
+        async fn create_invalidation(
+            &self,
+            _distribution_id: &str,
+            paths: &[String],
+            _caller_reference: &str,
+        ) -> Result<(), Error> {
+            self.calls.lock().unwrap().push(paths.to_vec());
+
+            let mut throttles = self.throttles.lock().unwrap();
+            if *throttles > 0 {
+                *throttles -= 1;
+                return Err(Throttled("Rate exceeded".to_string()).into());
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mock::ThrottlingCdn;
+    use super::*;
+
+    const POLICY: RetryPolicy = RetryPolicy {
+        attempts: 4,
+        backoff: Duration::from_millis(1),
+    };
+
+    #[tokio::test]
+    async fn test_throttled_invalidation_eventually_succeeds() {
+        let cdn = ThrottlingCdn::throttling(2);
+        let paths = vec!["/abc/*".to_string()];
+
+        invalidate_with_retries(&cdn, "E123", &paths, "abc-1", POLICY)
+            .await
+            .unwrap();
+
+        let calls = cdn.calls.lock().unwrap();
+        assert_eq!(calls.len(), 3);
+        assert!(calls.iter().all(|call| *call == paths));
+    }
+
+    #[tokio::test]
+    async fn test_invalidation_gives_up_after_attempts() {
+        let cdn = ThrottlingCdn::throttling(10);
+
+        let err = invalidate_with_retries(&cdn, "E123", &["/abc/*".to_string()], "abc-1", POLICY)
+            .await
+            .unwrap_err();
+
+        assert!(err.is::<Throttled>());
+        assert_eq!(cdn.calls.lock().unwrap().len(), 4);
+    }
+}
@@ -0,0 +1,255 @@
+//! Generation of the TSX entry points that Bun bundles.
+
+use crate::bundler::JsxRuntime;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// An extra page built from the same workspace. It renders the default export
+/// of `import_path`, which is resolved relative to `src`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct NamedEntry {
+    pub name: String,
+    pub import_path: String,
+}
+
+/// What a generated entry point imports and renders.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct EntryOptions<'a> {
+    /// Module whose component is rendered, relative to `src`.
+    pub import_path: &'a str,
+    /// Named export to render; the module's default export if unset.
+    pub component_export: Option<&'a str>,
+    pub jsx_runtime: JsxRuntime,
+    /// Stylesheet imported alongside the component, relative to `src`;
+    /// omitted entirely when the workspace has none.
+    pub stylesheet: Option<&'a str>,
+    /// Wrap the component in [`ERROR_BOUNDARY`] so render errors show up in
+    /// the preview instead of leaving it blank.
+    pub error_boundary: bool,
+}
+
+/// Class component that renders a thrown error's stack in place of its
+/// children.
+const ERROR_BOUNDARY: &str = r#"import { Component, type ReactNode } from 'react';
+
+class ErrorBoundary extends Component<{ children: ReactNode }, { error: Error | null }> {
+  state = { error: null as Error | null };
+
+  static getDerivedStateFromError(error: Error) {
+    return { error };
+  }
+
+  render() {
+    if (this.state.error) {
+      return (
+        <pre style={{ color: '#b91c1c', padding: 16, whiteSpace: 'pre-wrap' }}>
+          {String(this.state.error.stack ?? this.state.error)}
+        </pre>
+      );
+    }
+    return this.props.children;
+  }
+}
+"#;
+
+/// Entry point that mounts the component described by `options` into
+/// `#root`. The classic JSX runtime needs `React` in scope for the JSX below;
+/// the automatic one imports what it needs itself.
+pub(crate) fn render_entry_point(options: &EntryOptions) -> String {
+    let react_import = match options.jsx_runtime {
+        JsxRuntime::Classic => "import React from 'react';\n",
+        JsxRuntime::Automatic => "",
+    };
+    let (component_import, component) = match options.component_export {
+        Some(name) => (format!("{{ {} }}", name), name),
+        None => ("UserComponent".to_string(), "UserComponent"),
+    };
+    let stylesheet_import = options
+        .stylesheet
+        .map(|stylesheet| format!("import '{}';\n", stylesheet))
+        .unwrap_or_default();
+    let (error_boundary, element) = if options.error_boundary {
+        (
+            format!("\n{}", ERROR_BOUNDARY),
+            format!("<ErrorBoundary><{} /></ErrorBoundary>", component),
+        )
+    } else {
+        (String::new(), format!("<{} />", component))
+    };
+
+    format!(
+        r#"
+{}import ReactDOM from 'react-dom/client';
+import {} from '{}';
+{}{}
+const rootEl = document.getElementById('root');
+if (rootEl) ReactDOM.createRoot(rootEl).render({});
+"#,
+        react_import,
+        component_import,
+        options.import_path,
+        stylesheet_import,
+        error_boundary,
+        element
+    )
+}
+
+/// A named export must be usable as a JSX component: an identifier starting
+/// with an uppercase letter (lowercase tags are HTML elements) that doesn't
+/// shadow the entry point's own imports.
+pub(crate) fn is_valid_component_export(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_uppercase())
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '$'))
+        && name.len() <= 128
+        && !["React", "ReactDOM", "Component", "ErrorBoundary"].contains(&name)
+}
+
+/// Names of the sources the build writes for the main component, which an
+/// entry's `src/{name}.tsx` would overwrite.
+const RESERVED_ENTRY_NAMES: &[&str] = &["index", "UserComponent"];
+
+/// Entry names become `src/{name}.tsx`, `dist/{name}.js` and `{name}.html`,
+/// and import paths are embedded in a string literal, so both are restricted.
+pub(crate) fn validate_entries(entries: &[NamedEntry]) -> Result<(), String> {
+    let mut seen = HashSet::new();
+
+    for entry in entries {
+        let valid_name = !entry.name.is_empty()
+            && entry.name.len() <= 64
+            && entry
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'));
+        if !valid_name {
+            return Err(format!("invalid entry name: {}", entry.name));
+        }
+        // Case-insensitive, for file systems that are.
+        if RESERVED_ENTRY_NAMES
+            .iter()
+            .any(|reserved| entry.name.eq_ignore_ascii_case(reserved))
+        {
+            return Err(format!(
+                "entry name '{}' is reserved for the main component",
+                entry.name
+            ));
+        }
+        if !seen.insert(entry.name.as_str()) {
+            return Err(format!("duplicate entry name: {}", entry.name));
+        }
+
+        let path = &entry.import_path;
+        let valid_path = path.starts_with("./")
+            && !path.split('/').any(|segment| segment == "..")
+            && path
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '/' | '-' | '_' | '@'));
+        if !valid_path {
+            return Err(format!(
+                "entry import_path must be a relative path inside src: {}",
+                path
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, import_path: &str) -> NamedEntry {
+        NamedEntry {
+            name: name.to_string(),
+            import_path: import_path.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_entry_point_imports_given_path() {
+        let entry_point = render_entry_point(&EntryOptions {
+            import_path: "./components/Button",
+            stylesheet: Some("./globals.css"),
+            ..Default::default()
+        });
+
+        assert!(entry_point.contains("import UserComponent from './components/Button';"));
+        assert!(entry_point.contains("import './globals.css';"));
+        assert!(entry_point.contains("render(<UserComponent />)"));
+        assert!(!entry_point.contains("import React from 'react';"));
+    }
+
+    #[test]
+    fn test_classic_runtime_imports_react() {
+        let entry_point = render_entry_point(&EntryOptions {
+            import_path: "./UserComponent",
+            jsx_runtime: JsxRuntime::Classic,
+            ..Default::default()
+        });
+
+        assert!(entry_point.starts_with("\nimport React from 'react';\nimport ReactDOM"));
+    }
+
+    #[test]
+    fn test_named_export_entry_point() {
+        let entry_point = render_entry_point(&EntryOptions {
+            import_path: "./UserComponent",
+            component_export: Some("Demo"),
+            ..Default::default()
+        });
+
+        assert!(entry_point.contains("import { Demo } from './UserComponent';"));
+        assert!(entry_point.contains("render(<Demo />)"));
+        assert!(!entry_point.contains("UserComponent />"));
+    }
+
+    #[test]
+    fn test_entry_point_without_stylesheet() {
+        let entry_point = render_entry_point(&EntryOptions {
+            import_path: "./UserComponent",
+            ..Default::default()
+        });
+
+        assert!(!entry_point.contains(".css"));
+        assert!(entry_point.contains("import UserComponent from './UserComponent';"));
+    }
+
+    #[test]
+    fn test_error_boundary_wraps_component() {
+        let entry_point = render_entry_point(&EntryOptions {
+            import_path: "./UserComponent",
+            error_boundary: true,
+            ..Default::default()
+        });
+
+        assert!(entry_point.contains("class ErrorBoundary extends Component"));
+        assert!(entry_point.contains("static getDerivedStateFromError"));
+        assert!(entry_point.contains("render(<ErrorBoundary><UserComponent /></ErrorBoundary>)"));
+    }
+
+    #[test]
+    fn test_component_export_validation() {
+        assert!(is_valid_component_export("Demo"));
+        assert!(is_valid_component_export("Card_v2"));
+        assert!(!is_valid_component_export("demo"));
+        assert!(!is_valid_component_export("Demo }; alert(1); {"));
+        assert!(!is_valid_component_export("ReactDOM"));
+        assert!(!is_valid_component_export(""));
+    }
+
+    #[test]
+    fn test_entry_validation() {
+        assert!(validate_entries(&[entry("Button", "./Button"), entry("Card", "./Card")]).is_ok());
+        assert!(validate_entries(&[entry("a/b", "./Button")]).is_err());
+        assert!(validate_entries(&[entry("index", "./Button")]).is_err());
+        assert_eq!(
+            validate_entries(&[entry("UserComponent", "./Button")]).unwrap_err(),
+            "entry name 'UserComponent' is reserved for the main component"
+        );
+        assert!(validate_entries(&[entry("usercomponent", "./Button")]).is_err());
+        assert!(validate_entries(&[entry("Button", "./a"), entry("Button", "./b")]).is_err());
+        assert!(validate_entries(&[entry("Button", "../secrets")]).is_err());
+        assert!(validate_entries(&[entry("Button", "./x'; alert(1); '")]).is_err());
+    }
+}
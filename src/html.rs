@@ -0,0 +1,703 @@
+//! Generation of the `index.html` page that loads the bundled component.
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+/// Colour scheme the preview starts in.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ColorScheme {
+    Light,
+    Dark,
+    /// Follow the viewer's OS preference.
+    #[default]
+    System,
+}
+
+/// How the page refers to its bundle, stylesheet and icon.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum AssetUrlMode {
+    /// Next to the page (or under `base_path`), wherever it is served from.
+    #[default]
+    Relative,
+    /// Fully qualified URLs under the preview's render address, so copies of
+    /// the page served elsewhere still load the published assets.
+    Absolute,
+}
+
+/// A page variant that lays the component out at a fixed size, for checking
+/// it at several breakpoints.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct ViewportVariant {
+    /// Published as `{name}.html`.
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Largest width or height, in CSS pixels, a variant may ask for.
+const MAX_VARIANT_SIZE: u32 = 10_000;
+
+/// Per-request knobs for the generated page.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct HtmlOptions<'a> {
+    /// Nonce that scripts must carry to run under the emitted CSP.
+    pub csp_nonce: Option<&'a str>,
+    /// Content of the viewport meta tag; defaults to [`DEFAULT_VIEWPORT`].
+    pub viewport: Option<&'a str>,
+    /// Normalised prefix (see [`normalize_base_path`]) the page and its assets
+    /// are served under. Assets are referenced relatively when unset.
+    pub base_path: Option<&'a str>,
+    pub color_scheme: ColorScheme,
+    /// Bundle (`{name}.js`) the page loads; the main `index` bundle if unset.
+    pub entry_name: Option<&'a str>,
+    /// Markup appended to `<head>`, after the stylesheet.
+    pub head_extra: Option<&'a str>,
+    /// Markup placed at the end of `<body>`, after the bundle's script.
+    pub body_extra: Option<&'a str>,
+    /// Leave out the `index.css` link, for builds that produce no CSS.
+    pub omit_stylesheet: bool,
+    /// JSON import map (see [`import_map`]) resolving the bundle's external
+    /// bare specifiers.
+    pub import_map: Option<&'a str>,
+    /// Fixed size the page is laid out at; overrides `viewport`.
+    pub variant: Option<&'a ViewportVariant>,
+    /// File name of an icon uploaded next to the page; no icon link if unset.
+    pub favicon: Option<&'a str>,
+    /// Absolute URL, ending in `/`, that asset references start with instead
+    /// of `base_path`; see [`AssetUrlMode::Absolute`].
+    pub asset_base_url: Option<&'a str>,
+    /// Language of the page's content, checked by [`is_valid_lang`];
+    /// [`DEFAULT_LANG`] if unset.
+    pub lang: Option<&'a str>,
+    /// Placeholder markup shown inside `#root` until React mounts and
+    /// replaces it; checked by [`validate_loading_html`].
+    pub loading_html: Option<&'a str>,
+    /// File names of fonts published under [`FONTS_DIR`], which the page
+    /// preloads; [`FONTS_STYLESHEET`] is linked when there are any.
+    pub fonts: &'a [String],
+}
+
+pub(crate) const DEFAULT_LANG: &str = "en";
+
+pub(crate) const DEFAULT_VIEWPORT: &str = "width=device-width, initial-scale=1.0";
+
+pub(crate) fn render_html(options: &HtmlOptions) -> String {
+    let asset_prefix = options.asset_base_url.or(options.base_path).unwrap_or("./");
+    let entry_name = options.entry_name.unwrap_or("index");
+    let mut head = vec![r#"<meta charset="UTF-8" />"#.to_string()];
+
+    if let Some(base_path) = options.base_path {
+        head.push(format!(r#"<base href="{}" />"#, base_path));
+    }
+
+    if let Some(nonce) = options.csp_nonce {
+        head.push(format!(
+            r#"<meta http-equiv="Content-Security-Policy" content="script-src 'nonce-{}'" />"#,
+            nonce
+        ));
+    }
+
+    // Tailwind's `dark:` variant keys off the class; the meta tag makes the
+    // browser's own form controls and scrollbars match.
+    let html_class = match options.color_scheme {
+        ColorScheme::Light => {
+            head.push(r#"<meta name="color-scheme" content="light" />"#.to_string());
+            ""
+        }
+        ColorScheme::Dark => {
+            head.push(r#"<meta name="color-scheme" content="dark" />"#.to_string());
+            r#" class="dark""#
+        }
+        ColorScheme::System => "",
+    };
+
+    match options.variant {
+        Some(variant) => {
+            head.push(format!(
+                r#"<meta name="viewport" content="width={}, initial-scale=1.0" />"#,
+                variant.width
+            ));
+            head.push(format!(
+                "<style>body {{ width: {}px; height: {}px; margin: 0; overflow: auto; }}</style>",
+                variant.width, variant.height
+            ));
+        }
+        None => head.push(format!(
+            r#"<meta name="viewport" content="{}" />"#,
+            options.viewport.unwrap_or(DEFAULT_VIEWPORT)
+        )),
+    }
+    head.push("<title>Rendered Component</title>".to_string());
+
+    if let Some(favicon) = options.favicon {
+        head.push(format!(
+            r#"<link rel="icon" href="{}{}" />"#,
+            asset_prefix, favicon
+        ));
+    }
+
+    // Font preloads are fetched in CORS mode, so without `crossorigin` the
+    // browser would download each font a second time for the stylesheet.
+    for font in options.fonts {
+        head.push(format!(
+            r#"<link rel="preload" href="{}{}/{}" as="font" type="{}" crossorigin />"#,
+            asset_prefix,
+            FONTS_DIR,
+            font,
+            font_type(font).unwrap_or("font/woff2")
+        ));
+    }
+    if !options.fonts.is_empty() {
+        head.push(format!(
+            r#"<link rel="stylesheet" href="{}{}" />"#,
+            asset_prefix, FONTS_STYLESHEET
+        ));
+    }
+
+    if !options.omit_stylesheet {
+        head.push(format!(
+            r#"<link rel="stylesheet" href="{}index.css" />"#,
+            asset_prefix
+        ));
+    }
+
+    let script_nonce = options
+        .csp_nonce
+        .map(|nonce| format!(r#" nonce="{}""#, nonce))
+        .unwrap_or_default();
+
+    // Import maps only apply to module scripts that come after them.
+    if let Some(import_map) = options.import_map {
+        head.push(format!(
+            r#"<script type="importmap"{}>{}</script>"#,
+            script_nonce, import_map
+        ));
+    }
+
+    if let Some(extra) = options.head_extra {
+        head.push(extra.to_string());
+    }
+
+    format!(
+        r#"<!DOCTYPE html>
+      <html lang="{lang}"{html_class}>
+        <head>
+          {head}
+        </head>
+        <body>
+          <div id="root">{loading_html}</div>
+          <script type="module"{script_nonce} src="{asset_prefix}{entry_name}.js"></script>{body_extra}
+        </body>
+      </html>"#,
+        head = head.join("\n          "),
+        script_nonce = script_nonce,
+        asset_prefix = asset_prefix,
+        html_class = html_class,
+        lang = options.lang.unwrap_or(DEFAULT_LANG),
+        loading_html = options.loading_html.unwrap_or_default(),
+        entry_name = entry_name,
+        body_extra = options
+            .body_extra
+            .map(|extra| format!("\n          {}", extra))
+            .unwrap_or_default(),
+    )
+}
+
+/// Page that shows the component's own page, published next to it as
+/// `inner_name`, in an `<iframe>` sandboxed to scripts only, so the
+/// component can't reach the embedding page, its cookies or storage.
+///
+/// The frame gets an opaque origin, so its module script is fetched in CORS
+/// mode with `Origin: null`: the bucket or CDN must answer with
+/// `Access-Control-Allow-Origin` for the component to run.
+pub(crate) fn render_sandbox_page(inner_name: &str, lang: Option<&str>) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+      <html lang="{lang}">
+        <head>
+          <meta charset="UTF-8" />
+          <meta name="viewport" content="{viewport}" />
+          <title>Rendered Component</title>
+          <style>html, body, iframe {{ margin: 0; width: 100%; height: 100%; border: 0; display: block; }}</style>
+        </head>
+        <body>
+          <iframe sandbox="allow-scripts" src="./{inner_name}"></iframe>
+        </body>
+      </html>"#,
+        lang = lang.unwrap_or(DEFAULT_LANG),
+        viewport = DEFAULT_VIEWPORT,
+        inner_name = inner_name,
+    )
+}
+
+/// Import map pointing each of `modules` at `{cdn}/{package}@{version}`, so
+/// every preview on a page loads the same copy. Subpaths such as
+/// `react-dom/client` keep their path after the version.
+pub(crate) fn import_map(cdn: &str, react_version: &str, modules: &[&str]) -> String {
+    let cdn = cdn.trim_end_matches('/');
+    let imports: serde_json::Map<String, serde_json::Value> = modules
+        .iter()
+        .map(|module| {
+            let url = match module.split_once('/') {
+                Some((package, path)) => {
+                    format!("{}/{}@{}/{}", cdn, package, react_version, path)
+                }
+                None => format!("{}/{}@{}", cdn, module, react_version),
+            };
+            (module.to_string(), json!(url))
+        })
+        .collect();
+
+    json!({ "imports": imports }).to_string()
+}
+
+/// Variant names become `{name}.html`, so they follow the same rules as entry
+/// names; sizes must be positive and within [`MAX_VARIANT_SIZE`].
+pub(crate) fn validate_variant(variant: &ViewportVariant) -> Result<(), String> {
+    let valid_name = !variant.name.is_empty()
+        && variant.name.len() <= 64
+        && variant
+            .name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'));
+    if !valid_name {
+        return Err(format!("invalid viewport name: {}", variant.name));
+    }
+
+    for size in [variant.width, variant.height] {
+        if size == 0 || size > MAX_VARIANT_SIZE {
+            return Err(format!(
+                "viewport {} sizes must be between 1 and {} pixels",
+                variant.name, MAX_VARIANT_SIZE
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Nonces end up inside an attribute and a CSP source expression, so only
+/// base64 (standard or URL-safe) characters are accepted.
+pub(crate) fn is_valid_nonce(nonce: &str) -> bool {
+    !nonce.is_empty()
+        && nonce.len() <= 256
+        && nonce
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '/' | '=' | '-' | '_'))
+}
+
+/// Accepts BCP 47 shaped tags such as `fr`, `pt-BR` or `zh-Hant-TW`: a
+/// 2–8 letter language followed by 1–8 character alphanumeric subtags.
+pub(crate) fn is_valid_lang(lang: &str) -> bool {
+    let mut subtags = lang.split('-');
+    let language = subtags.next().unwrap_or_default();
+
+    (2..=8).contains(&language.len())
+        && language.chars().all(|c| c.is_ascii_alphabetic())
+        && subtags.all(|subtag| {
+            (1..=8).contains(&subtag.len()) && subtag.chars().all(|c| c.is_ascii_alphanumeric())
+        })
+}
+
+/// Turns `previews/abc` or `/previews/abc/` into `/previews/abc/`, leaving
+/// absolute URLs' scheme and host alone, so asset names can be appended.
+pub(crate) fn normalize_base_path(base_path: &str) -> String {
+    let trimmed = base_path.trim().trim_end_matches('/');
+    if trimmed.is_empty() {
+        return "/".to_string();
+    }
+
+    if trimmed.starts_with("https://") || trimmed.starts_with("http://") || trimmed.starts_with('/')
+    {
+        format!("{}/", trimmed)
+    } else {
+        format!("/{}/", trimmed)
+    }
+}
+
+/// Whether `value` can be placed inside a double-quoted attribute without
+/// closing it or opening a new tag.
+pub(crate) fn is_safe_attribute_value(value: &str) -> bool {
+    !value.contains(['"', '\'', '<', '>'])
+}
+
+/// Whether an injected snippet stays inside the element it is placed in,
+/// i.e. doesn't open or close the document's structural tags.
+pub(crate) fn is_safe_snippet(snippet: &str) -> bool {
+    let lower = snippet.to_ascii_lowercase();
+    if lower.contains("<!doctype") {
+        return false;
+    }
+
+    // `<header>` is fine; `<head>`, `<head ...>` and `</head>` are not.
+    ["<html", "</html", "<head", "</head", "<body", "</body"]
+        .iter()
+        .all(|tag| {
+            lower.match_indices(tag).all(|(at, _)| {
+                lower[at + tag.len()..]
+                    .chars()
+                    .next()
+                    .is_some_and(|c| c.is_ascii_alphanumeric() || c == '-')
+            })
+        })
+}
+
+/// Longest `loading_html` accepted; a placeholder is meant to be small.
+pub(crate) const MAX_LOADING_HTML_LEN: usize = 16 * 1024;
+
+/// Loading markup sits inside `#root` and is thrown away on mount, so it may
+/// be styled but mustn't run scripts or close `#root` early.
+pub(crate) fn validate_loading_html(html: &str) -> Result<(), String> {
+    if html.len() > MAX_LOADING_HTML_LEN {
+        return Err(format!(
+            "loading_html must be at most {} bytes",
+            MAX_LOADING_HTML_LEN
+        ));
+    }
+
+    let lower = html.to_ascii_lowercase();
+    let has_handler = lower.match_indices("on").any(|(at, _)| {
+        let starts_attribute = lower[..at].ends_with(|c: char| c.is_ascii_whitespace() || c == '/');
+        starts_attribute
+            && lower[at + 2..]
+                .trim_start_matches(|c: char| c.is_ascii_alphabetic())
+                .trim_start()
+                .starts_with('=')
+    });
+    if !is_safe_snippet(html)
+        || lower.contains("<script")
+        || lower.contains("javascript:")
+        || has_handler
+    {
+        return Err("loading_html must be plain markup without scripts".to_string());
+    }
+
+    let opened = lower
+        .match_indices("<div")
+        .filter(|(at, _)| {
+            lower[at + 4..]
+                .chars()
+                .next()
+                .is_some_and(|c| c == '>' || c.is_ascii_whitespace())
+        })
+        .count();
+    if lower.matches("</div").count() > opened {
+        return Err("loading_html closes more divs than it opens".to_string());
+    }
+
+    Ok(())
+}
+
+/// Directory, next to the page, that submitted fonts are published under.
+pub(crate) const FONTS_DIR: &str = "fonts";
+
+/// Stylesheet, next to the page, declaring the submitted fonts.
+pub(crate) const FONTS_STYLESHEET: &str = "fonts.css";
+
+/// Font file extensions accepted, with their MIME type and CSS `format()`.
+const FONT_FORMATS: &[(&str, &str, &str)] = &[
+    ("woff2", "font/woff2", "woff2"),
+    ("woff", "font/woff", "woff"),
+    ("ttf", "font/ttf", "truetype"),
+    ("otf", "font/otf", "opentype"),
+];
+
+fn font_format(file_name: &str) -> Option<&'static (&'static str, &'static str, &'static str)> {
+    let (_, extension) = file_name.rsplit_once('.')?;
+    FONT_FORMATS
+        .iter()
+        .find(|(known, _, _)| extension.eq_ignore_ascii_case(known))
+}
+
+/// MIME type of a font file, or `None` if it isn't one of [`FONT_FORMATS`].
+pub(crate) fn font_type(file_name: &str) -> Option<&'static str> {
+    font_format(file_name).map(|(_, mime, _)| *mime)
+}
+
+/// `@font-face` rule for `family` served from `fonts/{file_name}`, relative
+/// to [`FONTS_STYLESHEET`]. Text is drawn in a fallback font until the file
+/// arrives rather than left invisible.
+pub(crate) fn font_face_rule(family: &str, file_name: &str) -> String {
+    let format = font_format(file_name).map_or("woff2", |(_, _, format)| *format);
+    format!(
+        "@font-face {{\n  font-family: \"{}\";\n  src: url(\"./{}/{}\") format(\"{}\");\n  font-display: swap;\n}}\n",
+        family, FONTS_DIR, file_name, format
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csp_nonce_on_script_and_meta() {
+        let html = render_html(&HtmlOptions {
+            csp_nonce: Some("r4nd0m+Nonce=="),
+            ..Default::default()
+        });
+
+        assert!(html.contains(
+            r#"<meta http-equiv="Content-Security-Policy" content="script-src 'nonce-r4nd0m+Nonce=='" />"#
+        ));
+        assert!(html.contains(r#"<script type="module" nonce="r4nd0m+Nonce==" src="./index.js">"#));
+    }
+
+    #[test]
+    fn test_no_csp_without_nonce() {
+        let html = render_html(&HtmlOptions::default());
+
+        assert!(!html.contains("Content-Security-Policy"));
+        assert!(html.contains(r#"<script type="module" src="./index.js">"#));
+    }
+
+    #[test]
+    fn test_custom_viewport() {
+        let html = render_html(&HtmlOptions {
+            viewport: Some("width=1280, maximum-scale=1"),
+            ..Default::default()
+        });
+
+        assert!(html.contains(r#"<meta name="viewport" content="width=1280, maximum-scale=1" />"#));
+        assert!(!html.contains(DEFAULT_VIEWPORT));
+    }
+
+    #[test]
+    fn test_default_viewport() {
+        let html = render_html(&HtmlOptions::default());
+
+        assert!(html.contains(
+            r#"<meta name="viewport" content="width=device-width, initial-scale=1.0" />"#
+        ));
+        assert!(!is_safe_attribute_value(r#"width=100" onload="alert(1)"#));
+    }
+
+    #[test]
+    fn test_base_path_rewrites_asset_urls() {
+        let base_path = normalize_base_path("previews/abc");
+        let html = render_html(&HtmlOptions {
+            base_path: Some(&base_path),
+            ..Default::default()
+        });
+
+        assert!(html.contains(r#"<base href="/previews/abc/" />"#));
+        assert!(html.contains(r#"<link rel="stylesheet" href="/previews/abc/index.css" />"#));
+        assert!(html.contains(r#"src="/previews/abc/index.js""#));
+        assert!(!html.contains("./index"));
+    }
+
+    #[test]
+    fn test_normalize_base_path() {
+        assert_eq!(normalize_base_path("/previews/abc/"), "/previews/abc/");
+        assert_eq!(normalize_base_path("previews"), "/previews/");
+        assert_eq!(normalize_base_path("/"), "/");
+        assert_eq!(
+            normalize_base_path("https://cdn.example.com/abc"),
+            "https://cdn.example.com/abc/"
+        );
+    }
+
+    #[test]
+    fn test_dark_scheme_adds_dark_class() {
+        let html = render_html(&HtmlOptions {
+            color_scheme: ColorScheme::Dark,
+            ..Default::default()
+        });
+
+        assert!(html.contains(r#"<html lang="en" class="dark">"#));
+        assert!(html.contains(r#"<meta name="color-scheme" content="dark" />"#));
+    }
+
+    #[test]
+    fn test_system_scheme_leaves_root_untouched() {
+        let html = render_html(&HtmlOptions::default());
+
+        assert!(html.contains(r#"<html lang="en">"#));
+        assert!(!html.contains("color-scheme"));
+    }
+
+    #[test]
+    fn test_nonce_validation() {
+        assert!(is_valid_nonce("abc123+/=_-"));
+        assert!(!is_valid_nonce(""));
+        assert!(!is_valid_nonce(r#"abc" onload="x"#));
+        assert!(!is_valid_nonce("abc'; script-src *"));
+    }
+
+    #[test]
+    fn test_extra_snippets_are_injected() {
+        let html = render_html(&HtmlOptions {
+            head_extra: Some(r#"<script defer src="https://plausible.io/js/script.js"></script>"#),
+            body_extra: Some("<noscript>Enable JavaScript</noscript>"),
+            ..Default::default()
+        });
+
+        let head_end = html.find("</head>").unwrap();
+        let body_start = html.find("<body>").unwrap();
+        let body_end = html.find("</body>").unwrap();
+        let analytics = html.find("plausible.io").unwrap();
+        let script = html.find(r#"src="./index.js""#).unwrap();
+        let noscript = html.find("<noscript>").unwrap();
+
+        assert!(html.find("index.css").unwrap() < analytics && analytics < head_end);
+        assert!(body_start < script && script < noscript && noscript < body_end);
+    }
+
+    #[test]
+    fn test_snippets_may_not_close_the_document() {
+        assert!(is_safe_snippet(
+            "<meta name=\"robots\" content=\"noindex\" />"
+        ));
+        assert!(!is_safe_snippet("</head><body onload=\"x()\">"));
+        assert!(!is_safe_snippet("</BODY></HTML>"));
+        assert!(is_safe_snippet("<header>Preview</header>"));
+    }
+
+    #[test]
+    fn test_import_map_precedes_bundle() {
+        let map = import_map("https://esm.sh/", "19", &["react", "react-dom/client"]);
+        let html = render_html(&HtmlOptions {
+            import_map: Some(&map),
+            ..Default::default()
+        });
+
+        assert!(html.contains(
+            r#"<script type="importmap">{"imports":{"react":"https://esm.sh/react@19","react-dom/client":"https://esm.sh/react-dom@19/client"}}</script>"#
+        ));
+        assert!(html.find("importmap").unwrap() < html.find("index.js").unwrap());
+    }
+
+    #[test]
+    fn test_variant_sets_width_and_height() {
+        let variant = ViewportVariant {
+            name: "mobile".to_string(),
+            width: 375,
+            height: 812,
+        };
+        let html = render_html(&HtmlOptions {
+            variant: Some(&variant),
+            ..Default::default()
+        });
+
+        assert!(html.contains(r#"<meta name="viewport" content="width=375, initial-scale=1.0" />"#));
+        assert!(html.contains("body { width: 375px; height: 812px;"));
+        assert!(!html.contains(DEFAULT_VIEWPORT));
+
+        assert!(validate_variant(&variant).is_ok());
+        assert!(validate_variant(&ViewportVariant {
+            name: "../x".to_string(),
+            ..variant.clone()
+        })
+        .is_err());
+        assert!(validate_variant(&ViewportVariant {
+            width: 0,
+            ..variant
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn test_fonts_are_preloaded() {
+        let fonts = ["Inter.woff2".to_string(), "Mono.ttf".to_string()];
+        let html = render_html(&HtmlOptions {
+            fonts: &fonts,
+            ..Default::default()
+        });
+        assert!(html.contains(
+            r#"<link rel="preload" href="./fonts/Inter.woff2" as="font" type="font/woff2" crossorigin />"#
+        ));
+        assert!(html.contains(r#"href="./fonts/Mono.ttf" as="font" type="font/ttf""#));
+        assert!(html.contains(r#"<link rel="stylesheet" href="./fonts.css" />"#));
+        assert!(!render_html(&HtmlOptions::default()).contains("fonts.css"));
+
+        assert_eq!(
+            font_face_rule("Mono", "Mono.ttf"),
+            "@font-face {\n  font-family: \"Mono\";\n  src: url(\"./fonts/Mono.ttf\") format(\"truetype\");\n  font-display: swap;\n}\n"
+        );
+        assert_eq!(font_type("logo.png"), None);
+    }
+
+    #[test]
+    fn test_favicon_link() {
+        let html = render_html(&HtmlOptions {
+            favicon: Some("favicon.png"),
+            ..Default::default()
+        });
+        assert!(html.contains(r#"<link rel="icon" href="./favicon.png" />"#));
+
+        assert!(!render_html(&HtmlOptions::default()).contains(r#"rel="icon""#));
+    }
+
+    #[test]
+    fn test_absolute_asset_urls() {
+        let html = render_html(&HtmlOptions {
+            asset_base_url: Some("https://abc.preview.runney.cloud/1.2.0/"),
+            favicon: Some("favicon.png"),
+            ..Default::default()
+        });
+
+        assert!(html.contains(r#"src="https://abc.preview.runney.cloud/1.2.0/index.js""#));
+        assert!(html.contains(r#"href="https://abc.preview.runney.cloud/1.2.0/index.css""#));
+        assert!(html.contains(r#"href="https://abc.preview.runney.cloud/1.2.0/favicon.png""#));
+        assert!(!html.contains("./index"));
+    }
+
+    #[test]
+    fn test_lang_attribute() {
+        assert!(render_html(&HtmlOptions::default()).contains(r#"<html lang="en">"#));
+
+        let html = render_html(&HtmlOptions {
+            lang: Some("pt-BR"),
+            ..Default::default()
+        });
+        assert!(html.contains(r#"<html lang="pt-BR">"#));
+    }
+
+    #[test]
+    fn test_lang_validation() {
+        assert!(is_valid_lang("fr"));
+        assert!(is_valid_lang("zh-Hant-TW"));
+        assert!(is_valid_lang("es-419"));
+        assert!(!is_valid_lang("e"));
+        assert!(!is_valid_lang("en-"));
+        assert!(!is_valid_lang(r#"en" onload="x"#));
+    }
+
+    #[test]
+    fn test_sandbox_page_frames_inner_document() {
+        let outer = render_sandbox_page("sandboxed.html", None);
+
+        let start = outer.find("<iframe ").unwrap();
+        let tag = &outer[start..start + outer[start..].find('>').unwrap()];
+        let attribute = |name: &str| {
+            let marker = format!(" {}=\"", name);
+            tag.find(&marker).map(|at| {
+                let value = &tag[at + marker.len()..];
+                &value[..value.find('"').unwrap()]
+            })
+        };
+
+        assert_eq!(attribute("sandbox"), Some("allow-scripts"));
+        assert_eq!(attribute("src"), Some("./sandboxed.html"));
+        assert_eq!(attribute("srcdoc"), None);
+        assert!(!outer.contains("<script"));
+    }
+
+    #[test]
+    fn test_loading_html_is_placed_inside_root() {
+        let spinner = r#"<div class="spinner" aria-label="Loading"></div>"#;
+        let html = render_html(&HtmlOptions {
+            loading_html: Some(spinner),
+            ..Default::default()
+        });
+
+        assert!(html.contains(&format!(r#"<div id="root">{}</div>"#, spinner)));
+        assert!(render_html(&HtmlOptions::default()).contains(r#"<div id="root"></div>"#));
+
+        assert!(validate_loading_html(spinner).is_ok());
+        assert!(validate_loading_html("<script>alert(1)</script>").is_err());
+        assert!(validate_loading_html(r#"<img src="x" onerror="alert(1)">"#).is_err());
+        assert!(validate_loading_html("</div><div>").is_err());
+    }
+}
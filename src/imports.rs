@@ -0,0 +1,193 @@
+//! Best-effort static scan of the module specifiers a component imports.
+//!
+//! This is a plain text scan rather than a parse, so it can be fooled by
+//! determined code; it exists to catch accidental use of server-only modules.
+
+/// Modules denied when `IMPORT_DENYLIST` isn't set.
+pub(crate) const DEFAULT_DENYLIST: &[&str] = &[
+    "child_process",
+    "cluster",
+    "dgram",
+    "fs",
+    "net",
+    "os",
+    "process",
+    "worker_threads",
+];
+
+/// Every string literal following `from`, `import` or `require`, covering
+/// static imports, re-exports, side-effect imports, `import()` and `require()`.
+pub(crate) fn import_specifiers(code: &str) -> Vec<String> {
+    let mut specifiers = Vec::new();
+
+    for keyword in ["from", "import", "require"] {
+        let mut offset = 0;
+
+        while let Some(found) = code[offset..].find(keyword) {
+            let start = offset + found;
+            offset = start + keyword.len();
+
+            let preceded_by_ident = code[..start]
+                .chars()
+                .next_back()
+                .is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '$' || c == '.');
+            if preceded_by_ident {
+                continue;
+            }
+
+            let rest = code[offset..].trim_start();
+            let rest = rest.strip_prefix('(').map(str::trim_start).unwrap_or(rest);
+
+            let Some(quote) = rest
+                .chars()
+                .next()
+                .filter(|c| matches!(c, '\'' | '"' | '`'))
+            else {
+                continue;
+            };
+            if let Some(end) = rest[1..].find(quote) {
+                specifiers.push(rest[1..1 + end].to_string());
+            }
+        }
+    }
+
+    specifiers.sort();
+    specifiers.dedup();
+    specifiers
+}
+
+/// Returns the first imported specifier that names a denied module, whether
+/// directly, through a `node:` prefix or as a subpath (`fs/promises`).
+pub(crate) fn find_denied_import(code: &str, denylist: &[String]) -> Option<String> {
+    import_specifiers(code).into_iter().find(|specifier| {
+        let bare = specifier.strip_prefix("node:").unwrap_or(specifier);
+        denylist.iter().any(|denied| {
+            bare == denied
+                || bare
+                    .strip_prefix(denied.as_str())
+                    .is_some_and(|rest| rest.starts_with('/'))
+        })
+    })
+}
+
+/// Package a bare specifier resolves to (`lodash/get` to `lodash`,
+/// `@scope/pkg/sub` to `@scope/pkg`); `None` for relative paths, URLs and the
+/// `@/` source alias.
+fn package_name(specifier: &str) -> Option<&str> {
+    let bare = specifier.strip_prefix("npm:").unwrap_or(specifier);
+    if bare.is_empty()
+        || bare.starts_with(['.', '/'])
+        || bare.starts_with("@/")
+        || bare.contains(':')
+    {
+        return None;
+    }
+
+    let mut parts = bare.splitn(3, '/');
+    let first = parts.next()?;
+    if first.starts_with('@') {
+        let second = parts.next()?;
+        Some(&bare[..first.len() + 1 + second.len()])
+    } else {
+        Some(first)
+    }
+}
+
+/// Declared dependencies that none of the sources import, sorted.
+pub(crate) fn unused_dependencies<'a>(
+    declared: impl IntoIterator<Item = &'a String>,
+    sources: &[&str],
+) -> Vec<String> {
+    let imported: Vec<String> = sources
+        .iter()
+        .flat_map(|source| import_specifiers(source))
+        .collect();
+    let packages: Vec<&str> = imported.iter().filter_map(|s| package_name(s)).collect();
+
+    let mut unused: Vec<String> = declared
+        .into_iter()
+        .filter(|name| !packages.contains(&name.as_str()))
+        .cloned()
+        .collect();
+    unused.sort();
+    unused
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn denylist() -> Vec<String> {
+        DEFAULT_DENYLIST.iter().map(|m| m.to_string()).collect()
+    }
+
+    #[test]
+    fn test_denied_import_is_found() {
+        let code = r#"
+            import React from 'react';
+            import { readFileSync } from "node:fs";
+            export default () => <div>{readFileSync('/etc/passwd')}</div>;
+        "#;
+
+        assert_eq!(
+            find_denied_import(code, &denylist()),
+            Some("node:fs".to_string())
+        );
+        assert_eq!(
+            find_denied_import("const cp = require('child_process');", &denylist()),
+            Some("child_process".to_string())
+        );
+        assert_eq!(
+            find_denied_import("await import('fs/promises')", &denylist()),
+            Some("fs/promises".to_string())
+        );
+    }
+
+    #[test]
+    fn test_clean_code_passes() {
+        let code = r#"
+            import React, { useState } from 'react';
+            import { Button } from '@/components/ui/button';
+            import './styles.css';
+            const fromage = 'fs';
+            export default function Demo() { return <Button>{fromage}</Button>; }
+        "#;
+
+        assert_eq!(find_denied_import(code, &denylist()), None);
+        assert_eq!(
+            import_specifiers(code),
+            vec!["./styles.css", "@/components/ui/button", "react"]
+        );
+    }
+
+    #[test]
+    fn test_unused_dependency_is_reported() {
+        let declared = [
+            "date-fns".to_string(),
+            "@tanstack/react-query".to_string(),
+            "left-pad".to_string(),
+        ];
+        let component = r#"
+            import { format } from 'date-fns/format';
+            import { useQuery } from "@tanstack/react-query";
+            import { cn } from '@/lib/utils';
+        "#;
+
+        assert_eq!(
+            unused_dependencies(&declared, &[component]),
+            vec!["left-pad"]
+        );
+    }
+
+    #[test]
+    fn test_package_name() {
+        assert_eq!(package_name("react-dom/client"), Some("react-dom"));
+        assert_eq!(
+            package_name("@radix-ui/react-slot"),
+            Some("@radix-ui/react-slot")
+        );
+        assert_eq!(package_name("./Card"), None);
+        assert_eq!(package_name("@/components/ui/button"), None);
+        assert_eq!(package_name("node:fs"), None);
+    }
+}
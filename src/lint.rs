@@ -0,0 +1,159 @@
+//! Syntax check of a component's sources with `bun build --no-bundle`, which
+//! parses and transpiles each file on its own without resolving imports or
+//! touching `node_modules`.
+
+use serde::Serialize;
+use std::path::Path;
+
+/// Directory, inside the lint workspace, that Bun's transpiled output goes
+/// to. Nothing in it is kept.
+const LINT_OUT_DIR: &str = ".lint-out";
+
+/// Extensions Bun can transpile; other submitted files are left unchecked.
+const LINTED_EXTENSIONS: &[&str] = &["tsx", "ts", "jsx", "js"];
+
+pub(crate) fn is_linted(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| LINTED_EXTENSIONS.contains(&ext))
+}
+
+/// Arguments to `bun` that transpile `sources` (relative to the lint
+/// workspace) one by one.
+pub(crate) fn lint_args(sources: &[String]) -> Vec<String> {
+    let mut args = vec![
+        "build".to_string(),
+        "--no-bundle".to_string(),
+        "--outdir".to_string(),
+        format!("./{}", LINT_OUT_DIR),
+    ];
+    args.extend(sources.iter().map(|source| format!("./{}", source)));
+    args
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Severity {
+    Error,
+    Warning,
+}
+
+/// One problem Bun reported, located in a source file when Bun said where.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// Path relative to the lint workspace, e.g. `UserComponent.tsx`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub column: Option<u32>,
+}
+
+/// Reads Bun's diagnostics out of its output: each starts with an `error:` or
+/// `warning:` line and may be followed by `at {path}:{line}:{column}`. Paths
+/// under `root` are made relative to it.
+pub(crate) fn parse_diagnostics(output: &str, root: &Path) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = Vec::new();
+
+    for line in output.lines().map(str::trim) {
+        let severity = if let Some(message) = line.strip_prefix("error: ") {
+            Some((Severity::Error, message))
+        } else {
+            line.strip_prefix("warning: ")
+                .map(|message| (Severity::Warning, message))
+        };
+
+        if let Some((severity, message)) = severity {
+            diagnostics.push(Diagnostic {
+                severity,
+                message: message.to_string(),
+                file: None,
+                line: None,
+                column: None,
+            });
+            continue;
+        }
+
+        let Some(location) = line.strip_prefix("at ") else {
+            continue;
+        };
+        let Some(last) = diagnostics.last_mut().filter(|last| last.file.is_none()) else {
+            continue;
+        };
+
+        let mut parts = location.rsplitn(3, ':');
+        let column = parts.next().and_then(|column| column.parse().ok());
+        let line = parts.next().and_then(|line| line.parse().ok());
+        let (Some(column), Some(line), Some(path)) = (column, line, parts.next()) else {
+            continue;
+        };
+
+        let path = Path::new(path);
+        let relative = path
+            .strip_prefix(root)
+            .or_else(|_| path.strip_prefix("."))
+            .unwrap_or(path);
+        last.file = Some(relative.to_string_lossy().into_owned());
+        last.line = Some(line);
+        last.column = Some(column);
+    }
+
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bun_errors_are_located() {
+        let stderr = "1 | export default () => <div>\n\
+                      \x20                             ^\n\
+                      error: Unexpected end of file\n\
+                      \x20   at /tmp/abc-lint/UserComponent.tsx:1:27\n\
+                      \n\
+                      warning: \"React\" is not defined\n";
+
+        let diagnostics = parse_diagnostics(stderr, Path::new("/tmp/abc-lint"));
+
+        assert_eq!(
+            diagnostics,
+            vec![
+                Diagnostic {
+                    severity: Severity::Error,
+                    message: "Unexpected end of file".to_string(),
+                    file: Some("UserComponent.tsx".to_string()),
+                    line: Some(1),
+                    column: Some(27),
+                },
+                Diagnostic {
+                    severity: Severity::Warning,
+                    message: "\"React\" is not defined".to_string(),
+                    file: None,
+                    line: None,
+                    column: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_only_scripts_are_linted() {
+        assert!(is_linted("components/Button.tsx"));
+        assert!(!is_linted("data/items.json"));
+        assert_eq!(
+            lint_args(&["UserComponent.tsx".to_string()]),
+            [
+                "build",
+                "--no-bundle",
+                "--outdir",
+                "./.lint-out",
+                "./UserComponent.tsx"
+            ]
+        );
+    }
+}
@@ -0,0 +1,48 @@
+use lambda_http::tracing;
+use std::env;
+use tracing_subscriber::{
+    filter::LevelFilter,
+    fmt::{
+        format::{Format, Json, JsonFields},
+        Subscriber,
+    },
+    util::SubscriberInitExt,
+    EnvFilter,
+};
+
+/// Installs the global tracing subscriber. With `LOG_FORMAT=json` every event
+/// is written as a single JSON object whose fields (`component_id` etc.) are
+/// top-level keys; otherwise the runtime's default format is kept.
+pub(crate) fn init() {
+    if env::var("LOG_FORMAT").is_ok_and(|v| v == "json") {
+        json_subscriber().init();
+    } else {
+        tracing::init_default_subscriber();
+    }
+}
+
+fn json_subscriber() -> Subscriber<JsonFields, Format<Json>, EnvFilter> {
+    let filter = EnvFilter::builder()
+        .with_default_directive(LevelFilter::INFO.into())
+        .from_env_lossy();
+
+    tracing_subscriber::fmt()
+        .json()
+        .flatten_event(true)
+        .with_current_span(true)
+        .with_target(false)
+        .with_ansi(false)
+        .with_env_filter(filter)
+        .finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_subscriber_builds() {
+        let _guard = json_subscriber().set_default();
+        tracing::info!(component_id = "abc", "JSON logging enabled");
+    }
+}
@@ -1,12 +1,33 @@
-use lambda_http::{run, service_fn, tracing, Error};
+use lambda_http::{run, run_with_streaming_response, service_fn, tracing, Error};
+mod bundler;
+mod cdn;
+mod entry;
+mod html;
 mod http_handler;
-use http_handler::function_handler;
+mod imports;
+mod lint;
+mod logging;
+mod patch;
+mod progress;
+mod storage;
+mod tailwind;
+mod workspace;
+use http_handler::{function_handler, startup_bucket_check, streaming_handler};
+use std::env;
 
 #[tokio::main]
 async fn main() -> Result<(), Error> {
-    tracing::init_default_subscriber();
+    logging::init();
 
-    let result = run(service_fn(function_handler)).await;
+    if env::var("VERIFY_BUCKET_ON_START").is_ok_and(|v| v == "1") {
+        startup_bucket_check().await;
+    }
+
+    let result = if env::var("STREAMING").is_ok_and(|v| v == "1") {
+        run_with_streaming_response(service_fn(streaming_handler)).await
+    } else {
+        run(service_fn(function_handler)).await
+    };
 
     if let Err(e) = &result {
         tracing::error!("Lambda runtime failed: {}", e);
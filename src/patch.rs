@@ -0,0 +1,549 @@
+//! Application of unified diffs (as produced by `git diff` or `diff -u`) to
+//! the template files in a workspace.
+
+use crate::workspace::contained_path;
+use std::{fmt, io, path::Path};
+use tokio::fs;
+
+/// Why [`apply_patch`] changed nothing, or stopped partway.
+#[derive(Debug)]
+pub(crate) enum PatchError {
+    /// The patch is malformed, escapes the workspace or doesn't match the
+    /// template: a problem with the request.
+    Rejected(String),
+    /// Reading or writing the workspace failed.
+    Io { message: String, source: io::Error },
+}
+
+impl PatchError {
+    fn io(message: String, source: io::Error) -> Self {
+        Self::Io { message, source }
+    }
+}
+
+impl From<String> for PatchError {
+    fn from(message: String) -> Self {
+        Self::Rejected(message)
+    }
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Rejected(message) | Self::Io { message, .. } => f.write_str(message),
+        }
+    }
+}
+
+/// Changes to one file. A `None` side is `/dev/null`: the file is created
+/// or deleted rather than modified.
+#[derive(Debug, PartialEq)]
+pub(crate) struct FilePatch {
+    pub old_path: Option<String>,
+    pub new_path: Option<String>,
+    hunks: Vec<Hunk>,
+}
+
+#[derive(Debug, PartialEq)]
+struct Hunk {
+    /// 1-based line the hunk starts at in the original; for a hunk that
+    /// removes nothing, the line it inserts after.
+    old_start: usize,
+    old_count: usize,
+    lines: Vec<HunkLine>,
+}
+
+#[derive(Debug, PartialEq)]
+enum HunkLine {
+    Context(String),
+    Remove(String),
+    Add(String),
+}
+
+/// Template files a patch may change by name. Anything Bun, Tailwind or
+/// PostCSS executes during the build (`*.config.*`, `bunfig.toml`, package
+/// scripts) is missing on purpose: the patch comes from the request, and
+/// the build runs with the worker's S3 and CloudFront role.
+const PATCHABLE_FILES: &[&str] = &[
+    "package.json",
+    "tsconfig.json",
+    "components.json",
+    "globals.css",
+    "entrypoint.tsx",
+];
+
+/// Template directories whose sources (see [`PATCHABLE_EXTENSIONS`]) a
+/// patch may change; they are bundled for the browser, never run here.
+const PATCHABLE_DIRS: &[&str] = &["components", "hooks", "lib"];
+
+const PATCHABLE_EXTENSIONS: &[&str] = &["ts", "tsx", "css"];
+
+/// Whether a patch may create, change or delete `path`.
+fn is_patchable(path: &str) -> bool {
+    let segments: Vec<&str> = path
+        .split('/')
+        .filter(|segment| !segment.is_empty() && *segment != ".")
+        .collect();
+    if segments.contains(&"..") {
+        return false;
+    }
+    let Some((name, dirs)) = segments.split_last() else {
+        return false;
+    };
+    if name.contains(".config.") || *name == "bunfig.toml" {
+        return false;
+    }
+
+    match dirs.first() {
+        None => PATCHABLE_FILES.contains(name),
+        Some(dir) => {
+            PATCHABLE_DIRS.contains(dir)
+                && name
+                    .rsplit_once('.')
+                    .is_some_and(|(_, extension)| PATCHABLE_EXTENSIONS.contains(&extension))
+        }
+    }
+}
+
+const PACKAGE_SCRIPTS_MESSAGE: &str = "patch may not change package.json scripts";
+
+/// Checks every file the patch names is one it may change, and that it
+/// doesn't add `scripts` to `package.json`; [`apply_patch`] also compares
+/// the scripts before and after, which lines of the diff alone can't show.
+pub(crate) fn check_targets(files: &[FilePatch]) -> Result<(), String> {
+    for file in files {
+        for path in file.old_path.iter().chain(&file.new_path) {
+            if !is_patchable(path) {
+                return Err(format!("patch may not change {}", path));
+            }
+        }
+
+        let is_package = file
+            .new_path
+            .as_deref()
+            .is_some_and(|path| path.rsplit('/').next() == Some("package.json"));
+        let adds_scripts = file
+            .hunks
+            .iter()
+            .flat_map(|hunk| &hunk.lines)
+            .any(|line| matches!(line, HunkLine::Add(text) if text.contains("\"scripts\"")));
+        if is_package && adds_scripts {
+            return Err(PACKAGE_SCRIPTS_MESSAGE.to_string());
+        }
+    }
+
+    Ok(())
+}
+
+/// The `scripts` of a `package.json`, or `None` if it has none or isn't
+/// valid JSON.
+fn package_scripts(package: &str) -> Option<serde_json::Value> {
+    serde_json::from_str::<serde_json::Value>(package)
+        .ok()?
+        .get("scripts")
+        .cloned()
+}
+
+/// Every line the patch adds, joined by newlines, for scanning what it puts
+/// into the template; empty if it doesn't parse.
+pub(crate) fn added_text(patch: &str) -> String {
+    let files = parse_patch(patch).unwrap_or_default();
+    let added: Vec<&str> = files
+        .iter()
+        .flat_map(|file| &file.hunks)
+        .flat_map(|hunk| &hunk.lines)
+        .filter_map(|line| match line {
+            HunkLine::Add(text) => Some(text.as_str()),
+            _ => None,
+        })
+        .collect();
+    added.join("\n")
+}
+
+/// Splits a diff into per-file patches, checking every hunk's line counts
+/// against its header. Lines outside of file sections (`diff --git`,
+/// `index`, mode lines) are ignored.
+pub(crate) fn parse_patch(patch: &str) -> Result<Vec<FilePatch>, String> {
+    let mut files: Vec<FilePatch> = Vec::new();
+    let mut lines = patch.lines().peekable();
+
+    while let Some(line) = lines.next() {
+        let Some(old) = line.strip_prefix("--- ") else {
+            continue;
+        };
+        let Some(new) = lines.next().and_then(|next| next.strip_prefix("+++ ")) else {
+            return Err("patch is malformed: expected +++ after ---".to_string());
+        };
+
+        let mut file = FilePatch {
+            old_path: diff_path(old, "a/")?,
+            new_path: diff_path(new, "b/")?,
+            hunks: Vec::new(),
+        };
+        if file.old_path.is_none() && file.new_path.is_none() {
+            return Err("patch is malformed: both sides are /dev/null".to_string());
+        }
+
+        while let Some(header) = lines.next_if(|next| next.starts_with("@@ ")) {
+            let (old_start, old_count, new_count) = parse_hunk_header(header)?;
+            let mut hunk = Hunk {
+                old_start,
+                old_count,
+                lines: Vec::new(),
+            };
+
+            let (mut old_seen, mut new_seen) = (0, 0);
+            while old_seen < old_count || new_seen < new_count {
+                let Some(line) = lines.next() else {
+                    return Err(format!("patch is malformed: hunk ends early: {}", header));
+                };
+                // Every marker is one byte, so the text follows at index 1.
+                let text = line.get(1..).unwrap_or_default().to_string();
+                match line.chars().next() {
+                    Some(' ') | None => {
+                        hunk.lines.push(HunkLine::Context(text));
+                        old_seen += 1;
+                        new_seen += 1;
+                    }
+                    Some('-') => {
+                        hunk.lines.push(HunkLine::Remove(text));
+                        old_seen += 1;
+                    }
+                    Some('+') => {
+                        hunk.lines.push(HunkLine::Add(text));
+                        new_seen += 1;
+                    }
+                    Some('\\') => {}
+                    _ => return Err(format!("patch is malformed: unexpected line: {}", line)),
+                }
+            }
+            if old_seen != old_count || new_seen != new_count {
+                return Err(format!(
+                    "patch is malformed: hunk doesn't match its header: {}",
+                    header
+                ));
+            }
+
+            // "\ No newline at end of file" may follow the last line.
+            lines.next_if(|next| next.starts_with('\\'));
+            file.hunks.push(hunk);
+        }
+
+        files.push(file);
+    }
+
+    if files.is_empty() {
+        return Err("patch contains no file changes".to_string());
+    }
+
+    Ok(files)
+}
+
+/// The path named by a `---`/`+++` line, without its `a/`/`b/` prefix or any
+/// tab-separated timestamp.
+fn diff_path(spec: &str, prefix: &str) -> Result<Option<String>, String> {
+    let path = spec.split('\t').next().unwrap_or(spec).trim_end();
+    if path == "/dev/null" {
+        return Ok(None);
+    }
+
+    let path = path.strip_prefix(prefix).unwrap_or(path);
+    if path.is_empty() {
+        return Err("patch is malformed: missing file path".to_string());
+    }
+
+    Ok(Some(path.to_string()))
+}
+
+/// Parses `@@ -l[,s] +l[,s] @@`, returning the old start and both counts.
+fn parse_hunk_header(header: &str) -> Result<(usize, usize, usize), String> {
+    let malformed = || format!("patch is malformed: bad hunk header: {}", header);
+    let range = |spec: Option<&str>, sign: char| -> Result<(usize, usize), String> {
+        let spec = spec
+            .and_then(|s| s.strip_prefix(sign))
+            .ok_or_else(malformed)?;
+        let (start, count) = spec.split_once(',').unwrap_or((spec, "1"));
+        Ok((
+            start.parse().map_err(|_| malformed())?,
+            count.parse().map_err(|_| malformed())?,
+        ))
+    };
+
+    let mut parts = header.split_whitespace().skip(1);
+    let (old_start, old_count) = range(parts.next(), '-')?;
+    let (_, new_count) = range(parts.next(), '+')?;
+    if parts.next() != Some("@@") {
+        return Err(malformed());
+    }
+
+    Ok((old_start, old_count, new_count))
+}
+
+/// Applies `hunks` to `original`. Each hunk's context and removed lines must
+/// match exactly, though the hunk may sit at an offset from its header's
+/// line number, as after an earlier edit to the same file.
+fn apply_hunks(original: &str, hunks: &[Hunk]) -> Result<String, String> {
+    let lines: Vec<&str> = original.lines().collect();
+    let mut output: Vec<&str> = Vec::new();
+    let mut cursor = 0;
+
+    for (index, hunk) in hunks.iter().enumerate() {
+        let expected: Vec<&str> = hunk
+            .lines
+            .iter()
+            .filter_map(|line| match line {
+                HunkLine::Context(text) | HunkLine::Remove(text) => Some(text.as_str()),
+                HunkLine::Add(_) => None,
+            })
+            .collect();
+
+        let wanted = if hunk.old_count == 0 {
+            hunk.old_start
+        } else {
+            hunk.old_start.saturating_sub(1)
+        };
+        let position = (cursor..=lines.len().saturating_sub(expected.len()))
+            .filter(|&at| lines[at..].starts_with(&expected))
+            .min_by_key(|&at| at.abs_diff(wanted))
+            .ok_or_else(|| format!("hunk {} does not apply", index + 1))?;
+
+        output.extend(&lines[cursor..position]);
+        let mut at = position;
+        for line in &hunk.lines {
+            match line {
+                HunkLine::Context(_) => {
+                    output.push(lines[at]);
+                    at += 1;
+                }
+                HunkLine::Remove(_) => at += 1,
+                HunkLine::Add(text) => output.push(text),
+            }
+        }
+        cursor = at;
+    }
+    output.extend(&lines[cursor..]);
+
+    let mut patched = output.join("\n");
+    if !patched.is_empty() && (original.is_empty() || original.ends_with('\n')) {
+        patched.push('\n');
+    }
+    Ok(patched)
+}
+
+/// Applies a unified diff to the files under `root`. Every path must stay
+/// inside `root` (see [`contained_path`]); a path that escapes it or a hunk
+/// that doesn't match is reported before any file is changed.
+pub(crate) async fn apply_patch(root: &Path, patch: &str) -> Result<(), PatchError> {
+    let mut writes = Vec::new();
+
+    for file in parse_patch(patch)? {
+        let old_path = match &file.old_path {
+            Some(path) => Some(contained_path(root, path).await?),
+            None => None,
+        };
+        let new_path = match &file.new_path {
+            Some(path) => Some(contained_path(root, path).await?),
+            None => None,
+        };
+        let name = file.new_path.as_deref().or(file.old_path.as_deref());
+        let name = name.unwrap_or_default();
+
+        let original = match &old_path {
+            Some(path) => match fs::read_to_string(path).await {
+                Ok(original) => original,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    return Err(format!("patch names a missing file: {}", name).into());
+                }
+                Err(e) => return Err(PatchError::io(format!("Failed to read {}: {}", name, e), e)),
+            },
+            None => String::new(),
+        };
+        let patched = apply_hunks(&original, &file.hunks)
+            .map_err(|message| format!("patch does not apply to {}: {}", name, message))?;
+        if name.rsplit('/').next() == Some("package.json")
+            && package_scripts(&patched) != package_scripts(&original)
+        {
+            return Err(PACKAGE_SCRIPTS_MESSAGE.to_string().into());
+        }
+
+        writes.push((old_path, new_path, patched));
+    }
+
+    for (old_path, new_path, patched) in writes {
+        match new_path {
+            Some(path) => {
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent).await.map_err(|e| {
+                        PatchError::io(format!("Failed to create {}: {}", parent.display(), e), e)
+                    })?;
+                }
+                fs::write(&path, patched).await.map_err(|e| {
+                    PatchError::io(format!("Failed to write {}: {}", path.display(), e), e)
+                })?;
+                if let Some(old_path) = old_path.filter(|old_path| *old_path != path) {
+                    remove(&old_path).await?;
+                }
+            }
+            None => {
+                if let Some(old_path) = old_path {
+                    remove(&old_path).await?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn remove(path: &Path) -> Result<(), PatchError> {
+    fs::remove_file(path)
+        .await
+        .map_err(|e| PatchError::io(format!("Failed to remove {}: {}", path.display(), e), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PACKAGE_JSON: &str = r#"{
+  "name": "template",
+  "dependencies": {
+    "react": "^19.1.0"
+  }
+}
+"#;
+
+    #[tokio::test]
+    async fn test_patch_changes_package_json() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("package.json"), PACKAGE_JSON).unwrap();
+        let patch = r#"diff --git a/package.json b/package.json
+--- a/package.json
++++ b/package.json
+@@ -2,5 +2,6 @@
+   "name": "template",
+   "dependencies": {
+-    "react": "^19.1.0"
++    "react": "^19.1.0",
++    "zod": "^3.25.0"
+   }
+ }
+"#;
+
+        apply_patch(dir.path(), patch).await.unwrap();
+
+        let package = std::fs::read_to_string(dir.path().join("package.json")).unwrap();
+        assert!(package.contains(r#""react": "^19.1.0","#));
+        assert!(package.contains(r#""zod": "^3.25.0""#));
+        assert!(package.ends_with("}\n"));
+    }
+
+    #[tokio::test]
+    async fn test_patch_outside_workspace_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let patch = "--- /dev/null\n+++ b/../escape.txt\n@@ -0,0 +1 @@\n+oops\n";
+
+        let err = apply_patch(dir.path(), patch).await.unwrap_err();
+
+        assert!(matches!(err, PatchError::Rejected(_)), "{:?}", err);
+        assert!(
+            err.to_string().starts_with("path escapes the workspace"),
+            "{}",
+            err
+        );
+        assert!(!dir.path().join("../escape.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn test_mismatched_context_changes_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("package.json"), PACKAGE_JSON).unwrap();
+        std::fs::write(dir.path().join("a.txt"), "one\n").unwrap();
+        let patch = "--- a/a.txt\n+++ b/a.txt\n@@ -1 +1 @@\n-one\n+two\n\
+                     --- a/package.json\n+++ b/package.json\n@@ -1 +1 @@\n-[\n+{\n";
+
+        let err = apply_patch(dir.path(), patch).await.unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "patch does not apply to package.json: hunk 1 does not apply"
+        );
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("a.txt")).unwrap(),
+            "one\n"
+        );
+    }
+
+    #[test]
+    fn test_only_non_executable_files_are_patchable() {
+        for path in [
+            "package.json",
+            "./tsconfig.json",
+            "lib/utils.ts",
+            "components/ui/card.tsx",
+        ] {
+            assert!(is_patchable(path), "{}", path);
+        }
+        for path in [
+            "tailwind.config.js",
+            "postcss.config.js",
+            "bunfig.toml",
+            "lib/tailwind.config.ts",
+            "lib/../bunfig.toml",
+            "lib/setup.sh",
+            "index.ts",
+        ] {
+            assert!(!is_patchable(path), "{}", path);
+        }
+
+        let patch = "--- /dev/null\n+++ b/bunfig.toml\n@@ -0,0 +1 @@\n+preload = [\"./x.ts\"]\n";
+        assert_eq!(
+            check_targets(&parse_patch(patch).unwrap()).unwrap_err(),
+            "patch may not change bunfig.toml"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_patch_changing_existing_scripts_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let package = "{\n  \"scripts\": {\n    \"build\": \"bun build\"\n  }\n}\n";
+        std::fs::write(dir.path().join("package.json"), package).unwrap();
+        let patch = "--- a/package.json\n+++ b/package.json\n@@ -2,3 +2,3 @@\n   \"scripts\": {\n-    \"build\": \"bun build\"\n+    \"build\": \"sh ./pwn.sh\"\n   }\n";
+
+        assert!(check_targets(&parse_patch(patch).unwrap()).is_ok());
+        let err = apply_patch(dir.path(), patch).await.unwrap_err();
+
+        assert_eq!(err.to_string(), "patch may not change package.json scripts");
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("package.json")).unwrap(),
+            package
+        );
+    }
+
+    #[tokio::test]
+    async fn test_workspace_io_failures_are_not_rejections() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("a.txt")).unwrap();
+        let patch = "--- a/a.txt\n+++ b/a.txt\n@@ -1 +1 @@\n-one\n+two\n";
+
+        let err = apply_patch(dir.path(), patch).await.unwrap_err();
+        assert!(matches!(err, PatchError::Io { .. }), "{:?}", err);
+
+        let missing = "--- a/b.txt\n+++ b/b.txt\n@@ -1 +1 @@\n-one\n+two\n";
+        let err = apply_patch(dir.path(), missing).await.unwrap_err();
+        assert!(matches!(err, PatchError::Rejected(_)), "{:?}", err);
+    }
+
+    #[test]
+    fn test_hunk_counts_are_checked() {
+        let patch = "--- a/a.txt\n+++ b/a.txt\n@@ -1,2 +1,2 @@\n-one\n+two\n";
+
+        assert!(parse_patch(patch)
+            .unwrap_err()
+            .starts_with("patch is malformed: hunk ends early"));
+        assert_eq!(
+            parse_patch("not a diff").unwrap_err(),
+            "patch contains no file changes"
+        );
+    }
+}
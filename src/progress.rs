@@ -0,0 +1,95 @@
+//! Build progress events for clients using the streaming response.
+
+use serde_json::{json, Value};
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+/// Reports build phases as they start. The default reporter discards events,
+/// which is what the buffered handler uses.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Progress {
+    sender: Option<UnboundedSender<Value>>,
+    /// Each phase started so far and when, shared between clones.
+    phases: Arc<Mutex<Vec<(String, Instant)>>>,
+}
+
+impl Progress {
+    /// A reporter whose events can be read from the returned receiver.
+    pub(crate) fn channel() -> (Self, UnboundedReceiver<Value>) {
+        let (sender, receiver) = unbounded_channel();
+        (
+            Self {
+                sender: Some(sender),
+                ..Default::default()
+            },
+            receiver,
+        )
+    }
+
+    pub(crate) fn start(&self, phase: &str) {
+        self.phases
+            .lock()
+            .unwrap()
+            .push((phase.to_string(), Instant::now()));
+
+        if let Some(sender) = &self.sender {
+            // The receiver only goes away if the client disconnected.
+            let _ = sender.send(json!({ "phase": phase, "status": "start" }));
+        }
+    }
+
+    /// How long each phase started so far ran: until the next one started,
+    /// or until now for the latest.
+    pub(crate) fn timings(&self) -> Vec<(String, Duration)> {
+        let phases = self.phases.lock().unwrap();
+        let now = Instant::now();
+        phases
+            .iter()
+            .enumerate()
+            .map(|(index, (phase, started))| {
+                let ended = phases.get(index + 1).map_or(now, |(_, next)| *next);
+                (phase.clone(), ended.duration_since(*started))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_events_are_delivered_in_order() {
+        let (progress, mut events) = Progress::channel();
+
+        progress.start("bundle");
+        progress.start("upload");
+
+        assert_eq!(
+            events.try_recv().unwrap(),
+            json!({ "phase": "bundle", "status": "start" })
+        );
+        assert_eq!(events.try_recv().unwrap()["phase"], "upload");
+    }
+
+    #[test]
+    fn test_default_reporter_discards_events() {
+        Progress::default().start("bundle");
+    }
+
+    #[test]
+    fn test_timings_cover_each_phase() {
+        let progress = Progress::default();
+        progress.start("bundle");
+        std::thread::sleep(Duration::from_millis(5));
+        progress.clone().start("upload");
+
+        let timings = progress.timings();
+        let phases: Vec<&str> = timings.iter().map(|(phase, _)| phase.as_str()).collect();
+        assert_eq!(phases, ["bundle", "upload"]);
+        assert!(timings[0].1 >= Duration::from_millis(5));
+    }
+}
@@ -0,0 +1,552 @@
+use aws_sdk_s3::{
+    error::ProvideErrorMetadata, presigning::PresigningConfig, primitives::ByteStream, Client,
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use lambda_http::Error;
+use sha2::{Digest, Sha256};
+use std::{collections::BTreeMap, fmt, time::Duration};
+use tokio::sync::Semaphore;
+
+/// A single object to be written to the bucket.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct PutObject {
+    pub key: String,
+    pub body: Vec<u8>,
+    pub content_type: String,
+    /// URL-encoded tag set, as produced by [`encode_tagging`].
+    pub tagging: Option<String>,
+    /// User metadata, stored by S3 as `x-amz-meta-{key}` headers.
+    pub metadata: BTreeMap<String, String>,
+    /// Base64 SHA-256 of `body`, from [`sha256_of`], which S3 checks the
+    /// received bytes against.
+    pub checksum_sha256: Option<String>,
+    /// `Cache-Control` S3 (and CloudFront in front of it) serves the object
+    /// with.
+    pub cache_control: Option<String>,
+    /// Digest of everything above except `body`, from
+    /// [`PutObject::attributes_digest`], stored alongside the object so a
+    /// later upload of the same bytes can tell whether anything else changed.
+    pub attributes: Option<String>,
+}
+
+impl PutObject {
+    /// Hex SHA-256 over the content type, tags, metadata and cache control.
+    pub(crate) fn attributes_digest(&self) -> String {
+        let attributes = serde_json::json!([
+            self.content_type,
+            self.tagging,
+            self.metadata,
+            self.cache_control,
+        ]);
+        format!("{:x}", Sha256::digest(attributes.to_string()))
+    }
+}
+
+/// What a HEAD request reports about a stored object.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ObjectHead {
+    /// The ETag, without surrounding quotes.
+    pub etag: String,
+    /// The [`PutObject::attributes`] it was stored with, if any.
+    pub attributes: Option<String>,
+}
+
+/// User metadata key [`PutObject::attributes`] is stored under.
+const ATTRIBUTES_METADATA: &str = "nimbus-attributes";
+
+/// S3 allows at most this many tags on an object.
+const MAX_TAGS: usize = 10;
+
+/// Checks a tag against S3's limits: keys up to 128 and values up to 256
+/// characters, made of letters, digits, spaces and `+ - = . _ : / @`.
+pub(crate) fn validate_tag(key: &str, value: &str) -> Result<(), String> {
+    let allowed = |c: char| c.is_alphanumeric() || " +-=._:/@".contains(c);
+
+    if key.is_empty() || key.chars().count() > 128 || !key.chars().all(allowed) {
+        return Err(format!("invalid tag key: {}", key));
+    }
+    if value.chars().count() > 256 || !value.chars().all(allowed) {
+        return Err(format!("invalid value for tag {}: {}", key, value));
+    }
+    if key.starts_with("aws:") {
+        return Err(format!(
+            "tag keys may not use the reserved aws: prefix: {}",
+            key
+        ));
+    }
+
+    Ok(())
+}
+
+/// Parses `key=value` pairs separated by commas, as used by `S3_OBJECT_TAGS`.
+pub(crate) fn parse_tag_list(list: &str) -> Result<Vec<(String, String)>, String> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| match pair.split_once('=') {
+            Some((key, value)) => Ok((key.trim().to_string(), value.trim().to_string())),
+            None => Err(format!("expected key=value, got: {}", pair)),
+        })
+        .collect()
+}
+
+/// Validates a tag set and encodes it as the query string `put_object`
+/// expects for `x-amz-tagging`.
+pub(crate) fn encode_tagging(tags: &[(String, String)]) -> Result<String, String> {
+    if tags.len() > MAX_TAGS {
+        return Err(format!(
+            "at most {} object tags are allowed, got {}",
+            MAX_TAGS,
+            tags.len()
+        ));
+    }
+
+    let mut pairs = Vec::with_capacity(tags.len());
+    for (key, value) in tags {
+        validate_tag(key, value)?;
+        pairs.push(format!("{}={}", percent_encode(key), percent_encode(value)));
+    }
+
+    Ok(pairs.join("&"))
+}
+
+fn percent_encode(input: &str) -> String {
+    let mut encoded = String::with_capacity(input.len());
+    for byte in input.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// The configured bucket doesn't exist, which is a deployment problem rather
+/// than anything wrong with the request.
+#[derive(Debug)]
+pub(crate) struct NoSuchBucket(pub String);
+
+impl fmt::Display for NoSuchBucket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "S3 bucket '{}' does not exist; check the S3_BUCKET_NAME configuration",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for NoSuchBucket {}
+
+/// S3 received different bytes than were sent for the object at this key.
+#[derive(Debug)]
+pub(crate) struct ChecksumMismatch(pub String);
+
+impl fmt::Display for ChecksumMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "checksum mismatch uploading {}", self.0)
+    }
+}
+
+impl std::error::Error for ChecksumMismatch {}
+
+/// The subset of S3 operations the handler relies on. Implemented for the real
+/// `aws_sdk_s3::Client` and for an in-memory store in tests.
+pub(crate) trait ObjectStore {
+    async fn bucket_exists(&self, bucket: &str) -> Result<bool, Error>;
+
+    /// Returns the object's ETag and stored attributes, or `None` if the
+    /// object does not exist.
+    async fn head(&self, bucket: &str, key: &str) -> Result<Option<ObjectHead>, Error>;
+
+    async fn put(&self, bucket: &str, object: PutObject) -> Result<(), Error>;
+
+    /// Returns the object's contents, or `None` if it does not exist.
+    async fn get(&self, bucket: &str, key: &str) -> Result<Option<Vec<u8>>, Error>;
+
+    /// Returns a time-limited URL that can be used to download the object.
+    async fn presign_get(
+        &self,
+        bucket: &str,
+        key: &str,
+        expires_in: Duration,
+    ) -> Result<String, Error>;
+}
+
+impl ObjectStore for Client {
+    async fn bucket_exists(&self, bucket: &str) -> Result<bool, Error> {
+        match self.head_bucket().bucket(bucket).send().await {
+            Ok(_) => Ok(true),
+            Err(err) if err.as_service_error().is_some_and(|e| e.is_not_found()) => Ok(false),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn head(&self, bucket: &str, key: &str) -> Result<Option<ObjectHead>, Error> {
+        match self.head_object().bucket(bucket).key(key).send().await {
+            Ok(output) => Ok(output.e_tag().map(|tag| ObjectHead {
+                etag: tag.trim_matches('"').to_string(),
+                attributes: output
+                    .metadata()
+                    .and_then(|metadata| metadata.get(ATTRIBUTES_METADATA))
+                    .cloned(),
+            })),
+            Err(err) if err.as_service_error().is_some_and(|e| e.is_not_found()) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn put(&self, bucket: &str, mut object: PutObject) -> Result<(), Error> {
+        let key = object.key.clone();
+        if let Some(attributes) = object.attributes.take() {
+            object
+                .metadata
+                .insert(ATTRIBUTES_METADATA.to_string(), attributes);
+        }
+        let result = self
+            .put_object()
+            .bucket(bucket)
+            .key(object.key)
+            .body(ByteStream::from(object.body))
+            .content_type(object.content_type)
+            .set_tagging(object.tagging)
+            .set_checksum_sha256(object.checksum_sha256)
+            .set_cache_control(object.cache_control)
+            .set_metadata(
+                (!object.metadata.is_empty()).then(|| object.metadata.into_iter().collect()),
+            )
+            .send()
+            .await;
+
+        match result {
+            Ok(_) => Ok(()),
+            Err(err) if err.code() == Some("NoSuchBucket") => {
+                Err(NoSuchBucket(bucket.to_string()).into())
+            }
+            Err(err) if err.code() == Some("BadDigest") => Err(ChecksumMismatch(key).into()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn get(&self, bucket: &str, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        match self.get_object().bucket(bucket).key(key).send().await {
+            Ok(output) => Ok(Some(output.body.collect().await?.to_vec())),
+            Err(err) if err.as_service_error().is_some_and(|e| e.is_no_such_key()) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn presign_get(
+        &self,
+        bucket: &str,
+        key: &str,
+        expires_in: Duration,
+    ) -> Result<String, Error> {
+        let config = PresigningConfig::expires_in(expires_in)?;
+        let request = self
+            .get_object()
+            .bucket(bucket)
+            .key(key)
+            .presigned(config)
+            .await?;
+
+        Ok(request.uri().to_string())
+    }
+}
+
+/// An `ObjectStore` that lets at most `max_connections` requests to `inner`
+/// be in flight at once. The SDK opens a connection per concurrent request,
+/// so without a bound an upload of many files can run the container out of
+/// sockets.
+pub(crate) struct Pooled<S> {
+    inner: S,
+    max_connections: usize,
+    permits: Semaphore,
+}
+
+impl<S> Pooled<S> {
+    pub(crate) fn new(inner: S, max_connections: usize) -> Self {
+        let max_connections = max_connections.max(1);
+        Self {
+            inner,
+            max_connections,
+            permits: Semaphore::new(max_connections),
+        }
+    }
+
+    pub(crate) fn max_connections(&self) -> usize {
+        self.max_connections
+    }
+
+    /// Requests that could start right now without waiting for a slot.
+    pub(crate) fn available(&self) -> usize {
+        self.permits.available_permits()
+    }
+
+    /// The store every pooled request goes to.
+    #[cfg(test)]
+    pub(crate) fn inner(&self) -> &S {
+        &self.inner
+    }
+}
+
+impl<S: ObjectStore> ObjectStore for Pooled<S> {
+    async fn bucket_exists(&self, bucket: &str) -> Result<bool, Error> {
+        let _permit = self.permits.acquire().await?;
+        self.inner.bucket_exists(bucket).await
+    }
+
+    async fn head(&self, bucket: &str, key: &str) -> Result<Option<ObjectHead>, Error> {
+        let _permit = self.permits.acquire().await?;
+        self.inner.head(bucket, key).await
+    }
+
+    async fn put(&self, bucket: &str, object: PutObject) -> Result<(), Error> {
+        let _permit = self.permits.acquire().await?;
+        self.inner.put(bucket, object).await
+    }
+
+    async fn get(&self, bucket: &str, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        let _permit = self.permits.acquire().await?;
+        self.inner.get(bucket, key).await
+    }
+
+    // Presigning is local; it never touches the network.
+    async fn presign_get(
+        &self,
+        bucket: &str,
+        key: &str,
+        expires_in: Duration,
+    ) -> Result<String, Error> {
+        self.inner.presign_get(bucket, key, expires_in).await
+    }
+}
+
+/// ETag S3 assigns to a single-part upload of `content`.
+pub(crate) fn etag_of(content: &[u8]) -> String {
+    format!("{:x}", md5::compute(content))
+}
+
+/// The `x-amz-checksum-sha256` value for `content`.
+pub(crate) fn sha256_of(content: &[u8]) -> String {
+    BASE64.encode(Sha256::digest(content))
+}
+
+#[cfg(test)]
+pub(crate) mod mock {
+    use super::*;
+    use std::{collections::HashMap, sync::Mutex};
+
+    /// In-memory `ObjectStore` that records every put.
+    #[derive(Default)]
+    pub(crate) struct MemoryStore {
+        pub etags: Mutex<HashMap<String, String>>,
+        pub attributes: Mutex<HashMap<String, String>>,
+        pub objects: Mutex<HashMap<String, Vec<u8>>>,
+        pub puts: Mutex<Vec<PutObject>>,
+        pub missing_bucket: bool,
+        /// Every upload arrives damaged, so checksummed puts fail.
+        pub corrupt_uploads: bool,
+    }
+
+    impl MemoryStore {
+        /// A store whose bucket doesn't exist, so every write fails.
+        pub(crate) fn without_bucket() -> Self {
+            Self {
+                missing_bucket: true,
+                ..Self::default()
+            }
+        }
+
+        pub(crate) fn with_corrupted_uploads() -> Self {
+            Self {
+                corrupt_uploads: true,
+                ..Self::default()
+            }
+        }
+
+        pub(crate) fn with_etag(self, key: &str, etag: &str) -> Self {
+            self.etags
+                .lock()
+                .unwrap()
+                .insert(key.to_string(), etag.to_string());
+            self
+        }
+
+        /// Seeds an object as if a previous build had stored it.
+        pub(crate) fn with_object(self, key: &str, body: &[u8]) -> Self {
+            self.etags
+                .lock()
+                .unwrap()
+                .insert(key.to_string(), etag_of(body));
+            self.objects
+                .lock()
+                .unwrap()
+                .insert(key.to_string(), body.to_vec());
+            self
+        }
+
+        pub(crate) fn put_keys(&self) -> Vec<String> {
+            let mut keys: Vec<String> = self
+                .puts
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|p| p.key.clone())
+                .collect();
+            keys.sort();
+            keys
+        }
+
+        pub(crate) fn find_put(&self, key: &str) -> Option<PutObject> {
+            self.puts
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|p| p.key == key)
+                .cloned()
+        }
+
+        pub(crate) fn put_body(&self, key: &str) -> Option<Vec<u8>> {
+            self.puts
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|p| p.key == key)
+                .map(|p| p.body.clone())
+        }
+    }
+
+    impl ObjectStore for MemoryStore {
+        async fn bucket_exists(&self, _bucket: &str) -> Result<bool, Error> {
+            Ok(!self.missing_bucket)
+        }
+
+        async fn head(&self, _bucket: &str, key: &str) -> Result<Option<ObjectHead>, Error> {
+            Ok(self.etags.lock().unwrap().get(key).map(|etag| ObjectHead {
+                etag: etag.clone(),
+                attributes: self.attributes.lock().unwrap().get(key).cloned(),
+            }))
+        }
+
+        async fn put(&self, bucket: &str, object: PutObject) -> Result<(), Error> {
+            if self.missing_bucket {
+                return Err(NoSuchBucket(bucket.to_string()).into());
+            }
+
+            if let Some(checksum) = &object.checksum_sha256 {
+                let mut received = object.body.clone();
+                if self.corrupt_uploads {
+                    received.push(0);
+                }
+                if sha256_of(&received) != *checksum {
+                    return Err(ChecksumMismatch(object.key).into());
+                }
+            }
+
+            self.etags
+                .lock()
+                .unwrap()
+                .insert(object.key.clone(), etag_of(&object.body));
+            let mut attributes = self.attributes.lock().unwrap();
+            match &object.attributes {
+                Some(digest) => attributes.insert(object.key.clone(), digest.clone()),
+                None => attributes.remove(&object.key),
+            };
+            drop(attributes);
+            self.objects
+                .lock()
+                .unwrap()
+                .insert(object.key.clone(), object.body.clone());
+            self.puts.lock().unwrap().push(object);
+            Ok(())
+        }
+
+        async fn get(&self, _bucket: &str, key: &str) -> Result<Option<Vec<u8>>, Error> {
+            Ok(self.objects.lock().unwrap().get(key).cloned())
+        }
+
+        async fn presign_get(
+            &self,
+            bucket: &str,
+            key: &str,
+            _expires_in: Duration,
+        ) -> Result<String, Error> {
+            Ok(format!("https://{}.presigned.test/{}", bucket, key))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_tagging() {
+        let tags = vec![
+            ("component_id".to_string(), "abc".to_string()),
+            ("built_at".to_string(), "2026-01-02T03:04:05Z".to_string()),
+            ("team".to_string(), "web platform".to_string()),
+        ];
+
+        assert_eq!(
+            encode_tagging(&tags).unwrap(),
+            "component_id=abc&built_at=2026-01-02T03%3A04%3A05Z&team=web%20platform"
+        );
+    }
+
+    #[test]
+    fn test_tag_validation() {
+        assert!(validate_tag("environment", "staging").is_ok());
+        assert!(validate_tag("", "x").is_err());
+        assert!(validate_tag("env", "a&b").is_err());
+        assert!(validate_tag("aws:createdBy", "me").is_err());
+        assert!(validate_tag(&"k".repeat(129), "x").is_err());
+
+        let too_many: Vec<_> = (0..11)
+            .map(|i| (format!("k{}", i), "v".to_string()))
+            .collect();
+        assert!(encode_tagging(&too_many).is_err());
+    }
+
+    #[test]
+    fn test_parse_tag_list() {
+        assert_eq!(
+            parse_tag_list("environment=staging, team=web").unwrap(),
+            vec![
+                ("environment".to_string(), "staging".to_string()),
+                ("team".to_string(), "web".to_string())
+            ]
+        );
+        assert!(parse_tag_list("environment").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_pooled_store_bounds_requests_in_flight() {
+        let store = Pooled::new(mock::MemoryStore::default(), 2);
+        assert_eq!(store.max_connections(), 2);
+
+        let held = store.permits.acquire().await.unwrap();
+        assert_eq!(store.available(), 1);
+        store
+            .put(
+                "bucket",
+                PutObject {
+                    key: "abc/index.js".to_string(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        drop(held);
+
+        assert_eq!(store.available(), 2);
+        assert_eq!(store.inner.put_keys(), vec!["abc/index.js".to_string()]);
+        assert_eq!(
+            Pooled::new(mock::MemoryStore::default(), 0).max_connections(),
+            1
+        );
+    }
+}
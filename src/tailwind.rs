@@ -0,0 +1,389 @@
+//! Selection of the Tailwind CLI invocation for the template's major version.
+
+use crate::imports::import_specifiers;
+use std::{
+    collections::{BTreeSet, HashSet},
+    ffi::OsString,
+    path::Path,
+};
+use tokio::fs;
+
+/// Tailwind major versions with distinct CLIs: v3 ships the CLI in the
+/// `tailwindcss` package and reads `tailwind.config.js`, while v4 moved it to
+/// `@tailwindcss/cli` and configures itself from the CSS entry point.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum TailwindVersion {
+    V3,
+    V4,
+}
+
+impl TailwindVersion {
+    /// Accepts `3`, `v3`, `3.4.1` and so on.
+    pub(crate) fn parse(version: &str) -> Option<Self> {
+        let major = version.trim().trim_start_matches(['v', 'V', '^', '~']);
+        match major.split('.').next()? {
+            "3" => Some(Self::V3),
+            "4" => Some(Self::V4),
+            _ => None,
+        }
+    }
+
+    /// Arguments that compile `input` into `output`, following the runner's
+    /// own (`bun x` or `npx --yes`). `config` is the JavaScript config v3
+    /// reads; v4 ignores it.
+    pub(crate) fn args(&self, input: &Path, output: &Path, config: &str) -> Vec<OsString> {
+        let mut args: Vec<OsString> = Vec::new();
+
+        match self {
+            Self::V3 => {
+                args.push("tailwindcss".into());
+                args.push("-c".into());
+                args.push(config.into());
+            }
+            Self::V4 => args.push("@tailwindcss/cli".into()),
+        }
+
+        args.push("-i".into());
+        args.push(input.into());
+        args.push("-o".into());
+        args.push(output.into());
+        args
+    }
+}
+
+/// The template's own v3 config.
+pub(crate) const DEFAULT_CONFIG: &str = "./tailwind.config.js";
+
+/// v3 config generated next to the template's when a safelist is given.
+const SAFELIST_CONFIG: &str = "./tailwind.safelist.config.js";
+
+/// How many classes a request may safelist.
+pub(crate) const MAX_SAFELIST: usize = 500;
+
+/// Safelisted classes end up inside a JavaScript string or a CSS directive,
+/// so only the characters Tailwind class names use are allowed.
+pub(crate) fn validate_safelist_class(class: &str) -> Result<(), String> {
+    let valid = !class.is_empty()
+        && class.len() <= 128
+        && class
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_:/[].#%!()".contains(c));
+    if !valid {
+        return Err(format!("invalid safelist class: {}", class));
+    }
+
+    Ok(())
+}
+
+/// Makes Tailwind generate `classes` even though no source mentions them,
+/// returning the config to pass to [`TailwindVersion::args`]. v3 gets a
+/// config that extends the template's with a `safelist`; v4 has no config
+/// file, so an `@source inline(...)` directive is appended to `stylesheet`.
+pub(crate) async fn apply_safelist(
+    version: TailwindVersion,
+    workspace_dir: &Path,
+    stylesheet: &Path,
+    classes: &[String],
+) -> Result<&'static str, String> {
+    if classes.is_empty() {
+        return Ok(DEFAULT_CONFIG);
+    }
+
+    match version {
+        TailwindVersion::V3 => {
+            let config = format!(
+                "const config = require({base});\n\
+                 module.exports = {{ ...config, safelist: [...(config.safelist || []), ...{classes}] }};\n",
+                base = serde_json::to_string(DEFAULT_CONFIG).expect("string serializes"),
+                classes = serde_json::to_string(classes).expect("strings serialize"),
+            );
+            fs::write(workspace_dir.join(SAFELIST_CONFIG), config)
+                .await
+                .map_err(|e| format!("Failed to write Tailwind safelist config: {}", e))?;
+            Ok(SAFELIST_CONFIG)
+        }
+        TailwindVersion::V4 => {
+            let mut css = fs::read_to_string(stylesheet)
+                .await
+                .map_err(|e| format!("Failed to read stylesheet: {}", e))?;
+            css.push_str(&format!("\n@source inline(\"{}\");\n", classes.join(" ")));
+            fs::write(stylesheet, css)
+                .await
+                .map_err(|e| format!("Failed to write stylesheet: {}", e))?;
+            Ok(DEFAULT_CONFIG)
+        }
+    }
+}
+
+/// Reads the major version of the `tailwindcss` package installed in the
+/// workspace, if it can be determined.
+pub(crate) async fn installed_version(workspace_dir: &Path) -> Option<TailwindVersion> {
+    let manifest = workspace_dir
+        .join("node_modules")
+        .join("tailwindcss")
+        .join("package.json");
+    let contents = fs::read(manifest).await.ok()?;
+    let package: serde_json::Value = serde_json::from_slice(&contents).ok()?;
+
+    TailwindVersion::parse(package["version"].as_str()?)
+}
+
+/// Single-word utilities, which can't be told apart from ordinary words by
+/// shape alone.
+const BARE_UTILITIES: &[&str] = &[
+    "absolute",
+    "block",
+    "border",
+    "capitalize",
+    "container",
+    "contents",
+    "fixed",
+    "flex",
+    "grid",
+    "hidden",
+    "inline",
+    "italic",
+    "lowercase",
+    "relative",
+    "rounded",
+    "shadow",
+    "static",
+    "sticky",
+    "table",
+    "truncate",
+    "underline",
+    "uppercase",
+];
+
+/// Class names that appear in the sources' string literals but have no rule
+/// in `css`, usually because Tailwind never saw them as written (e.g. they
+/// were assembled at runtime). This is a heuristic: literals that merely look
+/// like classes may be reported too.
+pub(crate) fn purged_classes(sources: &[&str], css: &str) -> Vec<String> {
+    let generated = css_classes(css);
+    let mut purged = BTreeSet::new();
+
+    for source in sources {
+        let specifiers: HashSet<String> = import_specifiers(source).into_iter().collect();
+
+        for literal in string_literals(source) {
+            for token in literal.split_whitespace() {
+                if is_class_like(token) && !specifiers.contains(token) && !generated.contains(token)
+                {
+                    purged.insert(token.to_string());
+                }
+            }
+        }
+    }
+
+    purged.into_iter().collect()
+}
+
+/// Contents of every quoted or template literal. Interpolations are replaced
+/// with NUL so tokens built from them are never mistaken for whole classes.
+fn string_literals(source: &str) -> Vec<String> {
+    let mut literals = Vec::new();
+    let mut chars = source.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if !matches!(c, '"' | '\'' | '`') {
+            continue;
+        }
+
+        let mut literal = String::new();
+        while let Some(next) = chars.next() {
+            match next {
+                '\\' => {
+                    chars.next();
+                }
+                '$' if c == '`' && chars.peek() == Some(&'{') => {
+                    let mut depth = 0;
+                    for inner in chars.by_ref() {
+                        match inner {
+                            '{' => depth += 1,
+                            '}' if depth == 1 => break,
+                            '}' => depth -= 1,
+                            _ => {}
+                        }
+                    }
+                    literal.push('\0');
+                }
+                quote if quote == c => break,
+                other => literal.push(other),
+            }
+        }
+        literals.push(literal);
+    }
+
+    literals
+}
+
+fn is_class_like(token: &str) -> bool {
+    let shaped = token
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_lowercase() || matches!(c, '-' | '!'))
+        && token
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_:/[].#%!()".contains(c))
+        && !token.contains("//");
+
+    shaped && (token.contains(['-', ':']) || BARE_UTILITIES.contains(&token))
+}
+
+/// Class names defined by a stylesheet's selectors, with CSS escapes such as
+/// `hover\:bg-blue-500` undone.
+fn css_classes(css: &str) -> HashSet<String> {
+    let mut classes = HashSet::new();
+    let mut chars = css.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '.' {
+            continue;
+        }
+
+        let mut class = String::new();
+        while let Some(&next) = chars.peek() {
+            if next == '\\' {
+                chars.next();
+                if let Some(escaped) = chars.next() {
+                    class.push(escaped);
+                }
+            } else if next.is_ascii_alphanumeric() || matches!(next, '-' | '_') {
+                class.push(next);
+                chars.next();
+            } else {
+                break;
+            }
+        }
+
+        if !class.is_empty() {
+            classes.insert(class);
+        }
+    }
+
+    classes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_v3_args() {
+        let args =
+            TailwindVersion::V3.args(Path::new("in.css"), Path::new("out.css"), DEFAULT_CONFIG);
+
+        assert_eq!(
+            args,
+            [
+                "tailwindcss",
+                "-c",
+                "./tailwind.config.js",
+                "-i",
+                "in.css",
+                "-o",
+                "out.css"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_v4_args() {
+        let args =
+            TailwindVersion::V4.args(Path::new("in.css"), Path::new("out.css"), DEFAULT_CONFIG);
+
+        assert_eq!(args, ["@tailwindcss/cli", "-i", "in.css", "-o", "out.css"]);
+    }
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(TailwindVersion::parse("v3"), Some(TailwindVersion::V3));
+        assert_eq!(TailwindVersion::parse("4.1.10"), Some(TailwindVersion::V4));
+        assert_eq!(TailwindVersion::parse("^4.1.10"), Some(TailwindVersion::V4));
+        assert_eq!(TailwindVersion::parse("2"), None);
+    }
+
+    #[tokio::test]
+    async fn test_installed_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let package_dir = dir.path().join("node_modules").join("tailwindcss");
+        std::fs::create_dir_all(&package_dir).unwrap();
+        std::fs::write(package_dir.join("package.json"), r#"{"version":"3.4.17"}"#).unwrap();
+
+        assert_eq!(
+            installed_version(dir.path()).await,
+            Some(TailwindVersion::V3)
+        );
+    }
+
+    #[test]
+    fn test_dynamically_built_class_is_reported() {
+        let source = r#"
+import { cva } from "class-variance-authority";
+
+export default function Badge({ tone }) {
+  const shade = tone === "info" ? "bg-blue-500" : `bg-${tone}-500`;
+  return <span className={"px-2 hover:underline " + shade}>Badge</span>;
+}
+"#;
+        let css = r".px-2 { padding-inline: 0.5rem; } .hover\:underline:hover { text-decoration-line: underline; }";
+
+        assert_eq!(purged_classes(&[source], css), vec!["bg-blue-500"]);
+    }
+
+    #[test]
+    fn test_css_classes_unescape_selectors() {
+        let classes = css_classes(r".w-1\/2 { width: 50%; } .md\:flex { display: flex; }");
+
+        assert!(classes.contains("w-1/2"));
+        assert!(classes.contains("md:flex"));
+    }
+
+    #[tokio::test]
+    async fn test_v3_safelist_extends_template_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let classes = vec!["bg-red-500".to_string(), "md:grid-cols-3".to_string()];
+
+        let config = apply_safelist(
+            TailwindVersion::V3,
+            dir.path(),
+            Path::new("unused"),
+            &classes,
+        )
+        .await
+        .unwrap();
+        assert_eq!(config, SAFELIST_CONFIG);
+
+        let generated = std::fs::read_to_string(dir.path().join(config)).unwrap();
+        assert!(generated.contains(r#"require("./tailwind.config.js")"#));
+        assert!(generated.contains(r#"["bg-red-500","md:grid-cols-3"]"#));
+    }
+
+    #[tokio::test]
+    async fn test_v4_safelist_is_an_inline_source() {
+        let dir = tempfile::tempdir().unwrap();
+        let stylesheet = dir.path().join("globals.css");
+        std::fs::write(&stylesheet, "@import \"tailwindcss\";\n").unwrap();
+        let classes = vec!["bg-red-500".to_string(), "text-[13px]".to_string()];
+
+        let config = apply_safelist(TailwindVersion::V4, dir.path(), &stylesheet, &classes)
+            .await
+            .unwrap();
+        assert_eq!(config, DEFAULT_CONFIG);
+
+        let css = std::fs::read_to_string(&stylesheet).unwrap();
+        assert!(css.starts_with("@import \"tailwindcss\";"));
+        assert!(css.ends_with("@source inline(\"bg-red-500 text-[13px]\");\n"));
+    }
+
+    #[test]
+    fn test_safelist_class_validation() {
+        assert!(validate_safelist_class("hover:bg-blue-500").is_ok());
+        assert!(validate_safelist_class("w-[calc(100%-1rem)]").is_ok());
+        assert!(validate_safelist_class("a\");alert(1)//").is_err());
+        assert!(validate_safelist_class("two classes").is_err());
+        assert!(validate_safelist_class("{a,b}").is_err());
+        assert!(validate_safelist_class("").is_err());
+    }
+}
@@ -0,0 +1,222 @@
+//! Helpers for placing files inside a build workspace.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+use tokio::fs;
+
+/// An extra source file submitted alongside the component, written to `path`
+/// under `src`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct SourceFile {
+    pub path: String,
+    pub content: String,
+}
+
+/// Files the build generates itself, which submissions may not replace.
+const GENERATED_FILES: &[&str] = &["UserComponent.tsx", "index.tsx", "globals.css"];
+
+/// Rejects submissions that name the same file twice (however the path is
+/// spelled) or that would overwrite a generated file, including the
+/// `{name}.tsx` entry point of each of `entry_names`. Containment is checked
+/// separately by [`contained_path`] when the files are written.
+pub(crate) fn validate_files(files: &[SourceFile], entry_names: &[&str]) -> Result<(), String> {
+    let mut seen = HashSet::new();
+
+    for file in files {
+        let normalized = normalize_relative(&file.path);
+        if GENERATED_FILES.contains(&normalized.as_str()) {
+            return Err(format!("file path is reserved: {}", file.path));
+        }
+        if let Some(name) = entry_names
+            .iter()
+            .find(|name| format!("{}.tsx", name) == normalized)
+        {
+            return Err(format!(
+                "file path {} collides with the entry point of {}",
+                file.path, name
+            ));
+        }
+        if !seen.insert(normalized) {
+            return Err(format!("duplicate file path: {}", file.path));
+        }
+    }
+
+    Ok(())
+}
+
+/// How many path segments a submitted file may have when `MAX_PATH_DEPTH`
+/// isn't set.
+pub(crate) const DEFAULT_MAX_PATH_DEPTH: usize = 8;
+
+/// Rejects files nested more than `max_depth` segments deep (`a/b.tsx` has
+/// two), which would otherwise create arbitrarily deep directory trees.
+pub(crate) fn check_path_depth(files: &[SourceFile], max_depth: usize) -> Result<(), String> {
+    for file in files {
+        let depth = normalize_relative(&file.path).split('/').count();
+        if depth > max_depth {
+            return Err(format!(
+                "file path is nested too deeply ({} levels, allowed {}): {}",
+                depth, max_depth, file.path
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// `./a//b.tsx` and `a/b.tsx` name the same file.
+fn normalize_relative(path: &str) -> String {
+    Path::new(path)
+        .components()
+        .filter_map(|component| match component {
+            Component::CurDir => None,
+            other => Some(other.as_os_str().to_string_lossy().into_owned()),
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Resolves `relative` against `root`, refusing anything that could land
+/// outside it: absolute paths, `..` components and symlinks along the way.
+pub(crate) async fn contained_path(root: &Path, relative: &str) -> Result<PathBuf, String> {
+    let candidate = Path::new(relative);
+    if candidate.is_absolute() {
+        return Err(format!("path must be relative: {}", relative));
+    }
+
+    let mut resolved = root.to_path_buf();
+    for component in candidate.components() {
+        match component {
+            Component::Normal(part) => {
+                resolved.push(part);
+
+                let is_symlink = fs::symlink_metadata(&resolved)
+                    .await
+                    .is_ok_and(|meta| meta.file_type().is_symlink());
+                if is_symlink {
+                    return Err(format!("path may not pass through a symlink: {}", relative));
+                }
+            }
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+                return Err(format!("path escapes the workspace: {}", relative));
+            }
+        }
+    }
+
+    if resolved == root {
+        return Err(format!("path must name a file: {}", relative));
+    }
+
+    // The component checks above should already guarantee this; comparing the
+    // canonical parent catches anything they miss.
+    if let (Ok(root), Some(Ok(parent))) = (
+        fs::canonicalize(root).await,
+        resolved.parent().map(std::fs::canonicalize),
+    ) {
+        if !parent.starts_with(&root) {
+            return Err(format!("path escapes the workspace: {}", relative));
+        }
+    }
+
+    Ok(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_nested_relative_path_is_allowed() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let path = contained_path(dir.path(), "./components/Button.tsx")
+            .await
+            .unwrap();
+
+        assert_eq!(path, dir.path().join("components").join("Button.tsx"));
+    }
+
+    #[tokio::test]
+    async fn test_absolute_path_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let err = contained_path(dir.path(), "/etc/passwd").await.unwrap_err();
+
+        assert_eq!(err, "path must be relative: /etc/passwd");
+    }
+
+    #[tokio::test]
+    async fn test_parent_dir_escape_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let err = contained_path(dir.path(), "components/../../outside.tsx")
+            .await
+            .unwrap_err();
+
+        assert_eq!(
+            err,
+            "path escapes the workspace: components/../../outside.tsx"
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_symlink_escape_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        std::os::unix::fs::symlink(outside.path(), dir.path().join("link")).unwrap();
+
+        let err = contained_path(dir.path(), "link/file.tsx")
+            .await
+            .unwrap_err();
+
+        assert!(err.starts_with("path may not pass through a symlink"));
+    }
+
+    fn file(path: &str) -> SourceFile {
+        SourceFile {
+            path: path.to_string(),
+            content: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_duplicate_file_paths_are_rejected() {
+        assert!(validate_files(&[file("Button.tsx"), file("components/Card.tsx")], &[]).is_ok());
+
+        let err = validate_files(
+            &[file("components/Card.tsx"), file("./components//Card.tsx")],
+            &[],
+        )
+        .unwrap_err();
+        assert_eq!(err, "duplicate file path: ./components//Card.tsx");
+
+        assert!(validate_files(&[file("./index.tsx")], &[]).is_err());
+    }
+
+    #[test]
+    fn test_file_colliding_with_entry_is_rejected() {
+        let err = validate_files(&[file("./Card.tsx")], &["Card"]).unwrap_err();
+        assert_eq!(
+            err,
+            "file path ./Card.tsx collides with the entry point of Card"
+        );
+
+        assert!(validate_files(&[file("components/Card.tsx")], &["Card"]).is_ok());
+    }
+
+    #[test]
+    fn test_over_deep_path_is_rejected() {
+        assert!(check_path_depth(&[file("components/ui/Card.tsx")], 3).is_ok());
+        assert!(check_path_depth(&[file("./components//ui/Card.tsx")], 3).is_ok());
+
+        let err =
+            check_path_depth(&[file("a/b/c/d/e/f/g/h/x.tsx")], DEFAULT_MAX_PATH_DEPTH).unwrap_err();
+        assert_eq!(
+            err,
+            "file path is nested too deeply (9 levels, allowed 8): a/b/c/d/e/f/g/h/x.tsx"
+        );
+    }
+}